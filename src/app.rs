@@ -19,10 +19,11 @@ impl WasabiApplication {
     pub fn new() -> Self {
         // Load the settings values
         let state = WasabiState::new();
-        let settings = WasabiSettings::new_or_load().unwrap_or_else(|e| {
+        let mut settings = WasabiSettings::new_or_load().unwrap_or_else(|e| {
             state.errors.error(&e);
             WasabiSettings::default()
         });
+        settings.gui.prune_recent_files();
         settings
             .save_to_file()
             .unwrap_or_else(|e| state.errors.error(&e));
@@ -89,6 +90,7 @@ impl ApplicationHandler for WasabiApplication {
                     renderer.resize(None);
                 }
                 WindowEvent::CloseRequested => {
+                    renderer.gui_window().save_file_states(&self.state);
                     event_loop.exit();
                 }
                 WindowEvent::DroppedFile(path) => {
@@ -0,0 +1,99 @@
+use atomic_float::AtomicF64;
+use std::{sync::atomic::Ordering, time::Instant};
+
+/// Roughly how long the clock takes to follow a change of the sample rate, in seconds
+const SMOOTHING_TIME: f64 = 0.5;
+/// The time without a callback after which the clock runs on the wall clock
+const STALL_TIMEOUT: f64 = 0.25;
+
+/// A clock that follows the samples played by the audio device of an XSynth output.
+/// The output stream advances it from its callback, and while no stream does, it
+/// runs on the wall clock. Reading it never locks.
+#[derive(Debug)]
+pub struct AudioClock {
+    epoch: Instant,
+    /// The seconds of audio played, plus the time the clock ran on the wall clock
+    played: AtomicF64,
+    /// The length of the last block of samples, in seconds
+    last_block: AtomicF64,
+    /// When the last block was played, in seconds since `epoch`
+    last_callback: AtomicF64,
+    /// The smoothed difference between the audio time and the wall clock,
+    /// or NaN before the first read
+    offset: AtomicF64,
+    /// The wall clock time of the last read
+    last_read: AtomicF64,
+    last: AtomicF64,
+}
+
+impl AudioClock {
+    pub fn new() -> Self {
+        Self {
+            epoch: Instant::now(),
+            played: AtomicF64::new(0.0),
+            last_block: AtomicF64::new(0.0),
+            last_callback: AtomicF64::new(0.0),
+            offset: AtomicF64::new(f64::NAN),
+            last_read: AtomicF64::new(0.0),
+            last: AtomicF64::new(0.0),
+        }
+    }
+
+    /// Called by the audio callback with the length of the block it played
+    pub fn advance(&self, seconds: f64) {
+        let wall = self.epoch.elapsed().as_secs_f64();
+
+        // The clock ran on the wall clock since the last block, so carry on from there
+        let gap = wall - self.last_callback.load(Ordering::Relaxed);
+        if gap >= STALL_TIMEOUT {
+            self.played.fetch_add(gap, Ordering::Relaxed);
+        }
+
+        self.played.fetch_add(seconds, Ordering::Relaxed);
+        self.last_block.store(seconds, Ordering::Relaxed);
+        self.last_callback.store(wall, Ordering::Relaxed);
+    }
+
+    /// The seconds of audio played since the clock was created. The samples arrive
+    /// in chunks, so the wall clock fills the time between them and the difference
+    /// is smoothed out.
+    pub fn now(&self) -> f64 {
+        let wall = self.epoch.elapsed().as_secs_f64();
+
+        let played = self.played.load(Ordering::Relaxed);
+        let last_block = self.last_block.load(Ordering::Relaxed);
+        let last_callback = self.last_callback.load(Ordering::Relaxed);
+
+        // Without callbacks the device is stalled or gone, so keep running on the wall clock
+        let since_callback = (wall - last_callback).max(0.0);
+        let since_callback = if since_callback < STALL_TIMEOUT {
+            since_callback.min(last_block)
+        } else {
+            since_callback
+        };
+        let target = played + since_callback - wall;
+
+        // Smoothed by the time between reads, as the clock is read from several threads.
+        // Concurrent reads may overwrite each other's smoothing step, which only slows
+        // it down a little.
+        let offset = self.offset.load(Ordering::Relaxed);
+        let last_read = self.last_read.swap(wall, Ordering::Relaxed);
+        let offset = if offset.is_nan() {
+            target
+        } else {
+            let factor = 1.0 - (-(wall - last_read).max(0.0) / SMOOTHING_TIME).exp();
+            offset + (target - offset) * factor
+        };
+        self.offset.store(offset, Ordering::Relaxed);
+
+        // Never go back in time
+        let now = wall + offset;
+        now.max(self.last.fetch_max(now, Ordering::Relaxed))
+    }
+}
+
+impl Default for AudioClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
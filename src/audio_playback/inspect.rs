@@ -0,0 +1,546 @@
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fs::{self, File},
+    io::{self, BufReader, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use xsynth_core::{soundfont::SampleSoundfont, AudioStreamParams};
+
+use crate::{gui::window::WasabiError, settings::WasabiSoundfont};
+
+/// The size of a decoded sample point, as samples are decoded to `f32`
+const DECODED_SAMPLE_SIZE: u64 = 4;
+/// How deep `#include` directives are followed in SFZ files
+const MAX_INCLUDE_DEPTH: usize = 16;
+/// How many problems of the same kind are listed before they are summarized
+const MAX_LISTED_WARNINGS: usize = 20;
+
+/// SFZ opcodes that XSynth reads. Other opcodes are ignored when loading.
+const SUPPORTED_SFZ_OPCODES: &[&str] = &[
+    "sample",
+    "default_path",
+    "lokey",
+    "hikey",
+    "key",
+    "pitch_keycenter",
+    "lovel",
+    "hivel",
+    "volume",
+    "pan",
+    "tune",
+    "transpose",
+    "offset",
+    "loop_mode",
+    "loopmode",
+    "loop_start",
+    "loopstart",
+    "loop_end",
+    "loopend",
+    "cutoff",
+    "resonance",
+    "fil_type",
+    "fil_keycenter",
+    "fil_keytrack",
+    "fil_veltrack",
+    "amp_veltrack",
+    "ampeg_delay",
+    "ampeg_start",
+    "ampeg_attack",
+    "ampeg_hold",
+    "ampeg_decay",
+    "ampeg_sustain",
+    "ampeg_release",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SoundfontFormat {
+    Sf2,
+    Sfz,
+}
+
+impl SoundfontFormat {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            SoundfontFormat::Sf2 => "SoundFont 2 (SF2)",
+            SoundfontFormat::Sfz => "SFZ",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PresetInfo {
+    pub bank: u16,
+    pub program: u16,
+    pub name: String,
+}
+
+/// What could be read from a soundfont without loading it
+#[derive(Debug, Clone)]
+pub struct SoundfontInfo {
+    pub format: SoundfontFormat,
+    /// The presets of an SF2. SFZ files are a single instrument.
+    pub presets: Vec<PresetInfo>,
+    pub sample_count: usize,
+    /// The estimated memory of the decoded samples at the sample rate it was inspected for
+    pub decoded_bytes: u64,
+    pub sample_rate: u32,
+    pub warnings: Vec<String>,
+}
+
+fn invalid(message: impl Into<String>) -> WasabiError {
+    WasabiError::FilesystemError(io::Error::new(io::ErrorKind::InvalidData, message.into()))
+}
+
+/// Adds the warnings of one kind, summarizing them if there are too many
+fn push_warnings(warnings: &mut Vec<String>, items: Vec<String>) {
+    let count = items.len();
+    warnings.extend(items.into_iter().take(MAX_LISTED_WARNINGS));
+    if count > MAX_LISTED_WARNINGS {
+        warnings.push(format!("...and {} more", count - MAX_LISTED_WARNINGS));
+    }
+}
+
+/// The size of the decoded audio, resampled to the output sample rate
+fn decoded_size(frames: u64, channels: u64, rate: u32, output_rate: u32) -> u64 {
+    if rate == 0 {
+        return 0;
+    }
+    frames * channels * output_rate as u64 / rate as u64 * DECODED_SAMPLE_SIZE
+}
+
+/// Reads the format, presets and samples of a soundfont, for the given output sample rate
+pub fn inspect_soundfont(path: &Path, sample_rate: u32) -> Result<SoundfontInfo, WasabiError> {
+    let extension = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    match extension.as_str() {
+        "sf2" => inspect_sf2(path, sample_rate),
+        "sfz" => inspect_sfz(path, sample_rate),
+        _ => Err(invalid("Unsupported soundfont format")),
+    }
+}
+
+/// Loads the soundfont like the synth would and returns how long it took
+pub fn measure_load_time(
+    sf: &WasabiSoundfont,
+    stream_params: AudioStreamParams,
+) -> Result<Duration, WasabiError> {
+    let start = Instant::now();
+    SampleSoundfont::new(&sf.path, stream_params, sf.options)
+        .map_err(WasabiError::SoundFontLoadError)?;
+    Ok(start.elapsed())
+}
+
+// region: sf2
+
+struct RiffChunk {
+    id: [u8; 4],
+    size: u32,
+    offset: u64,
+}
+
+fn out_of_range(chunk: &RiffChunk) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!(
+            "The \"{}\" chunk extends past the end of its data",
+            String::from_utf8_lossy(&chunk.id)
+        ),
+    )
+}
+
+fn read_chunk_header(reader: &mut impl Read, offset: u64) -> io::Result<RiffChunk> {
+    let mut header = [0u8; 8];
+    reader.read_exact(&mut header)?;
+    Ok(RiffChunk {
+        id: [header[0], header[1], header[2], header[3]],
+        size: u32::from_le_bytes([header[4], header[5], header[6], header[7]]),
+        offset: offset + 8,
+    })
+}
+
+/// Lists the sub-chunks of a `LIST` chunk, skipping its type
+fn list_chunks(
+    reader: &mut (impl Read + Seek),
+    list: &RiffChunk,
+) -> io::Result<HashMap<[u8; 4], RiffChunk>> {
+    let mut chunks = HashMap::new();
+    let end = list.offset + list.size as u64;
+    let mut pos = list.offset + 4;
+
+    while pos + 8 <= end {
+        reader.seek(SeekFrom::Start(pos))?;
+        let chunk = read_chunk_header(reader, pos)?;
+        if chunk.offset + chunk.size as u64 > end {
+            return Err(out_of_range(&chunk));
+        }
+        // Chunks are padded to an even size
+        pos = chunk.offset + chunk.size as u64 + (chunk.size as u64 & 1);
+        chunks.insert(chunk.id, chunk);
+    }
+
+    Ok(chunks)
+}
+
+/// Reads the data of a chunk, checking its size against the file first, as it
+/// comes from the file and is allocated up front
+fn read_chunk(reader: &mut (impl Read + Seek), chunk: &RiffChunk) -> io::Result<Vec<u8>> {
+    let len = reader.seek(SeekFrom::End(0))?;
+    if chunk.offset + chunk.size as u64 > len {
+        return Err(out_of_range(chunk));
+    }
+    reader.seek(SeekFrom::Start(chunk.offset))?;
+    let mut data = vec![0; chunk.size as usize];
+    reader.read_exact(&mut data)?;
+    Ok(data)
+}
+
+fn read_name(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).trim().to_owned()
+}
+
+fn u16_at(bytes: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([bytes[at], bytes[at + 1]])
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+}
+
+fn inspect_sf2(path: &Path, sample_rate: u32) -> Result<SoundfontInfo, WasabiError> {
+    const PHDR_SIZE: usize = 38;
+    const SHDR_SIZE: usize = 46;
+    const ROM_SAMPLE: u16 = 0x8000;
+    const COMPRESSED_SAMPLE: u16 = 0x10;
+
+    let mut reader = BufReader::new(File::open(path).map_err(WasabiError::FilesystemError)?);
+    let riff = read_chunk_header(&mut reader, 0).map_err(WasabiError::FilesystemError)?;
+    let mut form = [0u8; 4];
+    reader
+        .read_exact(&mut form)
+        .map_err(WasabiError::FilesystemError)?;
+    if &riff.id != b"RIFF" || &form != b"sfbk" {
+        return Err(invalid("Not a valid SF2 file"));
+    }
+
+    // Find the sample data and preset data lists
+    let mut sdta = None;
+    let mut pdta = None;
+    let end = riff.offset + riff.size as u64;
+    let mut pos = riff.offset + 4;
+    while pos + 12 <= end {
+        reader
+            .seek(SeekFrom::Start(pos))
+            .map_err(WasabiError::FilesystemError)?;
+        let chunk = read_chunk_header(&mut reader, pos).map_err(WasabiError::FilesystemError)?;
+        let mut list_type = [0u8; 4];
+        reader
+            .read_exact(&mut list_type)
+            .map_err(WasabiError::FilesystemError)?;
+        pos = chunk.offset + chunk.size as u64 + (chunk.size as u64 & 1);
+        if &chunk.id == b"LIST" {
+            match &list_type {
+                b"sdta" => sdta = Some(chunk),
+                b"pdta" => pdta = Some(chunk),
+                _ => {}
+            }
+        }
+    }
+
+    let pdta = pdta.ok_or_else(|| invalid("The SF2 has no preset data"))?;
+    let pdta = list_chunks(&mut reader, &pdta).map_err(WasabiError::FilesystemError)?;
+
+    let mut warnings = Vec::new();
+
+    // The sample data is 16 bit, so the length in sample points is half its size
+    let sample_points = match sdta {
+        Some(sdta) => list_chunks(&mut reader, &sdta)
+            .map_err(WasabiError::FilesystemError)?
+            .get(b"smpl")
+            .map(|smpl| smpl.size as u64 / 2),
+        None => None,
+    };
+    if sample_points.is_none() {
+        warnings.push("The SF2 has no sample data".to_owned());
+    }
+
+    let mut presets = Vec::new();
+    if let Some(phdr) = pdta.get(b"phdr") {
+        let data = read_chunk(&mut reader, phdr).map_err(WasabiError::FilesystemError)?;
+        // The last record only marks the end of the list
+        let count = (data.len() / PHDR_SIZE).saturating_sub(1);
+        for record in data.chunks_exact(PHDR_SIZE).take(count) {
+            presets.push(PresetInfo {
+                name: read_name(&record[0..20]),
+                program: u16_at(record, 20),
+                bank: u16_at(record, 22),
+            });
+        }
+        presets.sort_by_key(|p| (p.bank, p.program));
+    } else {
+        warnings.push("The SF2 has no preset headers".to_owned());
+    }
+
+    let mut sample_count = 0;
+    let mut decoded_bytes = 0;
+    let mut out_of_range = Vec::new();
+    let mut unsupported = Vec::new();
+    if let Some(shdr) = pdta.get(b"shdr") {
+        let data = read_chunk(&mut reader, shdr).map_err(WasabiError::FilesystemError)?;
+        let count = (data.len() / SHDR_SIZE).saturating_sub(1);
+        for record in data.chunks_exact(SHDR_SIZE).take(count) {
+            let name = read_name(&record[0..20]);
+            let start = u32_at(record, 20) as u64;
+            let end = u32_at(record, 24) as u64;
+            let rate = u32_at(record, 36);
+            let sample_type = u16_at(record, 44);
+
+            sample_count += 1;
+            if sample_type & ROM_SAMPLE != 0 {
+                unsupported.push(format!("Sample \"{name}\" is stored in ROM"));
+            } else if sample_type & COMPRESSED_SAMPLE != 0 {
+                unsupported.push(format!("Sample \"{name}\" is compressed (SF3)"));
+            } else if end < start || sample_points.is_some_and(|points| end > points) {
+                out_of_range.push(format!("Sample \"{name}\" is outside of the sample data"));
+            } else if rate == 0 {
+                out_of_range.push(format!("Sample \"{name}\" has no sample rate"));
+            } else {
+                decoded_bytes += decoded_size(end - start, 1, rate, sample_rate);
+            }
+        }
+    } else {
+        warnings.push("The SF2 has no sample headers".to_owned());
+    }
+    push_warnings(&mut warnings, out_of_range);
+    push_warnings(&mut warnings, unsupported);
+
+    Ok(SoundfontInfo {
+        format: SoundfontFormat::Sf2,
+        presets,
+        sample_count,
+        decoded_bytes,
+        sample_rate,
+        warnings,
+    })
+}
+
+// endregion
+
+// region: sfz
+
+/// Reads an SFZ file with its includes, and applies the defines
+fn read_sfz_text(
+    path: &Path,
+    root: &Path,
+    defines: &mut Vec<(String, String)>,
+    depth: usize,
+    warnings: &mut Vec<String>,
+) -> Result<String, WasabiError> {
+    let content = fs::read_to_string(path).map_err(WasabiError::FilesystemError)?;
+    let mut out = String::new();
+
+    for line in content.lines() {
+        let line = line.split("//").next().unwrap_or_default();
+        let mut line = line.to_owned();
+        for (name, value) in defines.iter() {
+            line = line.replace(name.as_str(), value);
+        }
+        let trimmed = line.trim();
+
+        if let Some(define) = trimmed.strip_prefix("#define") {
+            let mut parts = define.split_whitespace();
+            if let (Some(name), Some(value)) = (parts.next(), parts.next()) {
+                defines.push((name.to_owned(), value.to_owned()));
+            }
+        } else if let Some(include) = trimmed.strip_prefix("#include") {
+            let include = root.join(include.trim().trim_matches('"').replace('\\', "/"));
+            if depth >= MAX_INCLUDE_DEPTH {
+                warnings.push(format!("Includes are nested too deeply at {include:?}"));
+            } else if include.exists() {
+                out += &read_sfz_text(&include, root, defines, depth + 1, warnings)?;
+            } else {
+                warnings.push(format!("Missing include: {include:?}"));
+            }
+        } else {
+            out += &line;
+            out.push('\n');
+        }
+    }
+
+    Ok(out)
+}
+
+/// The opcodes of each header in the file, as (header, opcodes) pairs
+fn parse_sfz_headers(text: &str) -> Vec<(String, Vec<(String, String)>)> {
+    let mut headers: Vec<(String, Vec<(String, String)>)> = Vec::new();
+
+    for line in text.lines() {
+        // Values like sample paths can contain spaces, so words without `=` are
+        // part of the previous value
+        let mut rest = line.trim();
+        while !rest.is_empty() {
+            if let Some(header) = rest.strip_prefix('<') {
+                let end = header.find('>').unwrap_or(header.len());
+                headers.push((header[..end].to_owned(), Vec::new()));
+                rest = header[(end + 1).min(header.len())..].trim_start();
+                continue;
+            }
+
+            let end = rest
+                .find(|c: char| c.is_whitespace() || c == '<')
+                .unwrap_or(rest.len());
+            let word = &rest[..end];
+            rest = rest[end..].trim_start();
+
+            let Some((_, opcodes)) = headers.last_mut() else {
+                continue;
+            };
+            match word.split_once('=') {
+                Some((key, value)) => opcodes.push((key.to_owned(), value.to_owned())),
+                None => {
+                    if let Some((_, value)) = opcodes.last_mut() {
+                        value.push(' ');
+                        value.push_str(word);
+                    }
+                }
+            }
+        }
+    }
+
+    headers
+}
+
+/// The frames, channels and sample rate of a WAV file, read from its header
+fn wav_info(path: &Path) -> io::Result<(u64, u64, u32)> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let riff = read_chunk_header(&mut reader, 0)?;
+    let mut form = [0u8; 4];
+    reader.read_exact(&mut form)?;
+    if &riff.id != b"RIFF" || &form != b"WAVE" {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Not a WAV file"));
+    }
+
+    let mut format = None;
+    let mut pos = 12;
+    loop {
+        reader.seek(SeekFrom::Start(pos))?;
+        let chunk = read_chunk_header(&mut reader, pos)?;
+        match &chunk.id {
+            b"fmt " => {
+                let data = read_chunk(&mut reader, &chunk)?;
+                if data.len() < 16 {
+                    break;
+                }
+                // Channels, sample rate and bytes per frame
+                format = Some((u16_at(&data, 2), u32_at(&data, 4), u16_at(&data, 12)));
+            }
+            b"data" => {
+                let (channels, rate, block_align) = format
+                    .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "No WAV format"))?;
+                let frames = chunk.size as u64 / block_align.max(1) as u64;
+                return Ok((frames, channels as u64, rate));
+            }
+            _ => {}
+        }
+        pos = chunk.offset + chunk.size as u64 + (chunk.size as u64 & 1);
+    }
+
+    Err(io::Error::new(io::ErrorKind::InvalidData, "No WAV data"))
+}
+
+fn inspect_sfz(path: &Path, sample_rate: u32) -> Result<SoundfontInfo, WasabiError> {
+    let root = path.parent().unwrap_or(Path::new("./"));
+    let mut warnings = Vec::new();
+    let text = read_sfz_text(path, root, &mut Vec::new(), 0, &mut warnings)?;
+
+    let mut default_path = String::new();
+    // The sample set by the enclosing <global>, <master> and <group> headers
+    let mut inherited: [Option<String>; 3] = Default::default();
+    let mut samples: Vec<PathBuf> = Vec::new();
+    let mut unsupported = BTreeSet::new();
+
+    for (header, opcodes) in parse_sfz_headers(&text) {
+        let mut sample = None;
+        for (key, value) in opcodes {
+            match key.as_str() {
+                "default_path" => default_path = value.replace('\\', "/"),
+                "sample" => sample = Some(value),
+                key if !SUPPORTED_SFZ_OPCODES.contains(&key) => {
+                    unsupported.insert(key.to_owned());
+                }
+                _ => {}
+            }
+        }
+
+        let level = match header.as_str() {
+            "global" => Some(0),
+            "master" => Some(1),
+            "group" => Some(2),
+            _ => None,
+        };
+        if let Some(level) = level {
+            inherited[level] = sample;
+            // A new header resets the ones nested in it
+            for nested in inherited.iter_mut().skip(level + 1) {
+                *nested = None;
+            }
+        } else if header == "region" {
+            let sample = sample.or_else(|| inherited.iter().rev().flatten().next().cloned());
+            if let Some(sample) = sample {
+                // Generated samples like *sine don't come from a file
+                if !sample.starts_with('*') {
+                    let sample = format!("{default_path}{}", sample.replace('\\', "/"));
+                    samples.push(root.join(sample));
+                }
+            }
+        }
+    }
+
+    let unique: HashSet<&PathBuf> = samples.iter().collect();
+    let mut missing = Vec::new();
+    let mut unmeasured = 0;
+    let mut decoded_bytes = 0;
+    for sample in unique.iter() {
+        if !sample.exists() {
+            missing.push(format!("Missing sample: {sample:?}"));
+        } else {
+            match wav_info(sample) {
+                Ok((frames, channels, rate)) => {
+                    decoded_bytes += decoded_size(frames, channels, rate, sample_rate)
+                }
+                Err(_) => unmeasured += 1,
+            }
+        }
+    }
+    missing.sort();
+    push_warnings(&mut warnings, missing);
+
+    if unmeasured > 0 {
+        warnings.push(format!(
+            "The memory of {unmeasured} compressed or unreadable samples is not included in the estimate"
+        ));
+    }
+    if !unsupported.is_empty() {
+        let opcodes: Vec<String> = unsupported.into_iter().collect();
+        warnings.push(format!("Unsupported opcodes: {}", opcodes.join(", ")));
+    }
+    if samples.is_empty() {
+        warnings.push("The SFZ has no regions with samples".to_owned());
+    }
+
+    Ok(SoundfontInfo {
+        format: SoundfontFormat::Sfz,
+        presets: Vec::new(),
+        sample_count: unique.len(),
+        decoded_bytes,
+        sample_rate,
+        warnings,
+    })
+}
+
+// endregion
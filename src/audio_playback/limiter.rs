@@ -0,0 +1,350 @@
+use std::time::{Duration, Instant};
+
+use crate::settings::{LimiterPriority, NoteLimiterSettings};
+
+const NOTE_OFF: u32 = 0x80;
+const NOTE_ON: u32 = 0x90;
+
+fn is_note_on(event: u32) -> bool {
+    event & 0xF0 == NOTE_ON && (event >> 16) & 0x7F > 0
+}
+
+fn is_note_off(event: u32) -> bool {
+    event & 0xF0 == NOTE_OFF || (event & 0xF0 == NOTE_ON && (event >> 16) & 0x7F == 0)
+}
+
+fn key_of(event: u32) -> (usize, usize) {
+    ((event & 0xF) as usize, ((event >> 8) & 0x7F) as usize)
+}
+
+/// Drops the note ons that go over the limits of a time slice, so the synths
+/// can keep up with very dense MIDIs. The note ons of a block of events that are
+/// sent at the same time are the candidates for the space left in the slice,
+/// and the priority decides which of them are kept. Notes of earlier blocks have
+/// already been sent, so they are never displaced.
+/// The note offs of the dropped notes are dropped too, so they don't release
+/// other notes on the same key.
+pub struct NoteLimiter {
+    enabled: bool,
+    slice: Duration,
+    max_notes: u32,
+    max_per_key: u32,
+    priority: LimiterPriority,
+
+    slice_start: Instant,
+    slice_notes: u32,
+    key_notes: [[u32; 128]; 16],
+    /// The note offs to skip on each key, one for every dropped note on
+    skipped_offs: [[u32; 128]; 16],
+    dropped: u64,
+
+    events: Vec<u32>,
+    /// The indices of the note ons of a block, in the order they are let through
+    note_ons: Vec<usize>,
+    kept: Vec<bool>,
+}
+
+impl NoteLimiter {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            slice: Duration::ZERO,
+            max_notes: u32::MAX,
+            max_per_key: u32::MAX,
+            priority: LimiterPriority::default(),
+
+            slice_start: Instant::now(),
+            slice_notes: 0,
+            key_notes: [[0; 128]; 16],
+            skipped_offs: [[0; 128]; 16],
+            dropped: 0,
+
+            events: Vec::new(),
+            note_ons: Vec::new(),
+            kept: Vec::new(),
+        }
+    }
+
+    pub fn configure(&mut self, settings: &NoteLimiterSettings) {
+        if self.enabled && !settings.enabled {
+            // The pending note offs must reach the synth again
+            self.skipped_offs = [[0; 128]; 16];
+        }
+
+        self.enabled = settings.enabled;
+        self.slice = Duration::from_secs_f64(settings.slice.max(0.0) / 1000.0);
+        self.max_notes = settings.max_notes;
+        self.max_per_key = settings.max_per_key;
+        self.priority = settings.priority;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// The note ons that were dropped, or `None` if the limiter is disabled
+    pub fn dropped_notes(&self) -> Option<u64> {
+        self.enabled.then_some(self.dropped)
+    }
+
+    /// Forgets the held notes, to match a synth that was reset
+    pub fn reset(&mut self) {
+        self.skipped_offs = [[0; 128]; 16];
+        self.slice_notes = 0;
+        self.key_notes = [[0; 128]; 16];
+    }
+
+    fn update_slice(&mut self, now: Instant) {
+        if now.duration_since(self.slice_start) >= self.slice {
+            self.slice_start = now;
+            self.slice_notes = 0;
+            self.key_notes = [[0; 128]; 16];
+        }
+    }
+
+    /// Limits a block of events that are sent at the same time
+    pub fn process(&mut self, data: impl Iterator<Item = u32>) -> impl Iterator<Item = u32> + '_ {
+        self.process_at(Instant::now(), data)
+    }
+
+    fn process_at(
+        &mut self,
+        now: Instant,
+        data: impl Iterator<Item = u32>,
+    ) -> impl Iterator<Item = u32> + '_ {
+        self.update_slice(now);
+
+        self.events.clear();
+        self.events.extend(data);
+
+        self.note_ons.clear();
+        self.note_ons.extend(
+            self.events
+                .iter()
+                .enumerate()
+                .filter(|(_, event)| is_note_on(**event))
+                .map(|(i, _)| i),
+        );
+        match self.priority {
+            // The sort is stable, so notes of the same velocity keep their order
+            LimiterPriority::Loudest => {
+                let events = &self.events;
+                self.note_ons
+                    .sort_by_key(|i| std::cmp::Reverse((events[*i] >> 16) & 0x7F));
+            }
+            LimiterPriority::Newest => self.note_ons.reverse(),
+        }
+
+        self.kept.clear();
+        self.kept.resize(self.events.len(), false);
+        for &i in self.note_ons.iter() {
+            let (channel, key) = key_of(self.events[i]);
+            if self.slice_notes < self.max_notes && self.key_notes[channel][key] < self.max_per_key
+            {
+                self.slice_notes += 1;
+                self.key_notes[channel][key] += 1;
+                self.kept[i] = true;
+            }
+        }
+
+        // Go through the block in order, so only the note offs that come
+        // after a dropped note on are skipped
+        let mut i = 0;
+        self.events.retain(|&event| {
+            let kept = self.kept[i];
+            i += 1;
+
+            if is_note_on(event) {
+                if !kept {
+                    let (channel, key) = key_of(event);
+                    self.skipped_offs[channel][key] += 1;
+                    self.dropped += 1;
+                }
+                kept
+            } else if is_note_off(event) {
+                let (channel, key) = key_of(event);
+                let skipped = &mut self.skipped_offs[channel][key];
+                if *skipped > 0 {
+                    *skipped -= 1;
+                    false
+                } else {
+                    true
+                }
+            } else {
+                true
+            }
+        });
+
+        self.events.iter().copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note_on(channel: u32, key: u32, vel: u32) -> u32 {
+        0x90 | channel | (key << 8) | (vel << 16)
+    }
+
+    fn note_off(channel: u32, key: u32) -> u32 {
+        0x80 | channel | (key << 8)
+    }
+
+    fn limiter(max_notes: u32, max_per_key: u32) -> NoteLimiter {
+        limiter_with(LimiterPriority::default(), max_notes, max_per_key)
+    }
+
+    fn limiter_with(priority: LimiterPriority, max_notes: u32, max_per_key: u32) -> NoteLimiter {
+        let mut limiter = NoteLimiter::new();
+        limiter.configure(&NoteLimiterSettings {
+            enabled: true,
+            slice: 10.0,
+            max_notes,
+            max_per_key,
+            priority,
+        });
+        limiter
+    }
+
+    fn process(limiter: &mut NoteLimiter, now: Instant, events: &[u32]) -> Vec<u32> {
+        limiter.process_at(now, events.iter().copied()).collect()
+    }
+
+    #[test]
+    fn caps_the_notes_of_a_slice() {
+        let mut limiter = limiter(3, u32::MAX);
+        let start = Instant::now();
+
+        let events: Vec<u32> = (0..5).map(|key| note_on(0, key, 100)).collect();
+        assert_eq!(process(&mut limiter, start, &events), events[..3]);
+
+        // The slice is still full until it ends
+        let late = [note_on(1, 60, 127)];
+        let within = start + Duration::from_millis(5);
+        assert!(process(&mut limiter, within, &late).is_empty());
+
+        let next = start + Duration::from_millis(10);
+        assert_eq!(process(&mut limiter, next, &late), late);
+        assert_eq!(limiter.dropped_notes(), Some(3));
+    }
+
+    #[test]
+    fn caps_the_notes_of_a_key() {
+        let mut limiter = limiter(u32::MAX, 2);
+        let now = Instant::now();
+
+        let events = [
+            note_on(0, 60, 100),
+            note_on(0, 60, 100),
+            note_on(0, 60, 100),
+            // Other channels and keys have their own caps
+            note_on(1, 60, 100),
+            note_on(0, 61, 100),
+        ];
+        assert_eq!(
+            process(&mut limiter, now, &events),
+            [events[0], events[1], events[3], events[4]]
+        );
+        assert_eq!(limiter.dropped_notes(), Some(1));
+    }
+
+    #[test]
+    fn skips_the_note_offs_of_dropped_notes() {
+        let mut limiter = limiter(u32::MAX, 1);
+        let start = Instant::now();
+
+        let events = [note_on(0, 60, 100), note_on(0, 60, 90)];
+        assert_eq!(process(&mut limiter, start, &events), [events[0]]);
+
+        // The first note off belongs to the dropped note, the second one to the kept note
+        let next = start + Duration::from_millis(10);
+        let offs = [note_off(0, 60), note_on(0, 60, 0)];
+        assert_eq!(process(&mut limiter, next, &offs), [offs[1]]);
+
+        // Note offs of other keys and other events are never skipped
+        let later = next + Duration::from_millis(10);
+        let others = [
+            note_on(0, 60, 100),
+            note_on(0, 60, 100),
+            note_off(0, 61),
+            0xB0 | (7 << 8),
+        ];
+        assert_eq!(
+            process(&mut limiter, later, &others),
+            [others[0], others[2], others[3]]
+        );
+    }
+
+    #[test]
+    fn reset_forgets_the_skipped_note_offs() {
+        let mut limiter = limiter(u32::MAX, 1);
+        let now = Instant::now();
+
+        let _ = process(
+            &mut limiter,
+            now,
+            &[note_on(0, 60, 100), note_on(0, 60, 100)],
+        );
+        limiter.reset();
+        assert_eq!(
+            process(&mut limiter, now, &[note_off(0, 60)]),
+            [note_off(0, 60)]
+        );
+    }
+
+    #[test]
+    fn keeps_the_loudest_notes() {
+        let mut limiter = limiter_with(LimiterPriority::Loudest, 3, 2);
+        let start = Instant::now();
+
+        let events = [
+            note_on(0, 60, 40),
+            note_on(0, 61, 100),
+            note_on(0, 60, 90),
+            note_on(0, 60, 120),
+            note_off(0, 60),
+            note_on(0, 62, 20),
+        ];
+        assert_eq!(
+            process(&mut limiter, start, &events),
+            [events[1], events[2], events[3]]
+        );
+        assert_eq!(limiter.dropped_notes(), Some(2));
+
+        // Only the space left in the slice is shared among the next block
+        let next = start + Duration::from_millis(10);
+        let _ = process(
+            &mut limiter,
+            next,
+            &[note_on(1, 0, 127), note_on(1, 1, 127)],
+        );
+        let within = next + Duration::from_millis(5);
+        let events = [note_on(1, 2, 30), note_on(1, 3, 80), note_on(1, 4, 50)];
+        assert_eq!(process(&mut limiter, within, &events), [events[1]]);
+    }
+
+    #[test]
+    fn keeps_the_newest_notes() {
+        let mut limiter = limiter_with(LimiterPriority::Newest, 2, 1);
+        let start = Instant::now();
+
+        let events = [
+            note_on(0, 60, 100),
+            note_on(0, 61, 100),
+            note_off(0, 60),
+            note_on(0, 60, 30),
+            note_on(0, 62, 10),
+        ];
+        // The note off belongs to the displaced note on
+        assert_eq!(
+            process(&mut limiter, start, &events),
+            [events[3], events[4]]
+        );
+        assert_eq!(limiter.dropped_notes(), Some(2));
+
+        let next = start + Duration::from_millis(10);
+        let offs = [note_off(0, 61), note_off(0, 60), note_off(0, 62)];
+        assert_eq!(process(&mut limiter, next, &offs), [offs[1], offs[2]]);
+    }
+}
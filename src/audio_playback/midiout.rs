@@ -1,45 +1,178 @@
-use std::thread;
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
 
-use crate::gui::window::WasabiError;
+use crate::{
+    gui::window::{GuiMessageSystem, WasabiError},
+    settings::BackpressurePolicy,
+};
 
-use crossbeam_channel::Sender;
-use midir::MidiOutput;
+use crossbeam_channel::{
+    Receiver, RecvTimeoutError, SendTimeoutError, Sender, TryRecvError, TrySendError,
+};
+use midir::{MidiOutput, MidiOutputConnection, MidiOutputPort};
+
+/// How often a blocked send checks whether the device was disconnected
+const SEND_TIMEOUT: Duration = Duration::from_millis(100);
+/// How often the device is checked for being unplugged or plugged back in
+const PORT_CHECK_INTERVAL: Duration = Duration::from_secs(2);
 
 pub struct MidiDevicePlayer {
     sender: Sender<u32>,
+    /// Used to drop the oldest queued events when the queue is full
+    receiver: Receiver<u32>,
+    policy: BackpressurePolicy,
+    dropped: Arc<AtomicU64>,
+    disconnected: Arc<AtomicBool>,
+}
+
+/// Finds the port of a device by its name
+fn find_port(out: &MidiOutput, name: &str) -> Option<MidiOutputPort> {
+    out.ports()
+        .into_iter()
+        .find(|port| out.port_name(port).is_ok_and(|n| n == name))
+}
+
+/// Connects to a device by its name, if it is available. Connecting consumes
+/// the client, so a new one is created for each connection.
+fn connect(name: &str) -> Option<MidiOutputConnection> {
+    let out = MidiOutput::new("wasabi").ok()?;
+    let port = find_port(&out, name)?;
+    out.connect(&port, "wasabi").ok()
+}
+
+/// Starts a device that was just connected from a clean state
+fn send_reset(connection: &mut MidiOutputConnection) {
+    for data in crate::utils::create_reset_midi_messages() {
+        connection.send(&data.to_le_bytes()[..3]).ok();
+    }
 }
 
 impl MidiDevicePlayer {
-    pub fn new(device: String) -> Result<Self, WasabiError> {
+    pub fn new(
+        device: String,
+        policy: BackpressurePolicy,
+        errors: Arc<GuiMessageSystem>,
+    ) -> Result<Self, WasabiError> {
         let out = MidiOutput::new("wasabi")
             .map_err(|e| WasabiError::SynthError(format!("MIDI Out Error: {e}")))?;
+        // Kept to watch the ports, as a client can't list them once it is connected
+        let watcher = MidiOutput::new("wasabi")
+            .map_err(|e| WasabiError::SynthError(format!("MIDI Out Error: {e}")))?;
         let ports = out.ports();
         if ports.is_empty() {
             return Err(WasabiError::SynthError("No MIDI devices available.".into()));
         }
 
-        let find = ports.iter().find(|d| {
-            if let Ok(name) = out.port_name(d) {
-                name == device
-            } else {
-                false
+        let found = match find_port(&out, &device) {
+            Some(port) => port,
+            None => {
+                if !device.is_empty() {
+                    errors.warning(format!(
+                        "The MIDI device \"{device}\" was not found, so the first device is used. It will be switched to when it is available."
+                    ));
+                }
+                ports[0].clone()
             }
-        });
-        let found = find.unwrap_or(&ports[0]);
-        let mut connection = out
-            .connect(found, "wasabi")
+        };
+        let name = out.port_name(&found).unwrap_or_else(|_| device.clone());
+        let connection = out
+            .connect(&found, "wasabi")
             .map_err(|e| WasabiError::SynthError(format!("MIDI Out Error: {e}")))?;
 
         let (sender, receiver) = crossbeam_channel::bounded::<u32>(1000);
+        let disconnected = Arc::new(AtomicBool::new(false));
 
+        let thread_receiver = receiver.clone();
+        let thread_disconnected = disconnected.clone();
         thread::spawn(move || {
-            for data in receiver {
-                let message = data.to_le_bytes();
-                connection.send(&message).unwrap_or_default();
+            let mut connection = Some(connection);
+            // The device that is connected, or the last one before it was unplugged
+            let mut name = name;
+            let mut last_check = Instant::now();
+
+            let disconnect = |connection: &mut Option<MidiOutputConnection>, name: &str| {
+                *connection = None;
+                thread_disconnected.store(true, Ordering::Relaxed);
+                errors.error(&WasabiError::SynthError(format!(
+                    "The MIDI device \"{name}\" was disconnected. It will be reconnected when it is available again."
+                )));
+            };
+
+            loop {
+                match thread_receiver.recv_timeout(PORT_CHECK_INTERVAL) {
+                    Ok(data) => {
+                        // The events are discarded while the device is disconnected
+                        if let Some(conn) = connection.as_mut() {
+                            let message = data.to_le_bytes();
+                            let len = match message[0] & 0xF0 {
+                                0xC0 | 0xD0 => 2,
+                                _ => 3,
+                            };
+                            if let Err(midir::SendError::Other(_)) = conn.send(&message[..len]) {
+                                disconnect(&mut connection, &name);
+                            }
+                        }
+                    }
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+
+                // Not every backend reports unplugged devices when sending, so the
+                // ports are checked as well
+                if last_check.elapsed() < PORT_CHECK_INTERVAL {
+                    continue;
+                }
+                last_check = Instant::now();
+
+                let available = |port: &str| find_port(&watcher, port).is_some();
+                if connection.is_some() && !available(&name) {
+                    disconnect(&mut connection, &name);
+                }
+
+                // Switch back to the configured device when it is plugged in,
+                // or reconnect the one that was unplugged
+                let wanted = if name != device && !device.is_empty() && available(&device) {
+                    Some(device.clone())
+                } else if connection.is_none() && available(&name) {
+                    Some(name.clone())
+                } else {
+                    None
+                };
+
+                if let Some(wanted) = wanted {
+                    if let Some(mut conn) = connect(&wanted) {
+                        // The device lost its state, so start it from a clean one
+                        send_reset(&mut conn);
+                        if let Some(mut previous) = connection.replace(conn) {
+                            // Release the notes held on the previous device
+                            send_reset(&mut previous);
+                        }
+                        thread_disconnected.store(false, Ordering::Relaxed);
+                        errors.info(format!("Connected to the MIDI device \"{wanted}\"."));
+                        name = wanted;
+                    }
+                }
             }
         });
 
-        Ok(Self { sender })
+        Ok(Self {
+            sender,
+            receiver,
+            policy,
+            dropped: Arc::new(AtomicU64::new(0)),
+            disconnected,
+        })
+    }
+
+    /// The number of events that were dropped because the device couldn't keep up
+    pub fn dropped_events(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
     }
 
     pub fn reset(&mut self) {
@@ -48,8 +181,76 @@ impl MidiDevicePlayer {
     }
 
     pub fn push_events(&mut self, data: impl Iterator<Item = u32>) {
+        if self.disconnected.load(Ordering::Relaxed) {
+            return;
+        }
+
         for ev in data {
-            self.sender.send(ev).unwrap();
+            let sent = match self.policy {
+                BackpressurePolicy::Block => self.send_blocking(ev),
+                BackpressurePolicy::DropOldest => self.send_dropping_oldest(ev),
+                BackpressurePolicy::DropNoteOns => self.send_dropping_note_ons(ev),
+            };
+
+            if !sent {
+                self.disconnected.store(true, Ordering::Relaxed);
+                return;
+            }
+        }
+    }
+
+    /// Waits for room in the queue, holding only the lock of this output, so
+    /// the other outputs keep playing. Returns false if the output thread has stopped
+    fn send_blocking(&self, mut ev: u32) -> bool {
+        loop {
+            match self.sender.send_timeout(ev, SEND_TIMEOUT) {
+                Ok(()) => return true,
+                Err(SendTimeoutError::Timeout(rejected)) => {
+                    if self.disconnected.load(Ordering::Relaxed) {
+                        return false;
+                    }
+                    ev = rejected;
+                }
+                Err(SendTimeoutError::Disconnected(_)) => return false,
+            }
+        }
+    }
+
+    /// Returns false if the output thread has stopped
+    fn send_dropping_oldest(&self, mut ev: u32) -> bool {
+        loop {
+            match self.sender.try_send(ev) {
+                Ok(()) => return true,
+                Err(TrySendError::Full(rejected)) => {
+                    ev = rejected;
+                    match self.receiver.try_recv() {
+                        Ok(_) => {
+                            self.dropped.fetch_add(1, Ordering::Relaxed);
+                        }
+                        Err(TryRecvError::Empty) => {}
+                        Err(TryRecvError::Disconnected) => return false,
+                    }
+                }
+                Err(TrySendError::Disconnected(_)) => return false,
+            }
+        }
+    }
+
+    /// Returns false if the output thread has stopped
+    fn send_dropping_note_ons(&self, ev: u32) -> bool {
+        match self.sender.try_send(ev) {
+            Ok(()) => true,
+            Err(TrySendError::Full(ev)) => {
+                let is_note_on = ev & 0xF0 == 0x90 && (ev >> 16) & 0x7F > 0;
+                if is_note_on {
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                    true
+                } else {
+                    // Other events are kept, so no notes get stuck
+                    self.send_blocking(ev)
+                }
+            }
+            Err(TrySendError::Disconnected(_)) => false,
         }
     }
 }
@@ -0,0 +1,65 @@
+use crate::settings::MixerSettings;
+
+const CONTROL_CHANGE: u32 = 0xB0;
+const CC_EXPRESSION: u32 = 11;
+/// The expression of a channel after a reset
+const DEFAULT_EXPRESSION: u8 = 127;
+
+/// The volumes of the mixer for one output. XSynth applies them as channel gains,
+/// while the other outputs receive them as expression controllers (CC11), scaled
+/// with the expression set by the MIDI. The volumes set by the MIDI are remembered,
+/// so the mixer can be changed during playback.
+pub struct VolumeMixer {
+    master: f32,
+    channels: [f32; 16],
+    midi_expression: [u8; 16],
+}
+
+impl VolumeMixer {
+    pub fn new() -> Self {
+        Self {
+            master: 1.0,
+            channels: [1.0; 16],
+            midi_expression: [DEFAULT_EXPRESSION; 16],
+        }
+    }
+
+    pub fn set_volumes(&mut self, settings: &MixerSettings) {
+        self.master = settings.master;
+        self.channels = settings.channels;
+    }
+
+    /// The gain of each channel, including the master volume
+    pub fn gains(&self) -> [f32; 16] {
+        self.channels.map(|volume| volume * self.master)
+    }
+
+    fn expression_event(&self, channel: usize) -> u32 {
+        let gain = self.master * self.channels[channel];
+        let expression = (self.midi_expression[channel] as f32 * gain)
+            .round()
+            .clamp(0.0, 127.0) as u32;
+        CONTROL_CHANGE | channel as u32 | (CC_EXPRESSION << 8) | (expression << 16)
+    }
+
+    /// Scales the event if it sets the expression of a channel
+    pub fn process(&mut self, event: u32) -> u32 {
+        if event & 0xF0 == CONTROL_CHANGE && (event >> 8) & 0x7F == CC_EXPRESSION {
+            let channel = (event & 0xF) as usize;
+            self.midi_expression[channel] = ((event >> 16) & 0x7F) as u8;
+            self.expression_event(channel)
+        } else {
+            event
+        }
+    }
+
+    /// The events that apply the current volumes to every channel
+    pub fn expression_events(&self) -> impl Iterator<Item = u32> + '_ {
+        (0..16).map(|channel| self.expression_event(channel))
+    }
+
+    /// Forgets the expression set by the MIDI, to match a synth that was reset
+    pub fn reset(&mut self) {
+        self.midi_expression = [DEFAULT_EXPRESSION; 16];
+    }
+}
@@ -1,13 +1,51 @@
-use std::sync::{Arc, RwLock};
+use atomic_float::AtomicF64;
+use std::{
+    collections::VecDeque,
+    ops::RangeInclusive,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, RwLock, Weak,
+    },
+    thread,
+    time::{Duration, Instant},
+};
 
 use crate::{
-    gui::window::{GuiMessageSystem, LoadingStatus},
-    settings::{Synth, SynthSettings, WasabiSoundfont},
+    gui::window::{GuiMessageSystem, LoadingStatus, WasabiError},
+    settings::{MixerSettings, OutputRouting, Synth, SynthSettings, WasabiSoundfont},
 };
 
+use xsynth_core::AudioStreamParams;
+
+mod clock;
+pub use clock::AudioClock;
+
+mod mixer;
+use mixer::VolumeMixer;
+
+mod limiter;
+use limiter::NoteLimiter;
+
+mod stats;
+use stats::EventStats;
+pub use stats::SynthStats;
+
+mod stream;
+use stream::*;
+
 mod xsynth;
 pub use xsynth::*;
 
+mod recording;
+pub use recording::*;
+
+mod network;
+pub use network::*;
+
+mod inspect;
+pub use inspect::*;
+
 #[cfg(supported_os)]
 mod kdmapi;
 #[cfg(supported_os)]
@@ -20,74 +58,638 @@ pub use midiout::*;
 
 enum MidiAudioPlayer {
     XSynth(XSynthPlayer),
+    Recording(RecordingPlayer),
     #[cfg(supported_os)]
     Kdmapi(KdmapiPlayer),
     #[cfg(all(supported_os, not(target_os = "freebsd")))]
     MidiDevice(MidiDevicePlayer),
+    Network(NetworkPlayer),
     None,
 }
 
-pub struct WasabiAudioPlayer(RwLock<MidiAudioPlayer>);
+impl MidiAudioPlayer {
+    #[allow(unused_variables)]
+    fn new(
+        synth: Synth,
+        settings: &SynthSettings,
+        midi_device: &str,
+        clock: Option<Arc<AudioClock>>,
+        errors: &Arc<GuiMessageSystem>,
+    ) -> Self {
+        match synth {
+            Synth::XSynth => match XSynthPlayer::new(&settings.xsynth, clock, errors) {
+                Ok(xsynth) => MidiAudioPlayer::XSynth(xsynth),
+                Err(e) => {
+                    errors.error(&e);
+                    MidiAudioPlayer::None
+                }
+            },
+            Synth::XSynthRecording => match RecordingPlayer::new(&settings.xsynth, clock, errors) {
+                Ok(xsynth) => MidiAudioPlayer::Recording(xsynth),
+                Err(e) => {
+                    errors.error(&e);
+                    MidiAudioPlayer::None
+                }
+            },
+            #[cfg(supported_os)]
+            Synth::Kdmapi => match KdmapiPlayer::new() {
+                Ok(kdmapi) => MidiAudioPlayer::Kdmapi(kdmapi),
+                Err(e) => {
+                    errors.error(&e);
+                    MidiAudioPlayer::None
+                }
+            },
+            #[cfg(all(supported_os, not(target_os = "freebsd")))]
+            Synth::MidiDevice => match MidiDevicePlayer::new(
+                midi_device.to_owned(),
+                settings.midi_device_backpressure,
+                errors.clone(),
+            ) {
+                Ok(midiout) => MidiAudioPlayer::MidiDevice(midiout),
+                Err(e) => {
+                    errors.error(&e);
+                    MidiAudioPlayer::None
+                }
+            },
+            Synth::Network => match NetworkPlayer::new(&settings.network) {
+                Ok(network) => MidiAudioPlayer::Network(network),
+                Err(e) => {
+                    errors.error(&e);
+                    MidiAudioPlayer::None
+                }
+            },
+            Synth::None => MidiAudioPlayer::None,
+        }
+    }
 
-impl WasabiAudioPlayer {
-    pub fn empty() -> Arc<Self> {
-        Arc::new(Self(RwLock::new(MidiAudioPlayer::None)))
+    fn synth(&self) -> Synth {
+        match self {
+            MidiAudioPlayer::XSynth(_) => Synth::XSynth,
+            MidiAudioPlayer::Recording(_) => Synth::XSynthRecording,
+            #[cfg(supported_os)]
+            MidiAudioPlayer::Kdmapi(_) => Synth::Kdmapi,
+            #[cfg(all(supported_os, not(target_os = "freebsd")))]
+            MidiAudioPlayer::MidiDevice(_) => Synth::MidiDevice,
+            MidiAudioPlayer::Network(_) => Synth::Network,
+            MidiAudioPlayer::None => Synth::None,
+        }
     }
 
-    pub fn voice_count(&self) -> Option<u64> {
-        match &*self.0.read().unwrap() {
+    fn voice_count(&self) -> Option<u64> {
+        match self {
             MidiAudioPlayer::XSynth(player) => Some(player.voice_count()),
+            MidiAudioPlayer::Recording(player) => Some(player.voice_count()),
+            #[cfg(supported_os)]
             MidiAudioPlayer::Kdmapi(player) => player.voice_count(),
             _ => None,
         }
     }
 
-    pub fn push_events(&self, data: impl Iterator<Item = u32>) {
-        match &mut *self.0.write().unwrap() {
+    fn stream_params(&self) -> Option<AudioStreamParams> {
+        match self {
+            MidiAudioPlayer::XSynth(player) => Some(player.stream_params()),
+            MidiAudioPlayer::Recording(player) => Some(player.stream_params()),
+            _ => None,
+        }
+    }
+
+    fn ignored_notes(&self) -> Option<u64> {
+        match self {
+            MidiAudioPlayer::XSynth(player) => Some(player.ignored_notes()),
+            MidiAudioPlayer::Recording(player) => Some(player.ignored_notes()),
+            _ => None,
+        }
+    }
+
+    fn render_load(&self) -> Option<f64> {
+        match self {
+            MidiAudioPlayer::XSynth(player) => Some(player.render_load()),
+            MidiAudioPlayer::Recording(player) => Some(player.render_load()),
+            _ => None,
+        }
+    }
+
+    fn channel_voices(&self) -> Option<[u64; 16]> {
+        match self {
+            MidiAudioPlayer::XSynth(player) => Some(player.channel_voices()),
+            MidiAudioPlayer::Recording(player) => Some(player.channel_voices()),
+            _ => None,
+        }
+    }
+
+    fn underruns(&self) -> Option<u64> {
+        match self {
+            MidiAudioPlayer::XSynth(player) => Some(player.underruns()),
+            MidiAudioPlayer::Recording(player) => Some(player.underruns()),
+            _ => None,
+        }
+    }
+
+    fn dropped_events(&self) -> Option<u64> {
+        match self {
+            #[cfg(all(supported_os, not(target_os = "freebsd")))]
+            MidiAudioPlayer::MidiDevice(player) => Some(player.dropped_events()),
+            MidiAudioPlayer::Network(player) => Some(player.dropped_events()),
+            _ => None,
+        }
+    }
+
+    fn push_events(&mut self, data: impl Iterator<Item = u32>) {
+        match self {
             MidiAudioPlayer::XSynth(player) => player.push_events(data),
+            MidiAudioPlayer::Recording(player) => player.push_events(data),
             #[cfg(supported_os)]
             MidiAudioPlayer::Kdmapi(player) => player.push_events(data),
             #[cfg(all(supported_os, not(target_os = "freebsd")))]
             MidiAudioPlayer::MidiDevice(player) => player.push_events(data),
+            MidiAudioPlayer::Network(player) => player.push_events(data),
             _ => {}
         }
     }
 
-    pub fn configure(&self, settings: &SynthSettings) {
-        match &mut *self.0.write().unwrap() {
+    /// Configures the player, which plays the events `latency` seconds after they are pushed
+    fn configure(&mut self, settings: &SynthSettings, latency: f64) {
+        match self {
             MidiAudioPlayer::XSynth(player) => player.configure(&settings.xsynth),
+            MidiAudioPlayer::Recording(player) => player.configure(&settings.xsynth),
             #[cfg(supported_os)]
             MidiAudioPlayer::Kdmapi(player) => player.configure(&settings.kdmapi),
+            MidiAudioPlayer::Network(player) => player.configure(&settings.network, latency),
             _ => {}
         }
     }
 
-    pub fn set_soundfonts(
-        &self,
+    /// Whether the player applies the mixer volumes as channel gains, instead
+    /// of receiving them as MIDI events
+    fn has_channel_gains(&self) -> bool {
+        matches!(
+            self,
+            MidiAudioPlayer::XSynth(_) | MidiAudioPlayer::Recording(_)
+        )
+    }
+
+    fn set_channel_gains(&mut self, gains: [f32; 16]) {
+        match self {
+            MidiAudioPlayer::XSynth(player) => player.set_gains(gains),
+            MidiAudioPlayer::Recording(player) => player.set_gains(gains),
+            _ => {}
+        }
+    }
+
+    fn set_soundfonts(
+        &mut self,
         soundfonts: &[WasabiSoundfont],
         loading_status: Arc<LoadingStatus>,
         errors: Arc<GuiMessageSystem>,
     ) {
-        match &mut *self.0.write().unwrap() {
+        match self {
             MidiAudioPlayer::XSynth(player) => {
                 player.set_soundfonts(soundfonts, loading_status, errors)
             }
+            MidiAudioPlayer::Recording(player) => {
+                player.set_soundfonts(soundfonts, loading_status, errors)
+            }
             #[cfg(supported_os)]
             MidiAudioPlayer::Kdmapi(player) => player.set_soundfonts(soundfonts, errors),
             _ => {}
         }
     }
 
-    pub fn reset(&self) {
-        match &mut *self.0.write().unwrap() {
+    fn reset(&mut self) {
+        match self {
             MidiAudioPlayer::XSynth(player) => player.reset(),
+            MidiAudioPlayer::Recording(player) => player.reset(),
             #[cfg(supported_os)]
             MidiAudioPlayer::Kdmapi(player) => player.reset(),
             #[cfg(all(supported_os, not(target_os = "freebsd")))]
             MidiAudioPlayer::MidiDevice(player) => player.reset(),
+            MidiAudioPlayer::Network(player) => player.reset(),
             _ => {}
         }
     }
+}
+
+/// Decides which events are sent to an output, based on its routing settings
+struct OutputRouter {
+    channels: u16,
+    /// The allowed tracks, or `None` if all tracks are allowed
+    tracks: Option<Vec<RangeInclusive<u32>>>,
+}
+
+impl OutputRouter {
+    fn new(routing: &OutputRouting) -> Self {
+        let tracks = routing.track_ranges();
+        Self {
+            channels: routing.channels,
+            tracks: if tracks.is_empty() {
+                None
+            } else {
+                Some(tracks)
+            },
+        }
+    }
+
+    fn allows_track(&self, track: Option<u32>) -> bool {
+        match (&self.tracks, track) {
+            (Some(tracks), Some(track)) => tracks.iter().any(|range| range.contains(&track)),
+            _ => true,
+        }
+    }
+
+    fn allows_all_channels(&self) -> bool {
+        self.channels == u16::MAX
+    }
+
+    fn allows_event(&self, event: u32) -> bool {
+        self.channels & (1 << (event & 0xF)) != 0
+    }
+}
+
+struct AudioOutput {
+    player: MidiAudioPlayer,
+    router: OutputRouter,
+    mixer: VolumeMixer,
+    /// The index of the output in the settings, or `None` for the main synth
+    source: Option<usize>,
+    /// How long the events are held back, so that the output plays them in step
+    /// with the output with the most latency, in seconds
+    delay: f64,
+    /// The events that are held back, each with the time it is due
+    delayed: VecDeque<(Instant, u32)>,
+}
+
+impl AudioOutput {
+    fn new(player: MidiAudioPlayer, routing: &OutputRouting, source: Option<usize>) -> Self {
+        Self {
+            player,
+            router: OutputRouter::new(routing),
+            mixer: VolumeMixer::new(),
+            source,
+            delay: 0.0,
+            delayed: VecDeque::new(),
+        }
+    }
+
+    fn push_events(&mut self, track: Option<u32>, data: impl Iterator<Item = u32>) {
+        if !self.router.allows_track(track) {
+            return;
+        }
+
+        let router = &self.router;
+        let all_channels = router.allows_all_channels();
+        let data = data.filter(|event| all_channels || router.allows_event(*event));
+
+        if self.delay > 0.0 {
+            let due = Instant::now() + Duration::from_secs_f64(self.delay);
+            self.delayed.extend(data.map(|event| (due, event)));
+        } else {
+            Self::send_events(&mut self.player, &mut self.mixer, data);
+        }
+    }
+
+    fn send_events(
+        player: &mut MidiAudioPlayer,
+        mixer: &mut VolumeMixer,
+        data: impl Iterator<Item = u32>,
+    ) {
+        if player.has_channel_gains() {
+            player.push_events(data);
+        } else {
+            player.push_events(data.map(|event| mixer.process(event)));
+        }
+    }
+
+    /// Sends the held back events that are due by `now`, or all of them if `None`
+    fn flush_delayed(&mut self, now: Option<Instant>) {
+        let count = match now {
+            Some(now) => self
+                .delayed
+                .iter()
+                .take_while(|(due, _)| *due <= now)
+                .count(),
+            None => self.delayed.len(),
+        };
+        if count > 0 {
+            let events = self.delayed.drain(..count).map(|(_, event)| event);
+            Self::send_events(&mut self.player, &mut self.mixer, events);
+        }
+    }
+
+    fn set_delay(&mut self, delay: f64) {
+        self.delay = delay;
+        if delay <= 0.0 {
+            self.flush_delayed(None);
+        }
+    }
+
+    fn set_mixer(&mut self, settings: &MixerSettings) {
+        self.mixer.set_volumes(settings);
+        self.apply_mixer();
+    }
+
+    fn apply_mixer(&mut self) {
+        if self.player.has_channel_gains() {
+            self.player.set_channel_gains(self.mixer.gains());
+        } else {
+            self.player.push_events(self.mixer.expression_events());
+        }
+    }
+
+    fn reset(&mut self) {
+        self.delayed.clear();
+        // The mixer volumes are applied again, as they are cleared by the reset
+        self.player.reset();
+        self.mixer.reset();
+        self.apply_mixer();
+    }
+}
+
+/// How often the held back events of the delayed outputs are checked
+const DELAY_PUMP_INTERVAL: Duration = Duration::from_millis(1);
+
+/// The synth outputs of Wasabi. The events are sent to the main synth and
+/// to all of the enabled additional outputs.
+pub struct WasabiAudioPlayer {
+    /// Each output is locked on its own, so the delay pump and the audio players
+    /// only contend for the outputs they are using. The list is replaced when the
+    /// outputs are switched, and events are pushed to a copy of it, so an output
+    /// that blocks on a full device queue doesn't hold the list locked.
+    outputs: RwLock<Arc<Vec<Mutex<AudioOutput>>>>,
+    /// Reused to send the same events to several outputs
+    scratch: Mutex<Vec<u32>>,
+    limiter: Mutex<NoteLimiter>,
+    event_stats: Mutex<EventStats>,
+    /// The output latency of the output with the most latency, in seconds
+    latency: Arc<AtomicF64>,
+    /// Whether a thread is sending the held back events of the delayed outputs
+    pumping: AtomicBool,
+    this: Weak<Self>,
+    /// Driven by the first XSynth output if syncing to the audio clock is enabled
+    clock: Arc<AudioClock>,
+}
+
+impl WasabiAudioPlayer {
+    pub fn empty() -> Arc<Self> {
+        Arc::new_cyclic(|this| Self {
+            outputs: RwLock::new(Arc::new(Vec::new())),
+            scratch: Mutex::new(Vec::new()),
+            limiter: Mutex::new(NoteLimiter::new()),
+            event_stats: Mutex::new(EventStats::new()),
+            latency: Arc::new(AtomicF64::new(0.0)),
+            pumping: AtomicBool::new(false),
+            this: this.clone(),
+            clock: Arc::new(AudioClock::new()),
+        })
+    }
+
+    /// The output latency of the output with the most latency, in seconds. The audio
+    /// players send their events this much ahead of the displayed time, and the outputs
+    /// with less latency hold them back for the difference.
+    pub fn latency(&self) -> Arc<AtomicF64> {
+        self.latency.clone()
+    }
+
+    /// The synth of each output, with its index in the settings or `None` for the main synth
+    pub fn output_synths(&self) -> Vec<(Option<usize>, Synth)> {
+        self.outputs
+            .read()
+            .unwrap()
+            .iter()
+            .map(|output| {
+                let output = output.lock().unwrap();
+                (output.source, output.player.synth())
+            })
+            .collect()
+    }
+
+    /// Sends events to a single output, ignoring the limiter and the statistics
+    pub fn push_output_events(&self, output: usize, data: impl Iterator<Item = u32>) {
+        let outputs = self.outputs.read().unwrap().clone();
+        if let Some(output) = outputs.get(output) {
+            output.lock().unwrap().push_events(None, data);
+        }
+    }
+
+    /// The statistics of all outputs combined
+    pub fn stats(&self) -> SynthStats {
+        fn sum<T: std::ops::Add<Output = T>>(values: impl Iterator<Item = T>) -> Option<T> {
+            values.reduce(|a, b| a + b)
+        }
+
+        let limited_notes = self.limiter.lock().unwrap().dropped_notes();
+
+        // Locked in the same order as when pushing events
+        let events_pushed = self.event_stats.lock().unwrap().pushed();
+
+        let list = self.outputs.read().unwrap().clone();
+        let outputs: Vec<_> = list.iter().map(|output| output.lock().unwrap()).collect();
+        let players = || outputs.iter().map(|output| &output.player);
+
+        SynthStats {
+            voice_count: sum(players().filter_map(|player| player.voice_count())),
+            events_pushed,
+            dropped_events: sum(players().filter_map(|player| player.dropped_events())),
+            ignored_notes: sum(players().filter_map(|player| player.ignored_notes())),
+            limited_notes,
+            render_load: players()
+                .filter_map(|player| player.render_load())
+                .reduce(f64::max),
+            buffer_underruns: sum(players().filter_map(|player| player.underruns())),
+            channel_voices: players()
+                .filter_map(|player| player.channel_voices())
+                .reduce(|a, b| std::array::from_fn(|i| a[i] + b[i])),
+        }
+    }
+
+    /// Sends events to every output, ignoring the track routing
+    pub fn push_events(&self, data: impl Iterator<Item = u32>) {
+        self.push_routed_events(None, data);
+    }
+
+    /// Sends the events of a track to the outputs that it is routed to
+    pub fn push_track_events(&self, track: u32, data: impl Iterator<Item = u32>) {
+        self.push_routed_events(Some(track), data);
+    }
+
+    fn push_routed_events(&self, track: Option<u32>, data: impl Iterator<Item = u32>) {
+        let mut limiter = self.limiter.lock().unwrap();
+        if limiter.is_enabled() {
+            self.push_to_outputs(track, limiter.process(data));
+        } else {
+            self.push_to_outputs(track, data);
+        }
+    }
+
+    fn push_to_outputs(&self, track: Option<u32>, data: impl Iterator<Item = u32>) {
+        let mut event_stats = self.event_stats.lock().unwrap();
+        let data = data.inspect(|_| event_stats.count());
+
+        let outputs = self.outputs.read().unwrap().clone();
+        match outputs.as_slice() {
+            [] => {}
+            [output] => output.lock().unwrap().push_events(track, data),
+            outputs => {
+                let mut scratch = self.scratch.lock().unwrap();
+                scratch.clear();
+                scratch.extend(data);
+                for output in outputs {
+                    output
+                        .lock()
+                        .unwrap()
+                        .push_events(track, scratch.iter().copied());
+                }
+            }
+        }
+    }
+
+    pub fn configure(&self, settings: &SynthSettings) {
+        self.limiter.lock().unwrap().configure(&settings.limiter);
+
+        let list = self.outputs.read().unwrap().clone();
+        let mut outputs: Vec<_> = list.iter().map(|output| output.lock().unwrap()).collect();
+        let latencies: Vec<f64> = outputs
+            .iter()
+            .map(|output| settings.output_latency(output.source))
+            .collect();
+        let latency = latencies.iter().copied().reduce(f64::max).unwrap_or(0.0);
+        self.latency.store(latency, Ordering::Relaxed);
+
+        for (output, output_latency) in outputs.iter_mut().zip(latencies) {
+            output.player.configure(settings, output_latency);
+            output.set_delay(latency - output_latency);
+        }
+        let delayed = outputs.iter().any(|output| output.delay > 0.0);
+        drop(outputs);
+        drop(list);
+
+        self.set_mixer(&settings.mixer);
+        if delayed {
+            self.start_delay_pump();
+        }
+    }
+
+    /// Starts a thread that sends the held back events of the delayed outputs
+    /// when they are due, until no output is delayed
+    fn start_delay_pump(&self) {
+        if self.pumping.swap(true, Ordering::Relaxed) {
+            return;
+        }
+
+        let this = self.this.clone();
+        thread::spawn(move || loop {
+            thread::sleep(DELAY_PUMP_INTERVAL);
+            let Some(this) = this.upgrade() else {
+                break;
+            };
+
+            let now = Instant::now();
+            let outputs = this.outputs.read().unwrap().clone();
+            let mut delayed = false;
+            for output in outputs.iter() {
+                // An output that is busy, such as one waiting for its device, is
+                // flushed on a later pass
+                let Ok(mut output) = output.try_lock() else {
+                    delayed = true;
+                    continue;
+                };
+                output.flush_delayed(Some(now));
+                delayed |= output.delay > 0.0;
+            }
+            if !delayed {
+                // A delay set since its output was flushed didn't start a new pump,
+                // so the outputs are checked again once a new pump can be started
+                this.pumping.store(false, Ordering::Relaxed);
+                let delayed = outputs
+                    .iter()
+                    .any(|output| output.try_lock().map_or(true, |output| output.delay > 0.0));
+                if !delayed || this.pumping.swap(true, Ordering::Relaxed) {
+                    break;
+                }
+            }
+        });
+    }
+
+    /// Applies the mixer volumes to every output
+    pub fn set_mixer(&self, settings: &MixerSettings) {
+        for output in self.outputs.read().unwrap().iter() {
+            output.lock().unwrap().set_mixer(settings);
+        }
+    }
+
+    /// The clock that the playback follows. It is driven by the audio device of the
+    /// first XSynth output if syncing to it is enabled, and follows the wall clock otherwise.
+    pub fn audio_clock(&self) -> Arc<AudioClock> {
+        self.clock.clone()
+    }
+
+    /// The output format of the first XSynth output, if there is one
+    pub fn stream_params(&self) -> Option<AudioStreamParams> {
+        self.outputs
+            .read()
+            .unwrap()
+            .iter()
+            .find_map(|output| output.lock().unwrap().player.stream_params())
+    }
+
+    pub fn set_soundfonts(
+        &self,
+        soundfonts: &[WasabiSoundfont],
+        loading_status: Arc<LoadingStatus>,
+        errors: Arc<GuiMessageSystem>,
+    ) {
+        for output in self.outputs.read().unwrap().iter() {
+            output.lock().unwrap().player.set_soundfonts(
+                soundfonts,
+                loading_status.clone(),
+                errors.clone(),
+            );
+        }
+    }
+
+    pub fn reset(&self) {
+        self.limiter.lock().unwrap().reset();
+
+        for output in self.outputs.read().unwrap().iter() {
+            output.lock().unwrap().reset();
+        }
+    }
+
+    /// Whether the synth output is being recorded, or `None` if no output can record
+    pub fn is_recording(&self) -> Option<bool> {
+        self.outputs.read().unwrap().iter().find_map(|output| {
+            match &output.lock().unwrap().player {
+                MidiAudioPlayer::Recording(player) => Some(player.is_recording()),
+                _ => None,
+            }
+        })
+    }
+
+    pub fn start_recording(&self, path: PathBuf) -> Result<(), WasabiError> {
+        self.outputs
+            .read()
+            .unwrap()
+            .iter()
+            .find_map(|output| match &mut output.lock().unwrap().player {
+                MidiAudioPlayer::Recording(player) => Some(player.start_recording(path.clone())),
+                _ => None,
+            })
+            .unwrap_or_else(|| {
+                Err(WasabiError::Other(format!(
+                    "Recording requires the \"{}\" synth",
+                    Synth::XSynthRecording.as_str()
+                )))
+            })
+    }
+
+    /// Stops the recording, returning the recorded file
+    pub fn stop_recording(&self) -> Option<Result<FinishedRecording, WasabiError>> {
+        self.outputs.read().unwrap().iter().find_map(|output| {
+            match &mut output.lock().unwrap().player {
+                MidiAudioPlayer::Recording(player) => player.stop_recording(),
+                _ => None,
+            }
+        })
+    }
 
     pub fn switch(
         &self,
@@ -95,38 +697,60 @@ impl WasabiAudioPlayer {
         loading_status: Arc<LoadingStatus>,
         errors: Arc<GuiMessageSystem>,
     ) {
-        // First drop the previous synth to avoid any loading errors
-        *self.0.write().unwrap() = MidiAudioPlayer::None;
+        // First drop the previous synths to avoid any loading errors. They are dropped
+        // once any events that are being pushed to them are sent.
+        *self.outputs.write().unwrap() = Arc::new(Vec::new());
 
-        // Create the new synth object based on the settings
-        let synth = match settings.synth {
-            Synth::XSynth => {
-                MidiAudioPlayer::XSynth(XSynthPlayer::new(settings.xsynth.config.clone()))
-            }
-            #[cfg(supported_os)]
-            Synth::Kdmapi => match KdmapiPlayer::new() {
-                Ok(kdmapi) => MidiAudioPlayer::Kdmapi(kdmapi),
-                Err(e) => {
-                    errors.error(&e);
-                    MidiAudioPlayer::None
-                }
-            },
-            #[cfg(all(supported_os, not(target_os = "freebsd")))]
-            Synth::MidiDevice => match MidiDevicePlayer::new(settings.midi_device.clone()) {
-                Ok(midiout) => MidiAudioPlayer::MidiDevice(midiout),
-                Err(e) => {
-                    errors.error(&e);
-                    MidiAudioPlayer::None
-                }
-            },
-            Synth::None => MidiAudioPlayer::None,
+        // Only one XSynth output can drive the clock
+        let mut clock = settings.xsynth.audio_clock.then(|| self.clock.clone());
+        let mut clock_for = |synth: Synth| match synth {
+            Synth::XSynth | Synth::XSynthRecording => clock.take(),
+            _ => None,
         };
 
-        // Apply the synth to the struct
-        *self.0.write().unwrap() = synth;
+        // Create the new synth objects based on the settings
+        let main = AudioOutput::new(
+            MidiAudioPlayer::new(
+                settings.synth,
+                settings,
+                &settings.midi_device,
+                clock_for(settings.synth),
+                &errors,
+            ),
+            &settings.routing,
+            None,
+        );
+        let extra: Vec<AudioOutput> = settings
+            .outputs
+            .iter()
+            .enumerate()
+            .filter(|(_, output)| output.enabled)
+            .map(|(i, output)| {
+                AudioOutput::new(
+                    MidiAudioPlayer::new(
+                        output.synth,
+                        settings,
+                        &output.midi_device,
+                        clock_for(output.synth),
+                        &errors,
+                    ),
+                    &output.routing,
+                    Some(i),
+                )
+            })
+            .collect();
+
+        // Apply the synths to the struct
+        *self.outputs.write().unwrap() = Arc::new(
+            std::iter::once(main)
+                .chain(extra)
+                .filter(|output| !matches!(output.player, MidiAudioPlayer::None))
+                .map(Mutex::new)
+                .collect(),
+        );
 
-        // Configure the synth and load the soundfont list
+        // Configure the synths and load the soundfont list
         self.configure(settings);
-        self.set_soundfonts(&settings.soundfonts, loading_status, errors);
+        self.set_soundfonts(settings.soundfonts(), loading_status, errors);
     }
 }
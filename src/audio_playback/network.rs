@@ -0,0 +1,364 @@
+use std::{
+    io::ErrorKind,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs, UdpSocket},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    gui::window::WasabiError,
+    settings::{NetworkSettings, OscAddressScheme, OscTimestamps},
+};
+
+/// Bundles are split to stay below this size, which fits any local network
+const MAX_PACKET_SIZE: usize = 8192;
+/// The seconds between the NTP epoch (1900) and the Unix epoch (1970)
+const NTP_UNIX_OFFSET: u64 = 2_208_988_800;
+/// The OSC time tag that means "immediately"
+const TIMETAG_IMMEDIATE: u64 = 1;
+
+fn write_osc_string(buf: &mut Vec<u8>, s: &str) {
+    buf.extend_from_slice(s.as_bytes());
+    // Strings are null terminated and padded to four bytes
+    let padding = 4 - s.len() % 4;
+    buf.extend(std::iter::repeat_n(0, padding));
+}
+
+/// Appends an OSC message for a raw MIDI event to the buffer
+pub fn encode_osc_message(buf: &mut Vec<u8>, address: &str, scheme: OscAddressScheme, ev: u32) {
+    let status = (ev & 0xFF) as u8;
+    let channel = (ev & 0xF) as i32;
+    let val1 = ((ev >> 8) & 0x7F) as i32;
+    let val2 = ((ev >> 16) & 0x7F) as i32;
+
+    let (path, args, count) = match scheme {
+        OscAddressScheme::Midi => {
+            write_osc_string(buf, address);
+            write_osc_string(buf, ",m");
+            // Port id, status and the two data bytes
+            buf.extend_from_slice(&[0, status, val1 as u8, val2 as u8]);
+            return;
+        }
+        OscAddressScheme::PerEvent => match status & 0xF0 {
+            0x90 if val2 > 0 => ("note_on", [channel, val1, val2], 3),
+            0x80 | 0x90 => ("note_off", [channel, val1, 0], 2),
+            0xA0 => ("poly_pressure", [channel, val1, val2], 3),
+            0xB0 => ("cc", [channel, val1, val2], 3),
+            0xC0 => ("program", [channel, val1, 0], 2),
+            0xD0 => ("pressure", [channel, val1, 0], 2),
+            0xE0 => ("pitch", [channel, ((val2 << 7) | val1) - 8192, 0], 2),
+            _ => return,
+        },
+    };
+
+    write_osc_string(buf, &format!("{}/{path}", address.trim_end_matches('/')));
+    write_osc_string(buf, &format!(",{}", "i".repeat(count)));
+    for arg in &args[..count] {
+        buf.extend_from_slice(&arg.to_be_bytes());
+    }
+}
+
+/// The current time plus a delay in seconds as an NTP time tag
+fn timetag_in(delay: f64) -> u64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        + Duration::from_secs_f64(delay.max(0.0));
+    let seconds = now.as_secs() + NTP_UNIX_OFFSET;
+    let fraction = (now.subsec_nanos() as u64 * (1 << 32)) / 1_000_000_000;
+    (seconds << 32) | fraction
+}
+
+/// Sends the MIDI events over UDP as OSC messages
+pub struct NetworkPlayer {
+    socket: UdpSocket,
+    address: String,
+    scheme: OscAddressScheme,
+    timestamps: OscTimestamps,
+    /// How far ahead of their playback time the events are pushed, in seconds
+    latency: f64,
+    dropped: u64,
+    packet: Vec<u8>,
+    message: Vec<u8>,
+}
+
+impl NetworkPlayer {
+    pub fn new(settings: &NetworkSettings) -> Result<Self, WasabiError> {
+        let target: SocketAddr = (settings.host.as_str(), settings.port)
+            .to_socket_addrs()
+            .map_err(|e| WasabiError::NetworkError(format!("{}: {e}", settings.host)))?
+            .next()
+            .ok_or_else(|| {
+                WasabiError::NetworkError(format!("{} could not be resolved", settings.host))
+            })?;
+
+        let bind = if target.is_ipv4() {
+            SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0))
+        } else {
+            SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0))
+        };
+        let socket = UdpSocket::bind(bind).map_err(|e| WasabiError::NetworkError(e.to_string()))?;
+        socket
+            .connect(target)
+            .map_err(|e| WasabiError::NetworkError(e.to_string()))?;
+        // Never hold up the playback, events that can't be sent are dropped instead
+        socket
+            .set_nonblocking(true)
+            .map_err(|e| WasabiError::NetworkError(e.to_string()))?;
+
+        Ok(Self {
+            socket,
+            address: settings.address.clone(),
+            scheme: settings.scheme,
+            timestamps: settings.timestamps,
+            latency: 0.0,
+            dropped: 0,
+            packet: Vec::with_capacity(MAX_PACKET_SIZE),
+            message: Vec::new(),
+        })
+    }
+
+    pub fn configure(&mut self, settings: &NetworkSettings, latency: f64) {
+        self.address = settings.address.clone();
+        self.scheme = settings.scheme;
+        self.timestamps = settings.timestamps;
+        self.latency = latency;
+    }
+
+    /// The number of events that couldn't be sent
+    pub fn dropped_events(&self) -> u64 {
+        self.dropped
+    }
+
+    pub fn reset(&mut self) {
+        let reset = crate::utils::create_reset_midi_messages();
+        self.push_events(reset.into_iter());
+    }
+
+    pub fn push_events(&mut self, data: impl Iterator<Item = u32>) {
+        let timetag = match self.timestamps {
+            OscTimestamps::None => None,
+            OscTimestamps::Immediate => Some(TIMETAG_IMMEDIATE),
+            // The events are pushed ahead by the latency, so this is when they are played
+            OscTimestamps::WallClock => Some(timetag_in(self.latency)),
+        };
+
+        let mut events = 0;
+        for ev in data {
+            self.message.clear();
+            encode_osc_message(&mut self.message, &self.address, self.scheme, ev);
+            if self.message.is_empty() {
+                continue;
+            }
+
+            match timetag {
+                None => {
+                    if !self.send(&self.message) {
+                        self.dropped += 1;
+                    }
+                }
+                Some(timetag) => {
+                    if !self.packet.is_empty()
+                        && self.packet.len() + 4 + self.message.len() > MAX_PACKET_SIZE
+                    {
+                        self.flush_bundle(events);
+                        events = 0;
+                    }
+                    if self.packet.is_empty() {
+                        write_osc_string(&mut self.packet, "#bundle");
+                        self.packet.extend_from_slice(&timetag.to_be_bytes());
+                    }
+                    self.packet
+                        .extend_from_slice(&(self.message.len() as i32).to_be_bytes());
+                    self.packet.extend_from_slice(&self.message);
+                    events += 1;
+                }
+            }
+        }
+
+        if !self.packet.is_empty() {
+            self.flush_bundle(events);
+        }
+    }
+
+    fn flush_bundle(&mut self, events: u64) {
+        if !self.send(&self.packet) {
+            self.dropped += events;
+        }
+        self.packet.clear();
+    }
+
+    /// Returns false if the packet couldn't be sent
+    fn send(&self, packet: &[u8]) -> bool {
+        match self.socket.send(packet) {
+            Ok(_) => true,
+            // Nobody listening is fine, it's UDP
+            Err(e) => e.kind() == ErrorKind::ConnectionRefused,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A note on of key 60 with velocity 100 on channel 2
+    const NOTE_ON: u32 = 0x643C91;
+
+    fn message(address: &str, scheme: OscAddressScheme, ev: u32) -> Vec<u8> {
+        let mut buf = Vec::new();
+        encode_osc_message(&mut buf, address, scheme, ev);
+        buf
+    }
+
+    fn listen(scheme: OscAddressScheme, timestamps: OscTimestamps) -> (UdpSocket, NetworkPlayer) {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_secs(1)))
+            .unwrap();
+        let settings = NetworkSettings {
+            host: "127.0.0.1".into(),
+            port: socket.local_addr().unwrap().port(),
+            address: "/wasabi/midi".into(),
+            scheme,
+            timestamps,
+        };
+        (socket, NetworkPlayer::new(&settings).unwrap())
+    }
+
+    fn recv(socket: &UdpSocket) -> Vec<u8> {
+        let mut buf = [0; 65536];
+        let len = socket.recv(&mut buf).unwrap();
+        buf[..len].to_vec()
+    }
+
+    /// Splits a bundle into its time tag and messages
+    fn parse_bundle(packet: &[u8]) -> (u64, Vec<&[u8]>) {
+        assert_eq!(&packet[..8], b"#bundle\0");
+        let timetag = u64::from_be_bytes(packet[8..16].try_into().unwrap());
+        let mut messages = Vec::new();
+        let mut rest = &packet[16..];
+        while !rest.is_empty() {
+            let len = i32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+            messages.push(&rest[4..4 + len]);
+            rest = &rest[4 + len..];
+        }
+        (timetag, messages)
+    }
+
+    #[test]
+    fn encodes_raw_midi() {
+        let mut expected = b"/wasabi/midi\0\0\0\0,m\0\0".to_vec();
+        expected.extend_from_slice(&[0, 0x91, 60, 100]);
+        assert_eq!(
+            message("/wasabi/midi", OscAddressScheme::Midi, NOTE_ON),
+            expected
+        );
+    }
+
+    #[test]
+    fn encodes_per_event_addresses() {
+        let mut expected = b"/wasabi/note_on\0,iii\0\0\0\0".to_vec();
+        for arg in [1i32, 60, 100] {
+            expected.extend_from_slice(&arg.to_be_bytes());
+        }
+        assert_eq!(
+            message("/wasabi/", OscAddressScheme::PerEvent, NOTE_ON),
+            expected
+        );
+
+        // A note on without velocity is a note off
+        let mut expected = b"/wasabi/note_off\0\0\0\0,ii\0".to_vec();
+        for arg in [1i32, 60] {
+            expected.extend_from_slice(&arg.to_be_bytes());
+        }
+        assert_eq!(
+            message("/wasabi", OscAddressScheme::PerEvent, 0x3C91),
+            expected
+        );
+
+        // The pitch bend is centered on zero
+        let mut expected = b"/wasabi/pitch\0\0\0,ii\0".to_vec();
+        for arg in [0i32, 0] {
+            expected.extend_from_slice(&arg.to_be_bytes());
+        }
+        assert_eq!(
+            message("/wasabi", OscAddressScheme::PerEvent, 0x4000E0),
+            expected
+        );
+
+        // System messages have no address
+        assert!(message("/wasabi", OscAddressScheme::PerEvent, 0xF8).is_empty());
+    }
+
+    #[test]
+    fn sends_plain_messages() {
+        for scheme in [OscAddressScheme::Midi, OscAddressScheme::PerEvent] {
+            let (socket, mut player) = listen(scheme, OscTimestamps::None);
+            player.push_events([NOTE_ON, 0x3C81].into_iter());
+
+            assert_eq!(recv(&socket), message("/wasabi/midi", scheme, NOTE_ON));
+            assert_eq!(recv(&socket), message("/wasabi/midi", scheme, 0x3C81));
+            assert_eq!(player.dropped_events(), 0);
+        }
+    }
+
+    #[test]
+    fn sends_bundles() {
+        for scheme in [OscAddressScheme::Midi, OscAddressScheme::PerEvent] {
+            let (socket, mut player) = listen(scheme, OscTimestamps::Immediate);
+            player.push_events([NOTE_ON, 0x3C81].into_iter());
+
+            let packet = recv(&socket);
+            let (timetag, messages) = parse_bundle(&packet);
+            assert_eq!(timetag, TIMETAG_IMMEDIATE);
+            assert_eq!(
+                messages,
+                [
+                    message("/wasabi/midi", scheme, NOTE_ON),
+                    message("/wasabi/midi", scheme, 0x3C81),
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn tags_bundles_with_the_playback_time() {
+        let (socket, mut player) = listen(OscAddressScheme::Midi, OscTimestamps::WallClock);
+        player.configure(
+            &NetworkSettings {
+                port: socket.local_addr().unwrap().port(),
+                timestamps: OscTimestamps::WallClock,
+                ..Default::default()
+            },
+            10.0,
+        );
+        let before = timetag_in(10.0);
+        player.push_events([NOTE_ON].into_iter());
+        let after = timetag_in(10.0);
+
+        let (timetag, _) = parse_bundle(&recv(&socket));
+        assert!((before..=after).contains(&timetag));
+    }
+
+    #[test]
+    fn splits_large_bundles() {
+        let (socket, mut player) = listen(OscAddressScheme::PerEvent, OscTimestamps::Immediate);
+        let events: Vec<u32> = (0..2000).map(|i| 0x640091 | (i % 128) << 8).collect();
+        player.push_events(events.iter().copied());
+
+        let mut received = Vec::new();
+        while received.len() < events.len() {
+            let packet = recv(&socket);
+            assert!(packet.len() <= MAX_PACKET_SIZE);
+            let (_, messages) = parse_bundle(&packet);
+            received.extend(messages.into_iter().map(<[u8]>::to_vec));
+        }
+
+        let expected: Vec<Vec<u8>> = events
+            .iter()
+            .map(|&ev| message("/wasabi/midi", OscAddressScheme::PerEvent, ev))
+            .collect();
+        assert_eq!(received, expected);
+        assert_eq!(player.dropped_events(), 0);
+    }
+}
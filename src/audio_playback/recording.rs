@@ -0,0 +1,277 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use xsynth_core::AudioStreamParams;
+
+use crate::{gui::window::WasabiError, settings::XSynthSettings};
+
+use super::*;
+
+/// The size of the JUNK chunk that is replaced by a ds64 chunk for RF64 files
+const DS64_SIZE: u32 = 28;
+/// The offset of the first sample in the file
+const DATA_START: u64 = 80;
+/// How often the recorder writes the recorded samples to the file
+const WRITE_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A WAV file with 32-bit float samples. Files larger than 4GB are written as RF64.
+struct WavWriter {
+    file: BufWriter<File>,
+    channels: u16,
+    samples_written: u64,
+}
+
+impl WavWriter {
+    fn create(path: &Path, channels: u16, sample_rate: u32) -> std::io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+
+        let block_align = channels * 4;
+        file.write_all(b"RIFF")?;
+        file.write_all(&0u32.to_le_bytes())?; // Filled in when finished
+        file.write_all(b"WAVE")?;
+        // Reserves the space of a ds64 chunk, in case the file grows over 4GB
+        file.write_all(b"JUNK")?;
+        file.write_all(&DS64_SIZE.to_le_bytes())?;
+        file.write_all(&[0; DS64_SIZE as usize])?;
+        file.write_all(b"fmt ")?;
+        file.write_all(&16u32.to_le_bytes())?;
+        file.write_all(&3u16.to_le_bytes())?; // IEEE float
+        file.write_all(&channels.to_le_bytes())?;
+        file.write_all(&sample_rate.to_le_bytes())?;
+        file.write_all(&(sample_rate * block_align as u32).to_le_bytes())?;
+        file.write_all(&block_align.to_le_bytes())?;
+        file.write_all(&32u16.to_le_bytes())?;
+        file.write_all(b"data")?;
+        file.write_all(&0u32.to_le_bytes())?; // Filled in when finished
+
+        Ok(Self {
+            file,
+            channels,
+            samples_written: 0,
+        })
+    }
+
+    fn write_samples(&mut self, samples: &[f32]) -> std::io::Result<()> {
+        for sample in samples {
+            self.file.write_all(&sample.to_le_bytes())?;
+        }
+        self.samples_written += samples.len() as u64;
+        Ok(())
+    }
+
+    fn finish(mut self) -> std::io::Result<()> {
+        let data_len = self.samples_written * 4;
+        let riff_len = data_len + DATA_START - 8;
+
+        if riff_len <= u32::MAX as u64 {
+            self.file.seek(SeekFrom::Start(4))?;
+            self.file.write_all(&(riff_len as u32).to_le_bytes())?;
+            self.file.seek(SeekFrom::Start(DATA_START - 4))?;
+            self.file.write_all(&(data_len as u32).to_le_bytes())?;
+        } else {
+            // The sizes don't fit in the RIFF header, so they go in the ds64 chunk
+            self.file.seek(SeekFrom::Start(0))?;
+            self.file.write_all(b"RF64")?;
+            self.file.write_all(&u32::MAX.to_le_bytes())?;
+            self.file.seek(SeekFrom::Start(12))?;
+            self.file.write_all(b"ds64")?;
+            self.file.write_all(&DS64_SIZE.to_le_bytes())?;
+            self.file.write_all(&riff_len.to_le_bytes())?;
+            self.file.write_all(&data_len.to_le_bytes())?;
+            self.file
+                .write_all(&(self.samples_written / self.channels as u64).to_le_bytes())?;
+            self.file.write_all(&0u32.to_le_bytes())?; // No table
+            self.file.seek(SeekFrom::Start(DATA_START - 4))?;
+            self.file.write_all(&u32::MAX.to_le_bytes())?;
+        }
+        self.file.flush()
+    }
+}
+
+/// A recording that was written to a file
+pub struct FinishedRecording {
+    pub path: PathBuf,
+    /// The seconds of audio missing from the file, because the recorder fell behind
+    pub dropped: f64,
+}
+
+/// Writes the samples played by the XSynth output stream to a WAV file, so the
+/// recording matches what was heard, including seeks, resets and underruns.
+pub struct SynthRecorder {
+    thread: Option<JoinHandle<Result<u64, WasabiError>>>,
+    stop: Arc<AtomicBool>,
+    path: PathBuf,
+    samples_per_second: f64,
+}
+
+impl SynthRecorder {
+    pub fn start(player: &XSynthPlayer, path: PathBuf) -> Result<Self, WasabiError> {
+        let stream_params: AudioStreamParams = player.stream_params();
+        let mut writer = WavWriter::create(
+            &path,
+            stream_params.channels.count(),
+            stream_params.sample_rate,
+        )
+        .map_err(WasabiError::FilesystemError)?;
+
+        let tap = player.record();
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let thread_stop = stop.clone();
+        let thread = thread::spawn(move || {
+            let mut samples = Vec::new();
+            loop {
+                let stopped = thread_stop.load(Ordering::Relaxed);
+                if stopped {
+                    tap.stop();
+                }
+
+                samples.clear();
+                tap.read(&mut samples);
+                writer
+                    .write_samples(&samples)
+                    .map_err(WasabiError::FilesystemError)?;
+
+                if stopped {
+                    break;
+                }
+                thread::sleep(WRITE_INTERVAL);
+            }
+            writer.finish().map_err(WasabiError::FilesystemError)?;
+            Ok(tap.overflow())
+        });
+
+        Ok(Self {
+            thread: Some(thread),
+            stop,
+            path,
+            samples_per_second: stream_params.sample_rate as f64
+                * stream_params.channels.count() as f64,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Stops the recording and finishes writing the file
+    pub fn stop(mut self) -> Result<FinishedRecording, WasabiError> {
+        let dropped = self.finish()?;
+        Ok(FinishedRecording {
+            path: self.path.clone(),
+            dropped: dropped as f64 / self.samples_per_second,
+        })
+    }
+
+    /// Returns the number of samples that were dropped
+    fn finish(&mut self) -> Result<u64, WasabiError> {
+        self.stop.store(true, Ordering::Relaxed);
+        match self.thread.take() {
+            Some(thread) => thread
+                .join()
+                .unwrap_or_else(|_| Err(WasabiError::Other("The recorder crashed".into()))),
+            None => Ok(0),
+        }
+    }
+}
+
+/// The XSynth player, which can also record its output to a WAV file
+pub struct RecordingPlayer {
+    player: XSynthPlayer,
+    recorder: Option<SynthRecorder>,
+}
+
+impl RecordingPlayer {
+    pub fn new(
+        settings: &XSynthSettings,
+        clock: Option<Arc<AudioClock>>,
+        errors: &GuiMessageSystem,
+    ) -> Result<Self, WasabiError> {
+        Ok(Self {
+            player: XSynthPlayer::new(settings, clock, errors)?,
+            recorder: None,
+        })
+    }
+
+    pub fn stream_params(&self) -> AudioStreamParams {
+        self.player.stream_params()
+    }
+
+    pub fn voice_count(&self) -> u64 {
+        self.player.voice_count()
+    }
+
+    pub fn ignored_notes(&self) -> u64 {
+        self.player.ignored_notes()
+    }
+
+    pub fn render_load(&self) -> f64 {
+        self.player.render_load()
+    }
+
+    pub fn channel_voices(&self) -> [u64; 16] {
+        self.player.channel_voices()
+    }
+
+    pub fn underruns(&self) -> u64 {
+        self.player.underruns()
+    }
+
+    pub fn push_events(&mut self, data: impl Iterator<Item = u32>) {
+        self.player.push_events(data);
+    }
+
+    pub fn reset(&mut self) {
+        self.player.reset();
+    }
+
+    pub fn configure(&mut self, settings: &XSynthSettings) {
+        self.player.configure(settings);
+    }
+
+    pub fn set_soundfonts(
+        &mut self,
+        soundfonts: &[WasabiSoundfont],
+        loading_status: Arc<LoadingStatus>,
+        errors: Arc<GuiMessageSystem>,
+    ) {
+        self.player
+            .set_soundfonts(soundfonts, loading_status, errors);
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
+
+    pub fn start_recording(&mut self, path: PathBuf) -> Result<(), WasabiError> {
+        if let Some(recorder) = self.recorder.as_ref() {
+            return Err(WasabiError::Other(format!(
+                "Already recording to {}",
+                recorder.path().to_string_lossy()
+            )));
+        }
+
+        self.recorder = Some(SynthRecorder::start(&self.player, path)?);
+        Ok(())
+    }
+
+    pub fn stop_recording(&mut self) -> Option<Result<FinishedRecording, WasabiError>> {
+        self.recorder.take().map(SynthRecorder::stop)
+    }
+}
+
+impl Drop for RecordingPlayer {
+    fn drop(&mut self) {
+        // Finishes the file of a recording that was never stopped
+        self.stop_recording();
+    }
+}
@@ -0,0 +1,38 @@
+/// The statistics of the synth outputs, read once per frame for the stats panel
+#[derive(Debug, Clone, Default)]
+pub struct SynthStats {
+    pub voice_count: Option<u64>,
+    /// The total number of events pushed to the synths
+    pub events_pushed: u64,
+    /// Events dropped by outputs that couldn't keep up
+    pub dropped_events: Option<u64>,
+    /// Notes skipped because of the ignored velocity range
+    pub ignored_notes: Option<u64>,
+    /// Notes dropped by the note limiter
+    pub limited_notes: Option<u64>,
+    /// How much of the available render time was used, from 0 to 1
+    pub render_load: Option<f64>,
+    /// The times the audio devices ran out of rendered samples
+    pub buffer_underruns: Option<u64>,
+    /// The voices playing on each channel of the XSynth outputs
+    pub channel_voices: Option<[u64; 16]>,
+}
+
+/// Counts the events as they are pushed to the synths
+pub struct EventStats {
+    pushed: u64,
+}
+
+impl EventStats {
+    pub fn new() -> Self {
+        Self { pushed: 0 }
+    }
+
+    pub fn count(&mut self) {
+        self.pushed += 1;
+    }
+
+    pub fn pushed(&self) -> u64 {
+        self.pushed
+    }
+}
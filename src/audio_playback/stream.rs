@@ -0,0 +1,580 @@
+use atomic_float::AtomicF64;
+use cpal::{
+    traits::{DeviceTrait, StreamTrait},
+    FromSample, SizedSample,
+};
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender, TryRecvError};
+use rayon::prelude::*;
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use xsynth_core::{
+    channel::{ChannelAudioEvent, ChannelConfigEvent, ChannelEvent, ControlEvent, VoiceChannel},
+    effects::VolumeLimiter,
+    AudioPipe, AudioStreamParams, ChannelCount,
+};
+use xsynth_realtime::{ThreadCount, XSynthRealtimeConfig};
+
+use crate::gui::window::WasabiError;
+
+use super::AudioClock;
+
+/// The length of the chunks rendered ahead of the audio device, in seconds
+const RENDER_CHUNK_LENGTH: f64 = 0.002;
+/// How fast the render load follows the time spent rendering each chunk
+const LOAD_SMOOTHING: f64 = 0.05;
+/// The most samples that can be rendered ahead of the device, in seconds
+const OUTPUT_BUFFER_LENGTH: f64 = 2.0;
+/// The samples that can wait to be written by the recorder, in seconds
+const RECORDING_BUFFER_LENGTH: f64 = 4.0;
+
+/// The events handled by the render thread, in the order they are sent
+pub enum StreamEvent {
+    /// An event for one of the 16 channels
+    Channel(u32, ChannelEvent),
+    AllChannels(ChannelEvent),
+    /// The gain of each channel, applied to its rendered samples
+    Gains([f32; 16]),
+}
+
+/// Parses a raw MIDI event the same way the XSynth realtime sender does
+pub fn parse_event_u32(event: u32) -> Option<StreamEvent> {
+    let channel = event & 0xF;
+    let code = (event >> 4) & 0xF;
+    let val1 = (event >> 8) as u8 & 0x7F;
+    let val2 = (event >> 16) as u8 & 0x7F;
+
+    let event = match code {
+        0x8 => ChannelAudioEvent::NoteOff { key: val1 },
+        0x9 if val2 == 0 => ChannelAudioEvent::NoteOff { key: val1 },
+        0x9 => ChannelAudioEvent::NoteOn {
+            key: val1,
+            vel: val2,
+        },
+        0xB => ChannelAudioEvent::Control(ControlEvent::Raw(val1, val2)),
+        0xC => ChannelAudioEvent::ProgramChange(val1),
+        0xE => {
+            let value = (((val2 as i16) << 7) | val1 as i16) - 8192;
+            ChannelAudioEvent::Control(ControlEvent::PitchBendValue(value as f32 / 8192.0))
+        }
+        _ => return None,
+    };
+
+    Some(StreamEvent::Channel(channel, ChannelEvent::Audio(event)))
+}
+
+fn thread_pool(threads: ThreadCount) -> Option<rayon::ThreadPool> {
+    let threads = match threads {
+        ThreadCount::None => return None,
+        ThreadCount::Auto => 0,
+        ThreadCount::Manual(threads) => threads,
+    };
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .ok()
+}
+
+/// The 16 channels of the synth. They are rendered separately, so each one
+/// can have its own gain.
+struct SynthChannels {
+    channels: Vec<VoiceChannel>,
+    buffers: Vec<Vec<f32>>,
+    gains: [f32; 16],
+    pool: Option<Arc<rayon::ThreadPool>>,
+}
+
+impl SynthChannels {
+    fn new(config: &XSynthRealtimeConfig, stream_params: AudioStreamParams) -> Self {
+        // The channels are rendered in parallel, and so are the keys of each channel,
+        // on the same pool
+        let pool = thread_pool(config.multithreading).map(Arc::new);
+        let mut channels: Vec<VoiceChannel> = (0..16)
+            .map(|_| VoiceChannel::new(config.channel_init_options, stream_params, pool.clone()))
+            .collect();
+        // Channel 10 plays the drums, like in XSynth's MIDI format
+        channels[9].process_event(ChannelEvent::Config(ChannelConfigEvent::SetPercussionMode(
+            true,
+        )));
+
+        Self {
+            channels,
+            buffers: vec![Vec::new(); 16],
+            gains: [1.0; 16],
+            pool,
+        }
+    }
+
+    fn send_event(&mut self, event: StreamEvent) {
+        match event {
+            StreamEvent::Channel(channel, event) => {
+                if let Some(channel) = self.channels.get_mut(channel as usize) {
+                    channel.process_event(event);
+                }
+            }
+            StreamEvent::AllChannels(event) => {
+                for channel in self.channels.iter_mut() {
+                    channel.process_event(event.clone());
+                }
+            }
+            StreamEvent::Gains(gains) => self.gains = gains,
+        }
+    }
+
+    fn voice_counts(&self) -> impl Iterator<Item = u64> + '_ {
+        self.channels
+            .iter()
+            .map(|channel| channel.get_channel_stats().voice_count())
+    }
+
+    fn read_samples(&mut self, out: &mut [f32]) {
+        let len = out.len();
+        let render = |(channel, buffer): (&mut VoiceChannel, &mut Vec<f32>)| {
+            buffer.clear();
+            buffer.resize(len, 0.0);
+            channel.read_samples(buffer);
+        };
+        match self.pool.as_ref() {
+            Some(pool) => pool.install(|| {
+                self.channels
+                    .par_iter_mut()
+                    .zip(self.buffers.par_iter_mut())
+                    .for_each(render)
+            }),
+            None => self
+                .channels
+                .iter_mut()
+                .zip(self.buffers.iter_mut())
+                .for_each(render),
+        }
+
+        out.fill(0.0);
+        for (buffer, gain) in self.buffers.iter().zip(self.gains) {
+            for (out, sample) in out.iter_mut().zip(buffer) {
+                *out += sample * gain;
+            }
+        }
+    }
+}
+
+/// The state shared by the render thread and the audio callback. The rendered
+/// samples and the recorded samples go through ring buffers, so the audio
+/// callback doesn't wait on locks or allocate for each callback.
+struct StreamShared {
+    /// The samples to keep rendered ahead of the device
+    buffer_samples: AtomicUsize,
+    /// The samples requested by the last callback
+    last_request: AtomicUsize,
+    /// The voices playing on each channel
+    channel_voices: [AtomicU64; 16],
+    render_load: AtomicF64,
+    /// The times the device ran out of rendered samples
+    underruns: AtomicU64,
+    /// Whether the samples sent to the device are copied to the recording buffer
+    recording: AtomicBool,
+    /// The recorded samples that weren't written yet. Only the recorder reads them.
+    recorded: Mutex<rtrb::Consumer<f32>>,
+    /// The recorded samples dropped because the recorder fell behind
+    recording_overflow: AtomicU64,
+    /// Advanced by the samples played on the device
+    clock: Option<Arc<AudioClock>>,
+    running: AtomicBool,
+}
+
+/// The 16 XSynth channels, rendering to an audio device. This replaces the XSynth
+/// realtime synth, which doesn't give access to the samples it plays. Owning the
+/// output stream lets the samples that reach the device be recorded, each
+/// channel have its own gain, the device callback drive the audio clock, and the
+/// underruns and voices of each channel be counted.
+pub struct XSynthStream {
+    sender: Sender<StreamEvent>,
+    shared: Arc<StreamShared>,
+    stream_params: AudioStreamParams,
+    render_thread: Option<JoinHandle<()>>,
+    /// Stops the stream when dropped
+    _stop: Sender<()>,
+}
+
+fn build_output_stream<T: SizedSample + FromSample<f32> + Send + 'static>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    render_channels: usize,
+    shared: Arc<StreamShared>,
+    mut rendered: rtrb::Consumer<f32>,
+    mut recorded: rtrb::Producer<f32>,
+) -> Result<cpal::Stream, cpal::BuildStreamError> {
+    let device_channels = config.channels.max(1) as usize;
+    let sample_rate = config.sample_rate.0 as f64;
+    let mut samples: Vec<f32> = Vec::new();
+    // Whether the last callback ran out of samples, so a long underrun is counted once
+    let mut underrunning = false;
+    // The first callbacks may come before anything was rendered
+    let mut started = false;
+
+    device.build_output_stream(
+        config,
+        move |data: &mut [T], _| {
+            let frames = data.len() / device_channels;
+            let wanted = frames * render_channels;
+            shared.last_request.store(wanted, Ordering::Relaxed);
+
+            samples.clear();
+            if let Ok(chunk) = rendered.read_chunk(rendered.slots().min(wanted)) {
+                let (first, second) = chunk.as_slices();
+                samples.extend_from_slice(first);
+                samples.extend_from_slice(second);
+                chunk.commit_all();
+            }
+            let short = samples.len() < wanted;
+            if short && started && !underrunning {
+                shared.underruns.fetch_add(1, Ordering::Relaxed);
+            }
+            underrunning = short;
+            started |= !short;
+            samples.resize(wanted, 0.0);
+
+            for (out, frame) in data
+                .chunks_mut(device_channels)
+                .zip(samples.chunks(render_channels))
+            {
+                for (channel, out) in out.iter_mut().enumerate() {
+                    let sample = match frame.get(channel) {
+                        Some(sample) => *sample,
+                        // Mono is played on every channel
+                        None if render_channels == 1 => frame[0],
+                        None => 0.0,
+                    };
+                    *out = T::from_sample(sample);
+                }
+            }
+
+            if shared.recording.load(Ordering::Acquire) {
+                match recorded.write_chunk_uninit(samples.len()) {
+                    Ok(chunk) => {
+                        chunk.fill_from_iter(samples.iter().copied());
+                    }
+                    Err(_) => {
+                        shared
+                            .recording_overflow
+                            .fetch_add(samples.len() as u64, Ordering::Relaxed);
+                    }
+                }
+            }
+            if let Some(clock) = shared.clock.as_ref() {
+                clock.advance(frames as f64 / sample_rate);
+            }
+        },
+        |_| {},
+        None,
+    )
+}
+
+fn render_loop(
+    receiver: Receiver<StreamEvent>,
+    shared: Arc<StreamShared>,
+    mut rendered: rtrb::Producer<f32>,
+    config: XSynthRealtimeConfig,
+    stream_params: AudioStreamParams,
+) {
+    let mut group = SynthChannels::new(&config, stream_params);
+    let mut limiter = VolumeLimiter::new(stream_params.channels.count());
+
+    let chunk_frames = ((stream_params.sample_rate as f64 * RENDER_CHUNK_LENGTH) as usize).max(1);
+    let chunk_time = chunk_frames as f64 / stream_params.sample_rate as f64;
+    let mut chunk = vec![0.0; chunk_frames * stream_params.channels.count() as usize];
+
+    while shared.running.load(Ordering::Relaxed) {
+        loop {
+            match receiver.try_recv() {
+                Ok(event) => group.send_event(event),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return,
+            }
+        }
+
+        // Keep at least one callback worth of samples, or the device underruns
+        // with buffers larger than the render window
+        let capacity = rendered.buffer().capacity();
+        let target = shared
+            .buffer_samples
+            .load(Ordering::Relaxed)
+            .max(shared.last_request.load(Ordering::Relaxed) + chunk.len())
+            .min(capacity - chunk.len());
+        if capacity - rendered.slots() >= target {
+            match receiver.recv_timeout(Duration::from_secs_f64(chunk_time)) {
+                Ok(event) => group.send_event(event),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+            continue;
+        }
+
+        let start = Instant::now();
+        group.read_samples(&mut chunk);
+        limiter.limit(&mut chunk);
+        let load = start.elapsed().as_secs_f64() / chunk_time;
+
+        let average = shared.render_load.load(Ordering::Relaxed);
+        shared.render_load.store(
+            average + (load - average) * LOAD_SMOOTHING,
+            Ordering::Relaxed,
+        );
+        for (voices, count) in shared.channel_voices.iter().zip(group.voice_counts()) {
+            voices.store(count, Ordering::Relaxed);
+        }
+
+        if let Ok(write) = rendered.write_chunk_uninit(chunk.len()) {
+            write.fill_from_iter(chunk.iter().copied());
+        }
+    }
+}
+
+impl XSynthStream {
+    /// Opens the stream on the device. If a clock is given, it is advanced by the
+    /// samples played on the device.
+    pub fn open(
+        config: XSynthRealtimeConfig,
+        device: cpal::Device,
+        stream_config: cpal::SupportedStreamConfig,
+        buffer_size: cpal::BufferSize,
+        clock: Option<Arc<AudioClock>>,
+    ) -> Result<Self, WasabiError> {
+        let channels = if stream_config.channels() == 1 {
+            ChannelCount::Mono
+        } else {
+            ChannelCount::Stereo
+        };
+        let stream_params = AudioStreamParams::new(stream_config.sample_rate().0, channels);
+        let render_channels = channels.count() as usize;
+
+        let samples_per_second = stream_params.sample_rate as f64 * render_channels as f64;
+        let (rendered_snd, rendered_rcv) =
+            rtrb::RingBuffer::new((samples_per_second * OUTPUT_BUFFER_LENGTH) as usize);
+        let (recorded_snd, recorded_rcv) =
+            rtrb::RingBuffer::new((samples_per_second * RECORDING_BUFFER_LENGTH) as usize);
+
+        let shared = Arc::new(StreamShared {
+            buffer_samples: AtomicUsize::new(0),
+            last_request: AtomicUsize::new(0),
+            channel_voices: Default::default(),
+            render_load: AtomicF64::new(0.0),
+            underruns: AtomicU64::new(0),
+            recording: AtomicBool::new(false),
+            recorded: Mutex::new(recorded_rcv),
+            recording_overflow: AtomicU64::new(0),
+            clock,
+            running: AtomicBool::new(true),
+        });
+
+        // The stream can't be moved between threads, so it lives on its own thread
+        let (result_snd, result_rcv) = crossbeam_channel::bounded(1);
+        let (stop_snd, stop_rcv) = crossbeam_channel::bounded::<()>(0);
+        let stream_shared = shared.clone();
+        thread::spawn(move || {
+            let format = stream_config.sample_format();
+            let config = cpal::StreamConfig {
+                buffer_size,
+                ..stream_config.config()
+            };
+            let shared = stream_shared;
+            let stream = match format {
+                cpal::SampleFormat::F32 => build_output_stream::<f32>(
+                    &device,
+                    &config,
+                    render_channels,
+                    shared,
+                    rendered_rcv,
+                    recorded_snd,
+                ),
+                cpal::SampleFormat::F64 => build_output_stream::<f64>(
+                    &device,
+                    &config,
+                    render_channels,
+                    shared,
+                    rendered_rcv,
+                    recorded_snd,
+                ),
+                cpal::SampleFormat::I16 => build_output_stream::<i16>(
+                    &device,
+                    &config,
+                    render_channels,
+                    shared,
+                    rendered_rcv,
+                    recorded_snd,
+                ),
+                cpal::SampleFormat::I32 => build_output_stream::<i32>(
+                    &device,
+                    &config,
+                    render_channels,
+                    shared,
+                    rendered_rcv,
+                    recorded_snd,
+                ),
+                cpal::SampleFormat::U16 => build_output_stream::<u16>(
+                    &device,
+                    &config,
+                    render_channels,
+                    shared,
+                    rendered_rcv,
+                    recorded_snd,
+                ),
+                format => {
+                    result_snd
+                        .send(Err(format!(
+                            "The sample format {format:?} is not supported"
+                        )))
+                        .ok();
+                    return;
+                }
+            };
+
+            match stream.map_err(|e| e.to_string()).and_then(|stream| {
+                stream.play().map_err(|e| e.to_string())?;
+                Ok(stream)
+            }) {
+                Ok(stream) => {
+                    result_snd.send(Ok(())).ok();
+                    // Returns when the synth is dropped
+                    stop_rcv.recv().ok();
+                    drop(stream);
+                }
+                Err(e) => {
+                    result_snd.send(Err(e)).ok();
+                }
+            }
+        });
+
+        result_rcv
+            .recv()
+            .unwrap_or_else(|_| Err("The audio stream stopped".into()))
+            .map_err(WasabiError::AudioDeviceError)?;
+
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        let render_shared = shared.clone();
+        let render_window_ms = config.render_window_ms;
+        let render_thread = thread::spawn(move || {
+            render_loop(receiver, render_shared, rendered_snd, config, stream_params)
+        });
+
+        let stream = Self {
+            sender,
+            shared,
+            stream_params,
+            render_thread: Some(render_thread),
+            _stop: stop_snd,
+        };
+        stream.set_buffer(render_window_ms);
+        Ok(stream)
+    }
+
+    pub fn stream_params(&self) -> AudioStreamParams {
+        self.stream_params
+    }
+
+    pub fn send_event(&self, event: StreamEvent) {
+        self.sender.send(event).ok();
+    }
+
+    /// A sender for the events of the synth, for sending from other threads
+    pub fn sender(&self) -> Sender<StreamEvent> {
+        self.sender.clone()
+    }
+
+    /// Sets how far ahead of the device the synth renders, in milliseconds
+    pub fn set_buffer(&self, render_window_ms: f64) {
+        let frames =
+            (render_window_ms.max(0.0) / 1000.0 * self.stream_params.sample_rate as f64) as usize;
+        self.shared.buffer_samples.store(
+            frames * self.stream_params.channels.count() as usize,
+            Ordering::Relaxed,
+        );
+    }
+
+    /// Starts copying the samples played by the device to the recording buffer,
+    /// until the returned tap is stopped
+    pub fn record(&self) -> RecordingTap {
+        {
+            // Samples left from the last recording don't belong to this one
+            let mut recorded = self.shared.recorded.lock().unwrap();
+            let slots = recorded.slots();
+            if let Ok(chunk) = recorded.read_chunk(slots) {
+                chunk.commit_all();
+            }
+        }
+        self.shared.recording_overflow.store(0, Ordering::Relaxed);
+        self.shared.recording.store(true, Ordering::Release);
+
+        RecordingTap {
+            shared: self.shared.clone(),
+        }
+    }
+
+    pub fn voice_count(&self) -> u64 {
+        self.channel_voices().iter().sum()
+    }
+
+    /// The voices playing on each of the 16 channels
+    pub fn channel_voices(&self) -> [u64; 16] {
+        std::array::from_fn(|i| self.shared.channel_voices[i].load(Ordering::Relaxed))
+    }
+
+    /// The average share of the render time spent rendering, from 0 to 1
+    pub fn render_load(&self) -> f64 {
+        self.shared.render_load.load(Ordering::Relaxed)
+    }
+
+    /// The times the device ran out of rendered samples since the stream was opened
+    pub fn underruns(&self) -> u64 {
+        self.shared.underruns.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for XSynthStream {
+    fn drop(&mut self) {
+        self.shared.running.store(false, Ordering::Relaxed);
+        if let Some(thread) = self.render_thread.take() {
+            thread.join().ok();
+        }
+    }
+}
+
+/// Reads the samples copied to the recording buffer by the audio callback
+pub struct RecordingTap {
+    shared: Arc<StreamShared>,
+}
+
+impl RecordingTap {
+    /// Moves the recorded samples that weren't read yet to the end of `out`
+    pub fn read(&self, out: &mut Vec<f32>) {
+        let mut recorded = self.shared.recorded.lock().unwrap();
+        let slots = recorded.slots();
+        if let Ok(chunk) = recorded.read_chunk(slots) {
+            let (first, second) = chunk.as_slices();
+            out.extend_from_slice(first);
+            out.extend_from_slice(second);
+            chunk.commit_all();
+        }
+    }
+
+    /// Stops copying the samples. The samples copied before can still be read.
+    pub fn stop(&self) {
+        self.shared.recording.store(false, Ordering::Release);
+    }
+
+    /// The samples that were dropped because the recorder fell behind
+    pub fn overflow(&self) -> u64 {
+        self.shared.recording_overflow.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for RecordingTap {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
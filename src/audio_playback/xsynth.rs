@@ -1,4 +1,5 @@
-use std::{sync::Arc, thread};
+use cpal::traits::{DeviceTrait, HostTrait};
+use std::{ops::RangeInclusive, sync::Arc, thread};
 
 use crate::{
     gui::window::{LoadingType, WasabiError},
@@ -6,50 +7,248 @@ use crate::{
 };
 
 use xsynth_core::{
-    channel::{ChannelConfigEvent, ChannelEvent},
+    channel::{ChannelAudioEvent, ChannelConfigEvent, ChannelEvent},
     soundfont::{SampleSoundfont, SoundfontBase},
     AudioStreamParams,
 };
-use xsynth_realtime::{
-    RealtimeEventSender, RealtimeSynth, RealtimeSynthStatsReader, SynthEvent, XSynthRealtimeConfig,
-};
 
 use super::*;
 
+pub type SoundfontStack = Vec<Arc<dyn SoundfontBase>>;
+
+fn same_stack(a: &SoundfontStack, b: &SoundfontStack) -> bool {
+    a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| Arc::ptr_eq(a, b))
+}
+
+/// Sends the soundfonts of each channel to the synth, as a single event if all
+/// channels use the same soundfonts
+pub fn send_channel_soundfonts(stacks: &[SoundfontStack; 16], mut send: impl FnMut(StreamEvent)) {
+    if stacks.iter().all(|stack| same_stack(stack, &stacks[0])) {
+        send(StreamEvent::AllChannels(ChannelEvent::Config(
+            ChannelConfigEvent::SetSoundfonts(stacks[0].clone()),
+        )));
+    } else {
+        for (channel, stack) in stacks.iter().enumerate() {
+            send(StreamEvent::Channel(
+                channel as u32,
+                ChannelEvent::Config(ChannelConfigEvent::SetSoundfonts(stack.clone())),
+            ));
+        }
+    }
+}
+
 pub struct XSynthPlayer {
-    sender: RealtimeEventSender,
-    stats: RealtimeSynthStatsReader,
-    stream_params: AudioStreamParams,
-    synth: RealtimeSynth,
+    stream: XSynthStream,
+    ignore_range: RangeInclusive<u8>,
+    /// The note offs to skip on each key, one for every ignored note on
+    skipped_offs: [[u32; 128]; 16],
+    ignored_notes: u64,
 }
 
-impl XSynthPlayer {
-    pub fn new(config: XSynthRealtimeConfig) -> Self {
-        let synth = RealtimeSynth::open_with_default_output(config);
-        let sender = synth.get_sender_ref().clone();
-        let stream_params = synth.stream_params();
-        let stats = synth.get_stats();
-
-        XSynthPlayer {
-            sender,
-            stats,
-            stream_params,
-            synth,
+/// The names of the available audio output devices
+pub fn audio_output_devices() -> Result<Vec<String>, WasabiError> {
+    let devices = cpal::default_host()
+        .output_devices()
+        .map_err(|e| WasabiError::AudioDeviceError(e.to_string()))?;
+    Ok(devices.filter_map(|device| device.name().ok()).collect())
+}
+
+fn default_audio_device() -> Result<cpal::Device, WasabiError> {
+    cpal::default_host()
+        .default_output_device()
+        .ok_or_else(|| WasabiError::AudioDeviceError("No default output device was found".into()))
+}
+
+/// An output device, with the stream config and buffer size to open it with
+type AudioOutputConfig = (cpal::Device, cpal::SupportedStreamConfig, cpal::BufferSize);
+
+/// The default output device with its default stream config
+fn default_audio_output() -> Result<AudioOutputConfig, WasabiError> {
+    let device = default_audio_device()?;
+    let config = device
+        .default_output_config()
+        .map_err(|e| WasabiError::AudioDeviceError(e.to_string()))?;
+    Ok((device, config, cpal::BufferSize::Default))
+}
+
+/// Finds the output device and stream config that were selected in the settings
+pub(super) fn find_audio_output(
+    settings: &XSynthSettings,
+) -> Result<AudioOutputConfig, WasabiError> {
+    let host = cpal::default_host();
+    let device = if settings.audio_device.is_empty() {
+        default_audio_device()?
+    } else {
+        host.output_devices()
+            .map_err(|e| WasabiError::AudioDeviceError(e.to_string()))?
+            .find(|device| {
+                device
+                    .name()
+                    .is_ok_and(|name| name == settings.audio_device)
+            })
+            .ok_or_else(|| {
+                WasabiError::AudioDeviceError(format!(
+                    "The device \"{}\" was not found",
+                    settings.audio_device
+                ))
+            })?
+    };
+
+    let default_config = device
+        .default_output_config()
+        .map_err(|e| WasabiError::AudioDeviceError(e.to_string()))?;
+    let stream_config = match settings.sample_rate {
+        Some(sample_rate) => find_stream_config(&device, &default_config, sample_rate)?,
+        None => default_config,
+    };
+
+    let buffer_size = match settings.buffer_size {
+        Some(frames) => {
+            if let cpal::SupportedBufferSize::Range { min, max } = stream_config.buffer_size() {
+                if !(*min..=*max).contains(&frames) {
+                    return Err(WasabiError::AudioDeviceError(format!(
+                        "The device does not support a buffer size of {frames} samples, only {min} to {max}"
+                    )));
+                }
+            }
+            cpal::BufferSize::Fixed(frames)
         }
+        None => cpal::BufferSize::Default,
+    };
+
+    Ok((device, stream_config, buffer_size))
+}
+
+/// Finds a stream config of the device with the sample rate, keeping the channels of
+/// the default config and preferring its sample format
+fn find_stream_config(
+    device: &cpal::Device,
+    default_config: &cpal::SupportedStreamConfig,
+    sample_rate: u32,
+) -> Result<cpal::SupportedStreamConfig, WasabiError> {
+    device
+        .supported_output_configs()
+        .map_err(|e| WasabiError::AudioDeviceError(e.to_string()))?
+        .filter(|config| {
+            config.channels() == default_config.channels()
+                && (config.min_sample_rate().0..=config.max_sample_rate().0).contains(&sample_rate)
+        })
+        .max_by_key(|config| config.sample_format() == default_config.sample_format())
+        .map(|config| config.with_sample_rate(cpal::SampleRate(sample_rate)))
+        .ok_or_else(|| {
+            WasabiError::AudioDeviceError(format!(
+                "The device does not support a sample rate of {sample_rate} Hz"
+            ))
+        })
+}
+
+impl XSynthPlayer {
+    /// Opens the synth on the audio device. If a clock is given, the device drives it.
+    pub fn new(
+        settings: &XSynthSettings,
+        clock: Option<Arc<AudioClock>>,
+        errors: &GuiMessageSystem,
+    ) -> Result<Self, WasabiError> {
+        let config = settings.config.clone();
+        let (device, stream_config, buffer_size) = match find_audio_output(settings) {
+            Ok(output) => output,
+            Err(e)
+                if !settings.audio_device.is_empty()
+                    || settings.sample_rate.is_some()
+                    || settings.buffer_size.is_some() =>
+            {
+                errors.warning(format!("{e}\nUsing the default audio device instead."));
+                default_audio_output()?
+            }
+            Err(e) => return Err(e),
+        };
+        let stream = XSynthStream::open(config.clone(), device, stream_config, buffer_size, clock)?;
+
+        Ok(XSynthPlayer {
+            stream,
+            ignore_range: config.ignore_range,
+            skipped_offs: [[0; 128]; 16],
+            ignored_notes: 0,
+        })
+    }
+
+    pub fn stream_params(&self) -> AudioStreamParams {
+        self.stream.stream_params()
+    }
+
+    /// Sets the gain of each channel, which can go above the volume set by the MIDI
+    pub fn set_gains(&self, gains: [f32; 16]) {
+        self.stream.send_event(StreamEvent::Gains(gains));
+    }
+
+    /// Starts copying the samples played by the device for a recording
+    pub fn record(&self) -> RecordingTap {
+        self.stream.record()
     }
 
     pub fn voice_count(&self) -> u64 {
-        self.stats.voice_count()
+        self.stream.voice_count()
+    }
+
+    /// The note ons that were skipped because of the ignored velocity range
+    pub fn ignored_notes(&self) -> u64 {
+        self.ignored_notes
+    }
+
+    /// The average share of the render time spent rendering, from 0 to 1
+    pub fn render_load(&self) -> f64 {
+        self.stream.render_load()
+    }
+
+    /// The voices playing on each of the 16 channels
+    pub fn channel_voices(&self) -> [u64; 16] {
+        self.stream.channel_voices()
+    }
+
+    /// The times the audio device ran out of rendered samples
+    pub fn underruns(&self) -> u64 {
+        self.stream.underruns()
     }
 
     pub fn push_events(&mut self, data: impl Iterator<Item = u32>) {
         for ev in data {
-            self.sender.send_event_u32(ev);
+            let Some(event) = parse_event_u32(ev) else {
+                continue;
+            };
+
+            // Skip the notes in the ignored velocity range, with their note offs
+            if let StreamEvent::Channel(channel, ChannelEvent::Audio(audio)) = &event {
+                match *audio {
+                    ChannelAudioEvent::NoteOn { key, vel } if self.ignore_range.contains(&vel) => {
+                        self.skipped_offs[*channel as usize][key as usize] += 1;
+                        self.ignored_notes += 1;
+                        continue;
+                    }
+                    ChannelAudioEvent::NoteOff { key } => {
+                        let skipped = &mut self.skipped_offs[*channel as usize][key as usize];
+                        if *skipped > 0 {
+                            *skipped -= 1;
+                            continue;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+
+            self.stream.send_event(event);
         }
     }
 
     pub fn reset(&mut self) {
-        self.sender.reset_synth();
+        self.skipped_offs = [[0; 128]; 16];
+        self.stream
+            .send_event(StreamEvent::AllChannels(ChannelEvent::Audio(
+                ChannelAudioEvent::AllNotesKilled,
+            )));
+        self.stream
+            .send_event(StreamEvent::AllChannels(ChannelEvent::Audio(
+                ChannelAudioEvent::ResetControl,
+            )));
     }
 
     pub fn configure(&mut self, settings: &XSynthSettings) {
@@ -58,14 +257,13 @@ impl XSynthPlayer {
         } else {
             None
         };
-        self.sender
-            .send_event(SynthEvent::AllChannels(ChannelEvent::Config(
+        self.stream
+            .send_event(StreamEvent::AllChannels(ChannelEvent::Config(
                 ChannelConfigEvent::SetLayerCount(layers),
             )));
 
-        self.synth.set_buffer(settings.config.render_window_ms);
-        self.sender
-            .set_ignore_range(settings.config.ignore_range.clone());
+        self.stream.set_buffer(settings.config.render_window_ms);
+        self.ignore_range = settings.config.ignore_range.clone();
     }
 
     pub fn set_soundfonts(
@@ -74,36 +272,45 @@ impl XSynthPlayer {
         loading_status: Arc<LoadingStatus>,
         errors: Arc<GuiMessageSystem>,
     ) {
-        let mut sender = self.sender.clone();
+        let sender = self.stream.sender();
         let soundfonts: Vec<WasabiSoundfont> = soundfonts.to_vec();
-        let stream_params = self.stream_params;
+        let stream_params = self.stream_params();
 
         loading_status.create(LoadingType::SoundFont, Default::default());
 
         thread::spawn(move || {
-            sender.send_event(SynthEvent::AllChannels(ChannelEvent::Config(
-                ChannelConfigEvent::SetSoundfonts(Vec::new()),
-            )));
+            sender
+                .send(StreamEvent::AllChannels(ChannelEvent::Config(
+                    ChannelConfigEvent::SetSoundfonts(Vec::new()),
+                )))
+                .ok();
 
-            let mut out: Vec<Arc<dyn SoundfontBase>> = Vec::new();
+            let mut out: [SoundfontStack; 16] = Default::default();
 
             for sf in soundfonts.iter().rev() {
-                if sf.enabled {
+                if sf.enabled && sf.channels != 0 {
                     loading_status.update_message(format!(
                         "Loading {:?}",
                         sf.path.file_name().unwrap_or_default()
                     ));
 
                     match SampleSoundfont::new(&sf.path, stream_params, sf.options) {
-                        Ok(sf) => out.push(Arc::new(sf)),
+                        Ok(loaded) => {
+                            let loaded: Arc<dyn SoundfontBase> = Arc::new(loaded);
+                            for (channel, stack) in out.iter_mut().enumerate() {
+                                if sf.channels & (1 << channel) != 0 {
+                                    stack.push(loaded.clone());
+                                }
+                            }
+                        }
                         Err(err) => errors.error(&WasabiError::SoundFontLoadError(err)),
                     }
                 }
             }
 
-            sender.send_event(SynthEvent::AllChannels(ChannelEvent::Config(
-                ChannelConfigEvent::SetSoundfonts(out),
-            )));
+            send_channel_soundfonts(&out, |event| {
+                sender.send(event).ok();
+            });
             loading_status.clear();
         });
     }
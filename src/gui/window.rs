@@ -5,9 +5,15 @@ mod scene;
 mod stats;
 
 mod about;
+mod bookmarks;
+mod calibration;
 mod errors;
+mod layers;
 mod loading;
+mod meta;
+mod mixer;
 mod playback_panel;
+mod recent;
 mod settings;
 mod shortcuts;
 pub use errors::*;
@@ -29,8 +35,11 @@ use crate::{
         window::{keyboard::GuiKeyboard, scene::GuiRenderScene},
         GuiRenderer, GuiState,
     },
-    midi::{CakeMIDIFile, InRamMIDIFile, LiveLoadMIDIFile, MIDIFileBase, MIDIFileUnion},
-    settings::{MidiParsing, WasabiSettings},
+    midi::{
+        CakeMIDIFile, InRamMIDIFile, LiveLoadMIDIFile, MIDIFileBase, MIDIFileUnion,
+        MIDIFileUniqueSignature, MIDILayer,
+    },
+    settings::{MIDIFileStates, MidiParsing, PercussionDisplay, RecentMIDIFile, WasabiSettings},
     state::WasabiState,
     utils::NOTE_SPEED_RANGE,
 };
@@ -40,11 +49,18 @@ pub struct GuiWasabiWindow {
     keyboard_layout: keyboard_layout::KeyboardLayout,
     keyboard: GuiKeyboard,
     midi_file: Option<MIDIFileUnion>,
+    midi_layers: Vec<MIDILayer>,
+    file_states: MIDIFileStates,
+    /// The position to offer resuming the loaded MIDI from
+    resume_position: Option<f64>,
+    calibration: Option<calibration::LatencyCalibration>,
     fps: fps::Fps,
     nps: stats::NpsCounter,
+    eps: stats::NpsCounter,
 
     settings_win: SettingsWindow,
     midi_picker: Option<Receiver<PathBuf>>,
+    layer_picker: Option<Receiver<PathBuf>>,
     midi_loader: Option<Receiver<MIDIFileUnion>>,
 }
 
@@ -63,6 +79,9 @@ impl GuiWasabiWindow {
         settings_win
             .load_midi_devices(settings)
             .unwrap_or_else(|e| state.errors.warning(e.to_string()));
+        settings_win
+            .load_audio_devices()
+            .unwrap_or_else(|e| state.errors.warning(e.to_string()));
 
         state.synth.switch(
             &settings.synth,
@@ -75,11 +94,20 @@ impl GuiWasabiWindow {
             keyboard_layout: keyboard_layout::KeyboardLayout::new(&Default::default()),
             keyboard: GuiKeyboard::new(),
             midi_file: None,
+            midi_layers: Vec::new(),
+            file_states: MIDIFileStates::load().unwrap_or_else(|e| {
+                state.errors.warning(e.to_string());
+                MIDIFileStates::default()
+            }),
+            resume_position: None,
+            calibration: None,
             fps: fps::Fps::new(),
             nps: Default::default(),
+            eps: Default::default(),
 
             settings_win,
             midi_picker: None,
+            layer_picker: None,
             midi_loader: None,
         }
     }
@@ -161,10 +189,33 @@ impl GuiWasabiWindow {
             }
         }
 
+        // Check for MIDIs selected by the layer picker
+        if let Some(recv) = self.layer_picker.as_mut() {
+            if let Ok(midi) = recv.try_recv() {
+                state.last_midi_location = midi.clone();
+                self.midi_layers.push(MIDILayer::new(midi));
+                self.load_layers(settings, state);
+                self.layer_picker = None;
+            }
+        }
+
         // Check for MIDIs parsed by the MIDI loader and play
         if let Some(recv) = self.midi_loader.as_mut() {
             if let Ok(mut midi) = recv.try_recv() {
                 midi.timer_mut().play();
+
+                // The stats of a layered session don't belong to a single file
+                let single = self.midi_layers.len() == 1;
+                settings.gui.add_recent_file(RecentMIDIFile {
+                    path: midi.signature().filepath.clone(),
+                    size: midi.signature().length_in_bytes,
+                    note_count: midi.stats().total_notes.filter(|_| single),
+                    length: midi.midi_length().filter(|_| single),
+                });
+                settings
+                    .save_to_file()
+                    .unwrap_or_else(|e| state.errors.error(&e));
+
                 self.midi_file = Some(midi);
                 self.midi_loader = None;
             }
@@ -179,11 +230,28 @@ impl GuiWasabiWindow {
             state.show_about = false;
             state.show_settings = false;
             state.show_shortcuts = false;
+            state.show_layers = false;
+            state.show_markers = false;
+            state.show_bookmarks = false;
+            state.show_calibration = false;
+            state.show_mixer = false;
         }
 
         // Render windows
         if state.show_settings {
+            let defaults = (settings.midi.parsing, settings.midi.start_delay);
             self.settings_win.show(&ctx, settings, state);
+
+            // Changing the parsing or start delay while a file is open changes them
+            // for that file too, the global values are only the defaults of new files
+            if defaults != (settings.midi.parsing, settings.midi.start_delay) {
+                if let Some(midi_file) = self.midi_file.as_ref() {
+                    if let Some(file_state) = self.file_states.get_mut(midi_file.signature()) {
+                        file_state.parsing = settings.midi.parsing;
+                        file_state.start_delay = settings.midi.start_delay;
+                    }
+                }
+            }
         }
 
         if state.show_about {
@@ -194,7 +262,57 @@ impl GuiWasabiWindow {
             self.show_shortcuts(&ctx, state);
         }
 
+        if state.show_layers {
+            self.show_layers(&ctx, settings, state);
+        }
+
+        if state.show_markers {
+            self.show_markers(&ctx, state);
+        }
+
+        if state.show_bookmarks {
+            self.show_bookmarks(&ctx, state);
+        }
+
+        if state.show_mixer {
+            self.show_mixer(&ctx, settings, state);
+        }
+
+        if state.show_calibration {
+            self.show_calibration(&ctx, settings, state);
+        } else {
+            self.calibration = None;
+        }
+
+        self.show_resume_prompt(&ctx);
+
+        // Remember the playback state of the current file
+        if let Some(midi_file) = self.midi_file.as_ref() {
+            if let Some(file_state) = self.file_states.get_mut(midi_file.signature()) {
+                // Keep the previous position until the user decides whether to resume
+                if self.resume_position.is_none() {
+                    let time = midi_file.timer().get_time().as_seconds_f64();
+                    let length = midi_file.midi_length().unwrap_or(f64::MAX);
+                    // Finished files start over when reopened
+                    file_state.position = if time < length { time } else { 0.0 };
+                }
+            }
+
+            // Fill in the stats of the recent file once the parser knows them
+            if let Some(recent) = settings.gui.recent_files.first_mut() {
+                if self.midi_layers.len() == 1 && recent.path == midi_file.signature().filepath {
+                    if recent.note_count.is_none() {
+                        recent.note_count = midi_file.stats().total_notes;
+                    }
+                    if recent.length.is_none() {
+                        recent.length = midi_file.midi_length();
+                    }
+                }
+            }
+        }
+
         // Set global keyboard shortcuts
+        let typing = ctx.wants_keyboard_input();
         ctx.input(|events| {
             for event in &events.events {
                 if let egui::Event::Key {
@@ -209,9 +327,16 @@ impl GuiWasabiWindow {
                             egui::Key::F => state.panel_pinned = !state.panel_pinned,
                             egui::Key::G => state.stats_visible = !state.stats_visible,
                             egui::Key::O => self.open_midi_dialog(state),
+                            egui::Key::B => self.add_bookmark(),
+                            egui::Key::R => Self::toggle_recording(state),
                             _ => {}
                         }
                     }
+                    if *pressed && !typing && modifiers.is_none() {
+                        if let Some(i) = bookmarks::BOOKMARK_KEYS.iter().position(|k| k == key) {
+                            self.jump_to_bookmark(i);
+                        }
+                    }
                     if *pressed && modifiers.alt && key == &egui::Key::Enter {
                         state.fullscreen = !state.fullscreen
                     }
@@ -234,10 +359,13 @@ impl GuiWasabiWindow {
             (11.6 / settings.scene.key_range.len() as f32 * available.width()).min(height / 2.0);
         let notes_height = height - keyboard_height;
 
-        let key_view = self.keyboard_layout.get_view_for_keys(
-            *settings.scene.key_range.start() as usize,
-            *settings.scene.key_range.end() as usize,
-        );
+        let key_view = self
+            .keyboard_layout
+            .get_view_for_keys(
+                *settings.scene.key_range.start() as usize,
+                *settings.scene.key_range.end() as usize,
+            )
+            .with_percussion_lane(settings.scene.percussion == PercussionDisplay::Lane);
 
         let no_frame = Frame::default()
             .inner_margin(egui::Margin::same(0))
@@ -246,6 +374,7 @@ impl GuiWasabiWindow {
         let mut stats = stats::GuiMidiStats::empty();
 
         let mut render_result_data: Option<scene::RenderResultData> = None;
+        let start_delay = self.start_delay(settings);
 
         // Render the notes
         egui::TopBottomPanel::top("Note panel")
@@ -268,11 +397,10 @@ impl GuiWasabiWindow {
                                         }
                                         egui::Key::ArrowLeft => {
                                             if midi_file.allows_seeking_backward() {
-                                                midi_file.timer_mut().seek((time - skip_dur).max(
-                                                    Duration::seconds_f64(
-                                                        -settings.midi.start_delay,
-                                                    ),
-                                                ))
+                                                midi_file.timer_mut().seek(
+                                                    (time - skip_dur)
+                                                        .max(Duration::seconds_f64(-start_delay)),
+                                                )
                                             }
                                         }
                                         egui::Key::ArrowUp => {
@@ -306,6 +434,7 @@ impl GuiWasabiWindow {
                         &key_view,
                         midi_file,
                         settings.scene.note_speed,
+                        settings.scene.percussion,
                     );
                     stats.set_rendered_note_count(result.notes_rendered);
                     stats.set_polyphony(result.polyphony);
@@ -331,8 +460,7 @@ impl GuiWasabiWindow {
 
         // Render the stats
         if state.stats_visible {
-            let voice_count = state.synth.voice_count();
-            stats.set_voice_count(voice_count);
+            stats.set_synth_stats(state.synth.stats());
 
             let pad = if settings.scene.statistics.floating {
                 12.0
@@ -343,6 +471,11 @@ impl GuiWasabiWindow {
             self.draw_stats(&ctx, pos, stats, settings);
         }
 
+        // Render the markers and lyrics
+        if settings.scene.meta_overlay {
+            self.draw_meta_overlay(&ctx, panel_height, settings);
+        }
+
         // Render errors
         state.errors.show(&ctx);
 
@@ -355,8 +488,20 @@ impl GuiWasabiWindow {
             return;
         }
 
+        self.midi_picker = Some(Self::pick_midi_file(state));
+    }
+
+    pub fn add_layer_dialog(&mut self, state: &mut WasabiState) {
+        // Do not open if something is loading already
+        if state.loading_status.is_loading() {
+            return;
+        }
+
+        self.layer_picker = Some(Self::pick_midi_file(state));
+    }
+
+    fn pick_midi_file(state: &WasabiState) -> Receiver<PathBuf> {
         let (tx, rx) = oneshot::channel();
-        self.midi_picker = Some(rx);
         let last_location = state.last_midi_location.clone();
 
         // Open the file picker in a thread so the main UI thread does not freeze
@@ -372,28 +517,118 @@ impl GuiWasabiWindow {
                 tx.send(midi_path).unwrap_or_default();
             }
         });
+
+        rx
+    }
+
+    /// Starts recording the synth output to a new file in the recordings
+    /// folder, or stops the current recording
+    pub fn toggle_recording(state: &WasabiState) {
+        match state.synth.is_recording() {
+            Some(true) => match state.synth.stop_recording() {
+                Some(Ok(recording)) => {
+                    state.errors.info(format!(
+                        "Saved the recording to {}",
+                        recording.path.to_string_lossy()
+                    ));
+                    if recording.dropped > 0.0 {
+                        state.errors.warning(format!(
+                            "{:.2} seconds of audio are missing from the recording, as it couldn't be written fast enough",
+                            recording.dropped
+                        ));
+                    }
+                }
+                Some(Err(e)) => state.errors.error(&e),
+                None => {}
+            },
+            _ => {
+                let timestamp = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs();
+                let mut path = WasabiSettings::get_recordings_dir();
+                path.push(format!("wasabi-{}.wav", timestamp));
+
+                state
+                    .synth
+                    .start_recording(path)
+                    .unwrap_or_else(|e| state.errors.error(&e));
+            }
+        }
+    }
+
+    /// The start delay of the loaded file, or the default one if no file is loaded
+    pub fn start_delay(&self, settings: &WasabiSettings) -> f64 {
+        self.midi_file
+            .as_ref()
+            .and_then(|midi_file| self.file_states.get(midi_file.signature()))
+            .map_or(settings.midi.start_delay, |file_state| {
+                file_state.start_delay
+            })
     }
 
     pub fn load_midi(
         &mut self,
         midi_path: PathBuf,
-        settings: &mut WasabiSettings,
+        settings: &WasabiSettings,
         state: &WasabiState,
     ) {
+        self.midi_layers = vec![MIDILayer::new(midi_path)];
+        self.load_layers(settings, state);
+    }
+
+    /// Loads all the current MIDI layers into a single playback session
+    pub fn load_layers(&mut self, settings: &WasabiSettings, state: &WasabiState) {
         // Unload current MIDI to free resources while loading the new one
         if let Some(mut midi_file) = self.midi_file.take() {
             midi_file.timer_mut().pause();
         }
+        self.save_file_states(state);
+        self.resume_position = None;
 
-        let filename = midi_path.file_name().unwrap_or_default().to_os_string();
+        if self.midi_layers.is_empty() {
+            return;
+        }
 
-        state.loading_status.create(
-            loading::LoadingType::Midi,
-            format!("Parsing {:?}", filename),
-        );
+        // Restore the settings and position that were last used for this file,
+        // without changing the defaults
+        let mut midi_settings = settings.midi.clone();
+        match MIDIFileUniqueSignature::from_path(&self.midi_layers[0].path) {
+            Ok(signature) => {
+                let file_state = self.file_states.open(&signature, &settings.midi);
+                midi_settings.parsing = file_state.parsing;
+                midi_settings.start_delay = file_state.start_delay;
+                self.resume_position = Some(file_state.position).filter(|p| *p >= 1.0);
+            }
+            Err(e) => {
+                state.errors.error(&e);
+                return;
+            }
+        }
 
+        let filename = self.midi_layers[0]
+            .path
+            .file_name()
+            .unwrap_or_default()
+            .to_os_string();
+
+        let status = if self.midi_layers.len() > 1 {
+            format!(
+                "Parsing {:?} and {} more layers",
+                filename,
+                self.midi_layers.len() - 1
+            )
+        } else {
+            format!("Parsing {:?}", filename)
+        };
+        state
+            .loading_status
+            .create(loading::LoadingType::Midi, status);
+
+        let layers = self.midi_layers.clone();
         let synth = state.synth.clone();
-        let settings = settings.midi.clone();
+        let percussion = settings.scene.percussion;
+        let settings = midi_settings;
         let loading_status = state.loading_status.clone();
         let errors = state.errors.clone();
 
@@ -402,40 +637,48 @@ impl GuiWasabiWindow {
 
         // Load the MIDI in a thread so the UI doesn't freeze and send it
         // via crossbeam
-        thread::spawn(move || {
-            if let Some(midi_path) = midi_path.to_str() {
-                match settings.parsing {
-                    MidiParsing::Ram => {
-                        match InRamMIDIFile::load_from_file(midi_path, synth, &settings) {
-                            Ok(midi) => {
-                                let midi_file = MIDIFileUnion::InRam(midi);
-                                tx.send(midi_file).ok();
-                            }
-                            Err(e) => errors.error(&e),
-                        }
-                        loading_status.clear();
+        thread::spawn(move || match settings.parsing {
+            MidiParsing::Ram => {
+                match InRamMIDIFile::load_from_layers(&layers, synth, &settings) {
+                    Ok(midi) => {
+                        let midi_file = MIDIFileUnion::InRam(midi);
+                        tx.send(midi_file).ok();
                     }
-                    MidiParsing::Live => {
-                        match LiveLoadMIDIFile::load_from_file(midi_path, synth, &settings) {
-                            Ok(midi) => {
-                                let midi_file = MIDIFileUnion::Live(midi);
-                                tx.send(midi_file).ok();
-                            }
-                            Err(e) => errors.error(&e),
-                        }
-                        loading_status.clear();
+                    Err(e) => errors.error(&e),
+                }
+                loading_status.clear();
+            }
+            MidiParsing::Live => {
+                match LiveLoadMIDIFile::load_from_layers(&layers, synth, &settings) {
+                    Ok(midi) => {
+                        let midi_file = MIDIFileUnion::Live(midi);
+                        tx.send(midi_file).ok();
                     }
-                    MidiParsing::Cake => {
-                        match CakeMIDIFile::load_from_file(midi_path, synth, &settings) {
-                            Ok(midi) => {
-                                let midi_file = MIDIFileUnion::Cake(midi);
-                                tx.send(midi_file).ok();
-                            }
-                            Err(e) => errors.error(&e),
-                        }
-                        loading_status.clear();
+                    Err(e) => errors.error(&e),
+                }
+                loading_status.clear();
+            }
+            MidiParsing::Cake => {
+                match CakeMIDIFile::load_from_layers(&layers, synth, &settings, percussion) {
+                    Ok(midi) => {
+                        let midi_file = MIDIFileUnion::Cake(midi);
+                        tx.send(midi_file).ok();
+                    }
+                    Err(e) => errors.error(&e),
+                }
+                loading_status.clear();
+            }
+            MidiParsing::CakeLive => {
+                match CakeMIDIFile::load_streaming_from_layers(
+                    &layers, synth, &settings, percussion,
+                ) {
+                    Ok(midi) => {
+                        let midi_file = MIDIFileUnion::Cake(midi);
+                        tx.send(midi_file).ok();
                     }
+                    Err(e) => errors.error(&e),
                 }
+                loading_status.clear();
             }
         });
     }
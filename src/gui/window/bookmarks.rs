@@ -0,0 +1,167 @@
+use egui::Context;
+use time::Duration;
+
+use crate::{
+    midi::MIDIFileBase,
+    state::WasabiState,
+    utils::{self, convert_seconds_to_time_string},
+};
+
+use super::GuiWasabiWindow;
+
+/// The keys that jump to the first nine bookmarks
+pub const BOOKMARK_KEYS: [egui::Key; 9] = [
+    egui::Key::Num1,
+    egui::Key::Num2,
+    egui::Key::Num3,
+    egui::Key::Num4,
+    egui::Key::Num5,
+    egui::Key::Num6,
+    egui::Key::Num7,
+    egui::Key::Num8,
+    egui::Key::Num9,
+];
+
+impl GuiWasabiWindow {
+    pub fn add_bookmark(&mut self) {
+        let Some(midi_file) = self.midi_file.as_ref() else {
+            return;
+        };
+
+        let time = midi_file.timer().get_time().as_seconds_f64();
+        if let Some(file_state) = self.file_states.get_mut(midi_file.signature()) {
+            file_state.add_bookmark(time);
+        }
+    }
+
+    pub fn jump_to_bookmark(&mut self, index: usize) {
+        let Some(midi_file) = self.midi_file.as_mut() else {
+            return;
+        };
+
+        let Some(time) = self
+            .file_states
+            .get(midi_file.signature())
+            .and_then(|file_state| file_state.bookmarks.get(index).copied())
+        else {
+            return;
+        };
+
+        let current = midi_file.timer().get_time().as_seconds_f64();
+        if midi_file.allows_seeking_backward() || time >= current {
+            midi_file.timer_mut().seek(Duration::seconds_f64(time));
+        }
+    }
+
+    pub fn show_bookmarks(&mut self, ctx: &Context, state: &mut WasabiState) {
+        let frame = utils::create_window_frame(ctx);
+        let size = [400.0, 300.0];
+
+        let mut add = false;
+        let mut jump = None;
+
+        egui::Window::new("Bookmarks")
+            .collapsible(false)
+            .title_bar(true)
+            .scroll([false, true])
+            .enabled(true)
+            .frame(frame)
+            .fixed_size(size)
+            .open(&mut state.show_bookmarks)
+            .show(ctx, |ui| {
+                let Some(midi_file) = self.midi_file.as_ref() else {
+                    ui.label("No MIDI loaded");
+                    return;
+                };
+                let Some(file_state) = self.file_states.get_mut(midi_file.signature()) else {
+                    return;
+                };
+
+                let mut remove = None;
+
+                egui::Grid::new("bookmarks_grid")
+                    .num_columns(4)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for (i, time) in file_state.bookmarks.iter().enumerate() {
+                            ui.monospace(convert_seconds_to_time_string(*time));
+                            ui.label(if i < BOOKMARK_KEYS.len() {
+                                format!("Key {}", i + 1)
+                            } else {
+                                String::new()
+                            });
+
+                            if ui.button("Jump").clicked() {
+                                jump = Some(i);
+                            }
+                            if ui.button("Remove").clicked() {
+                                remove = Some(i);
+                            }
+                            ui.end_row();
+                        }
+                    });
+
+                if let Some(i) = remove {
+                    file_state.bookmarks.remove(i);
+                }
+
+                ui.add_space(4.0);
+                if ui.button("Add Bookmark").clicked() {
+                    add = true;
+                }
+            });
+
+        if add {
+            self.add_bookmark();
+        }
+        if let Some(i) = jump {
+            self.jump_to_bookmark(i);
+        }
+    }
+
+    /// Offers to resume the loaded MIDI from where it was last left
+    pub fn show_resume_prompt(&mut self, ctx: &Context) {
+        let Some(position) = self.resume_position else {
+            return;
+        };
+        let Some(midi_file) = self.midi_file.as_mut() else {
+            return;
+        };
+
+        let frame = utils::create_window_frame(ctx);
+        let mut close = false;
+
+        egui::Window::new("Resume Playback")
+            .collapsible(false)
+            .resizable(false)
+            .title_bar(true)
+            .frame(frame)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "Resume from {}?",
+                    convert_seconds_to_time_string(position)
+                ));
+                ui.horizontal(|ui| {
+                    if ui.button("Resume").clicked() {
+                        midi_file.timer_mut().seek(Duration::seconds_f64(position));
+                        close = true;
+                    }
+                    if ui.button("Start Over").clicked() {
+                        close = true;
+                    }
+                });
+            });
+
+        if close {
+            self.resume_position = None;
+        }
+    }
+
+    /// Writes the remembered file states to the disk
+    pub fn save_file_states(&self, state: &WasabiState) {
+        self.file_states
+            .save()
+            .unwrap_or_else(|e| state.errors.warning(e.to_string()));
+    }
+}
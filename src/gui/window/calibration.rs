@@ -0,0 +1,192 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+};
+
+use egui::Context;
+use time::Duration;
+
+use crate::{
+    audio_playback::WasabiAudioPlayer,
+    midi::{MIDIFileBase, TimeKeeper, WaitResult},
+    settings::WasabiSettings,
+    state::WasabiState,
+    utils,
+};
+
+use super::GuiWasabiWindow;
+
+/// The time between the calibration clicks, in seconds
+const CLICK_INTERVAL: f64 = 0.5;
+/// How long the visual cue of each click is shown, in seconds
+const FLASH_LENGTH: f64 = 0.08;
+
+/// A hi wood block hit on the percussion channel, which is short on every synth
+const CLICK_NOTE_ON: u32 = 0x99 | (76 << 8) | (127 << 16);
+const CLICK_NOTE_OFF: u32 = 0x89 | (76 << 8);
+
+/// Plays clicks through one of the outputs in step with a visual cue. The clicks
+/// go through a listener with the latency offset of the synth, like the MIDI audio.
+pub struct LatencyCalibration {
+    timer: TimeKeeper,
+    thread: Option<JoinHandle<()>>,
+    /// The output that plays the clicks
+    output: Arc<AtomicUsize>,
+}
+
+impl LatencyCalibration {
+    pub fn new(synth: Arc<WasabiAudioPlayer>) -> Self {
+        let mut timer = TimeKeeper::new(0.0);
+        timer.play();
+        let mut listener = timer.get_listener().with_offset(synth.latency());
+        let output = Arc::new(AtomicUsize::new(0));
+
+        let thread_output = output.clone();
+        let thread = thread::spawn(move || {
+            let push = |data: &[u32]| {
+                let output = thread_output.load(Ordering::Relaxed);
+                synth.push_output_events(output, data.iter().copied());
+            };
+
+            let mut click = 0;
+            loop {
+                let time = Duration::seconds_f64(click as f64 * CLICK_INTERVAL);
+                match listener.wait_until(time) {
+                    WaitResult::Ok if listener.get_time() >= time => {
+                        push(&[CLICK_NOTE_OFF, CLICK_NOTE_ON]);
+
+                        // Skip the clicks that were missed if the offset was changed
+                        let now = listener.get_time().as_seconds_f64();
+                        click = (now / CLICK_INTERVAL).floor() as i64 + 1;
+                    }
+                    WaitResult::Killed => break,
+                    _ => {}
+                }
+            }
+            push(&[CLICK_NOTE_OFF]);
+        });
+
+        Self {
+            timer,
+            thread: Some(thread),
+            output,
+        }
+    }
+
+    /// Whether the visual cue of a click should be shown
+    fn is_flashing(&self) -> bool {
+        self.timer.get_time().as_seconds_f64() % CLICK_INTERVAL < FLASH_LENGTH
+    }
+}
+
+impl Drop for LatencyCalibration {
+    fn drop(&mut self) {
+        // Dropping the timer kills the listener, which stops the click thread
+        self.timer = TimeKeeper::new(0.0);
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
+
+impl GuiWasabiWindow {
+    pub fn show_calibration(
+        &mut self,
+        ctx: &Context,
+        settings: &mut WasabiSettings,
+        state: &mut WasabiState,
+    ) {
+        if self.calibration.is_none() {
+            if let Some(midi_file) = self.midi_file.as_mut() {
+                midi_file.timer_mut().pause();
+            }
+            self.calibration = Some(LatencyCalibration::new(state.synth.clone()));
+        }
+        let Some(calibration) = self.calibration.as_ref() else {
+            return;
+        };
+
+        let frame = utils::create_window_frame(ctx);
+
+        egui::Window::new("Latency Calibration")
+            .collapsible(false)
+            .resizable(false)
+            .title_bar(true)
+            .enabled(true)
+            .frame(frame)
+            .open(&mut state.show_calibration)
+            .show(ctx, |ui| {
+                ui.label("Adjust the offset until the clicks are heard when the circle lights up.");
+                ui.label("Increase it if the clicks are late, decrease it if they are early.");
+                ui.add_space(8.0);
+
+                ui.vertical_centered(|ui| {
+                    let (rect, _) =
+                        ui.allocate_exact_size(egui::vec2(64.0, 64.0), egui::Sense::hover());
+                    let color = if calibration.is_flashing() {
+                        egui::Color32::from_rgb(255, 200, 60)
+                    } else {
+                        ui.visuals().widgets.inactive.bg_fill
+                    };
+                    ui.painter().circle_filled(rect.center(), 28.0, color);
+                });
+                ui.add_space(8.0);
+
+                let outputs = state.synth.output_synths();
+                let selected = calibration.output.load(Ordering::Relaxed);
+                let output_name = |i: usize| match outputs.get(i) {
+                    Some((None, synth)) => format!("Main: {}", synth.as_str()),
+                    Some((Some(source), synth)) => {
+                        format!("Output {}: {}", source + 1, synth.as_str())
+                    }
+                    None => "No synth selected".into(),
+                };
+
+                ui.horizontal(|ui| {
+                    ui.label("Output:");
+                    egui::ComboBox::from_id_salt("calibration_output_select")
+                        .selected_text(output_name(selected))
+                        .show_ui(ui, |ui| {
+                            for i in 0..outputs.len() {
+                                if ui.selectable_label(i == selected, output_name(i)).clicked() {
+                                    // Release the click held on the previous output
+                                    state.synth.push_output_events(
+                                        selected,
+                                        std::iter::once(CLICK_NOTE_OFF),
+                                    );
+                                    calibration.output.store(i, Ordering::Relaxed);
+                                }
+                            }
+                        });
+                });
+
+                ui.horizontal(|ui| {
+                    ui.label("Latency Offset:");
+                    let source = outputs.get(selected).map(|(source, _)| *source);
+                    match source.and_then(|source| settings.synth.output_latency_mut(source)) {
+                        Some(latency) => {
+                            if ui
+                                .add(
+                                    egui::DragValue::new(latency)
+                                        .speed(1.0)
+                                        .suffix(" ms")
+                                        .range(-1000.0..=1000.0),
+                                )
+                                .changed()
+                            {
+                                state.synth.configure(&settings.synth);
+                            }
+                        }
+                        None => {
+                            ui.label("No synth selected");
+                        }
+                    }
+                });
+            });
+
+        ctx.request_repaint();
+    }
+}
@@ -16,6 +16,8 @@ pub enum WasabiError {
     SoundFontLoadError(LoadSfError),
     #[cfg(supported_os)]
     SynthError(String),
+    AudioDeviceError(String),
+    NetworkError(String),
     FilesystemError(std::io::Error),
     SettingsError(String),
     UpdaterError(String),
@@ -36,6 +38,8 @@ impl fmt::Display for WasabiError {
             WasabiError::SoundFontLoadError(e) => write!(f, "Error Parsing SoundFont: {e}"),
             #[cfg(supported_os)]
             WasabiError::SynthError(e) => write!(f, "Synth Error: {e}"),
+            WasabiError::AudioDeviceError(e) => write!(f, "Audio Device Error: {e}"),
+            WasabiError::NetworkError(e) => write!(f, "Network Error: {e}"),
             WasabiError::FilesystemError(e) => write!(f, "Filesystem Error: {e}"),
             WasabiError::SettingsError(e) => write!(f, "Settings Error: {e}"),
             WasabiError::UpdaterError(e) => write!(f, "Update Error: {e}"),
@@ -46,6 +50,7 @@ impl fmt::Display for WasabiError {
 }
 
 enum MessageType {
+    Info,
     Warning,
     Error,
     NewUpdate(String),
@@ -74,6 +79,16 @@ impl GuiMessageSystem {
         self.errors.lock().unwrap().push(error);
     }
 
+    pub fn info(&self, message: impl Into<WidgetText>) {
+        self.add(GuiMessage {
+            id: Id::new(rand::random::<u64>()),
+            visible: true,
+            errtype: MessageType::Info,
+            title: "Info".into(),
+            message: message.into(),
+        });
+    }
+
     pub fn warning(&self, message: impl Into<WidgetText>) {
         self.add(GuiMessage {
             id: Id::new(rand::random::<u64>()),
@@ -120,6 +135,17 @@ impl GuiMessageSystem {
         });
     }
 
+    /// Removes the queued messages and returns their titles and text, for
+    /// showing them somewhere other than the window
+    pub fn take_messages(&self) -> Vec<(String, String)> {
+        self.errors
+            .lock()
+            .unwrap()
+            .drain(..)
+            .map(|m| (m.title, m.message.text().to_owned()))
+            .collect()
+    }
+
     pub fn show(&self, ctx: &Context) {
         self.errors.lock().unwrap().retain(|m| m.visible);
 
@@ -135,7 +161,7 @@ impl GuiMessageSystem {
                     let image = match &message.errtype {
                         MessageType::Error => egui::include_image!("../../../assets/error.svg"),
                         MessageType::Warning => egui::include_image!("../../../assets/warning.svg"),
-                        MessageType::NewUpdate(..) => {
+                        MessageType::Info | MessageType::NewUpdate(..) => {
                             egui::include_image!("../../../assets/info.svg")
                         }
                     };
@@ -2,6 +2,11 @@
 
 use std::ops::Range;
 
+use crate::midi::PERCUSSION_LANE_KEY;
+
+/// The width of the percussion lane, relative to the width of the view
+const PERCUSSION_LANE_WIDTH: f32 = 0.04;
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum KeyboardParams {
     SameWidth,
@@ -182,6 +187,7 @@ impl KeyboardLayout {
             layout: self,
             range,
             visible_range: left_key..right_key,
+            percussion_lane: false,
         }
     }
 
@@ -212,6 +218,7 @@ impl KeyboardLayout {
             layout: self,
             range,
             visible_range: left_key..right_key,
+            percussion_lane: false,
         }
     }
 }
@@ -236,24 +243,49 @@ pub struct KeyboardView<'a> {
     layout: &'a KeyboardLayout,
     pub range: KeyboardRange,
     pub visible_range: Range<usize>,
+    percussion_lane: bool,
 }
 
 impl<'a> KeyboardView<'a> {
+    /// Reserves a lane on the right side of the view for percussion notes.
+    /// The keys are squeezed to the left, and the note column of
+    /// [`PERCUSSION_LANE_KEY`], which comes after the last key, is placed in the lane.
+    pub fn with_percussion_lane(mut self, enabled: bool) -> Self {
+        self.percussion_lane = enabled;
+        self
+    }
+
+    fn transform(&self, x: f32) -> f32 {
+        if self.percussion_lane {
+            self.range.transform(x) * (1.0 - PERCUSSION_LANE_WIDTH)
+        } else {
+            self.range.transform(x)
+        }
+    }
+
     pub fn key(&self, key: usize) -> KeyPosition {
         let key = self.layout.keys[key];
         KeyPosition {
             black: key.black,
-            left: self.range.transform(key.left),
-            right: self.range.transform(key.right),
+            left: self.transform(key.left),
+            right: self.transform(key.right),
         }
     }
 
     pub fn note(&self, key: usize) -> KeyPosition {
+        if self.percussion_lane && key == PERCUSSION_LANE_KEY {
+            return KeyPosition {
+                black: false,
+                left: 1.0 - PERCUSSION_LANE_WIDTH,
+                right: 1.0,
+            };
+        }
+
         let note = self.layout.notes[key];
         KeyPosition {
             black: note.black,
-            left: self.range.transform(note.left),
-            right: self.range.transform(note.right),
+            left: self.transform(note.left),
+            right: self.transform(note.right),
         }
     }
 
@@ -0,0 +1,102 @@
+use crate::{
+    settings::{Colors, WasabiSettings},
+    state::WasabiState,
+    utils,
+};
+
+use super::GuiWasabiWindow;
+
+impl GuiWasabiWindow {
+    pub fn show_layers(
+        &mut self,
+        ctx: &egui::Context,
+        settings: &mut WasabiSettings,
+        state: &mut WasabiState,
+    ) {
+        let frame = utils::create_window_frame(ctx);
+        let size = [500.0, 260.0];
+
+        let mut show_layers = state.show_layers;
+        let mut reload = false;
+
+        egui::Window::new("MIDI Layers")
+            .collapsible(false)
+            .title_bar(true)
+            .scroll([false, true])
+            .enabled(true)
+            .frame(frame)
+            .fixed_size(size)
+            .open(&mut show_layers)
+            .show(ctx, |ui| {
+                let mut remove = None;
+
+                egui::Grid::new("layers_grid")
+                    .num_columns(4)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.label("File");
+                        ui.label("Offset (s)");
+                        ui.label("Colors");
+                        ui.label("");
+                        ui.end_row();
+
+                        for (i, layer) in self.midi_layers.iter_mut().enumerate() {
+                            let filename = layer
+                                .path
+                                .file_name()
+                                .unwrap_or_default()
+                                .to_string_lossy()
+                                .to_string();
+                            ui.label(filename)
+                                .on_hover_text(layer.path.to_string_lossy());
+
+                            ui.add(
+                                egui::DragValue::new(&mut layer.offset)
+                                    .speed(0.1)
+                                    .range(0.0..=3600.0),
+                            );
+
+                            let selected = layer.colors.map(|c| c.as_str()).unwrap_or("Default");
+                            egui::ComboBox::from_id_salt(("layer_colors_select", i))
+                                .selected_text(selected)
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(&mut layer.colors, None, "Default");
+                                    for colors in [Colors::Rainbow, Colors::Random, Colors::Palette]
+                                    {
+                                        ui.selectable_value(
+                                            &mut layer.colors,
+                                            Some(colors),
+                                            colors.as_str(),
+                                        );
+                                    }
+                                });
+
+                            if ui.button("Remove").clicked() {
+                                remove = Some(i);
+                            }
+                            ui.end_row();
+                        }
+                    });
+
+                if let Some(i) = remove {
+                    self.midi_layers.remove(i);
+                }
+
+                ui.add_space(4.0);
+                ui.horizontal(|ui| {
+                    if ui.button("Add Layer").clicked() {
+                        self.add_layer_dialog(state);
+                    }
+                    if ui.button("Reload Layers").clicked() {
+                        reload = true;
+                    }
+                });
+            });
+
+        state.show_layers = show_layers;
+
+        if reload {
+            self.load_layers(settings, state);
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use egui::{Context, Frame};
+use time::Duration;
+
+use crate::{
+    midi::MIDIFileBase,
+    settings::WasabiSettings,
+    state::WasabiState,
+    utils::{self, convert_seconds_to_time_string},
+};
+
+use super::GuiWasabiWindow;
+
+impl GuiWasabiWindow {
+    /// Draws the current marker, lyrics and text events over the notes
+    pub fn draw_meta_overlay(&self, ctx: &Context, top: f32, settings: &WasabiSettings) {
+        let Some(midi_file) = self.midi_file.as_ref() else {
+            return;
+        };
+
+        let time = midi_file.timer().get_time().as_seconds_f64();
+        let meta = midi_file.meta_events();
+
+        let marker = meta.current_marker(time);
+        let lyrics = meta.current_lyrics(time);
+        let texts = meta.current_texts(time);
+
+        if marker.is_none() && lyrics.is_none() && texts.is_empty() {
+            return;
+        }
+
+        let opacity = settings.scene.statistics.opacity.clamp(0.0, 1.0);
+        let alpha = (u8::MAX as f32 * opacity).round() as u8;
+
+        let frame = Frame::default()
+            .inner_margin(egui::Margin::same(7))
+            .corner_radius(egui::CornerRadius::same(8))
+            .fill(egui::Color32::from_black_alpha(alpha));
+
+        egui::Area::new(egui::Id::new("meta_overlay"))
+            .anchor(egui::Align2::CENTER_TOP, [0.0, top + 12.0])
+            .interactable(false)
+            .show(ctx, |ui| {
+                frame.show(ui, |ui| {
+                    ui.with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
+                        if let Some(marker) = marker {
+                            ui.label(egui::RichText::new(marker).strong());
+                        }
+                        if let Some(lyrics) = lyrics {
+                            ui.label(egui::RichText::new(lyrics).size(20.0));
+                        }
+                        for text in texts {
+                            ui.label(egui::RichText::new(text).small());
+                        }
+                    });
+                });
+            });
+    }
+
+    pub fn show_markers(&mut self, ctx: &Context, state: &mut WasabiState) {
+        let frame = utils::create_window_frame(ctx);
+        let size = [400.0, 300.0];
+
+        egui::Window::new("Markers")
+            .collapsible(false)
+            .title_bar(true)
+            .scroll([false, true])
+            .enabled(true)
+            .frame(frame)
+            .fixed_size(size)
+            .open(&mut state.show_markers)
+            .show(ctx, |ui| {
+                let Some(midi_file) = self.midi_file.as_mut() else {
+                    ui.label("No MIDI loaded");
+                    return;
+                };
+
+                let markers = midi_file.meta_events().markers();
+                if markers.is_empty() {
+                    ui.label("This MIDI has no markers");
+                    return;
+                }
+
+                let time = midi_file.timer().get_time().as_seconds_f64();
+                let current = markers.iter().rposition(|m| m.time <= time);
+
+                let mut seek = None;
+
+                egui::Grid::new("markers_grid")
+                    .num_columns(3)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for (i, marker) in markers.iter().enumerate() {
+                            ui.monospace(convert_seconds_to_time_string(marker.time));
+                            ui.label(marker.kind.as_str());
+
+                            // Passed markers can't be reached if the file can't seek backwards
+                            let reachable =
+                                midi_file.allows_seeking_backward() || marker.time >= time;
+                            let text = if marker.text.is_empty() {
+                                "-"
+                            } else {
+                                marker.text.as_str()
+                            };
+
+                            if ui
+                                .add_enabled(
+                                    reachable,
+                                    egui::SelectableLabel::new(current == Some(i), text),
+                                )
+                                .clicked()
+                            {
+                                seek = Some(marker.time);
+                            }
+                            ui.end_row();
+                        }
+                    });
+
+                if let Some(time) = seek {
+                    midi_file.timer_mut().seek(Duration::seconds_f64(time));
+                }
+            });
+    }
+}
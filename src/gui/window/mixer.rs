@@ -0,0 +1,70 @@
+use egui::Context;
+
+use crate::{
+    settings::{MixerSettings, WasabiSettings},
+    state::WasabiState,
+    utils,
+};
+
+use super::GuiWasabiWindow;
+
+/// The highest volume of a fader, which boosts the channel
+const MAX_VOLUME: f32 = 2.0;
+
+impl GuiWasabiWindow {
+    pub fn show_mixer(
+        &mut self,
+        ctx: &Context,
+        settings: &mut WasabiSettings,
+        state: &mut WasabiState,
+    ) {
+        let frame = utils::create_window_frame(ctx);
+        let mut changed = false;
+
+        egui::Window::new("Mixer")
+            .collapsible(false)
+            .resizable(false)
+            .title_bar(true)
+            .enabled(true)
+            .frame(frame)
+            .open(&mut state.show_mixer)
+            .show(ctx, |ui| {
+                let mixer = &mut settings.synth.mixer;
+
+                ui.horizontal(|ui| {
+                    changed |= fader(ui, &mut mixer.master, "Master");
+                    ui.separator();
+                    for (i, volume) in mixer.channels.iter_mut().enumerate() {
+                        changed |= fader(ui, volume, &format!("{}", i + 1));
+                    }
+                });
+
+                ui.add_space(4.0);
+                ui.small("Volumes above 100% only boost the XSynth outputs.");
+                if ui.button("Reset").clicked() {
+                    *mixer = MixerSettings::default();
+                    changed = true;
+                }
+            });
+
+        if changed {
+            state.synth.set_mixer(&settings.synth.mixer);
+        }
+    }
+}
+
+fn fader(ui: &mut egui::Ui, volume: &mut f32, label: &str) -> bool {
+    ui.vertical(|ui| {
+        let changed = ui
+            .add(
+                egui::Slider::new(volume, 0.0..=MAX_VOLUME)
+                    .vertical()
+                    .show_value(false),
+            )
+            .on_hover_text(format!("{:.0}%", *volume * 100.0))
+            .changed();
+        ui.small(label);
+        changed
+    })
+    .inner
+}
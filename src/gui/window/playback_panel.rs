@@ -5,7 +5,7 @@ use time::Duration;
 use egui::{popup_below_widget, PopupCloseBehavior};
 
 use crate::{
-    midi::MIDIFileBase,
+    midi::{MIDIFileBase, NoteDensity},
     settings::WasabiSettings,
     state::WasabiState,
     utils::{self, convert_seconds_to_time_string},
@@ -18,7 +18,7 @@ impl GuiWasabiWindow {
     pub fn show_playback_panel(
         &mut self,
         ctx: &egui::Context,
-        settings: &WasabiSettings,
+        settings: &mut WasabiSettings,
         state: &mut WasabiState,
     ) -> f32 {
         // Check if mouse is on the panel area
@@ -32,6 +32,7 @@ impl GuiWasabiWindow {
         let icon_color = ctx.style().visuals.strong_text_color();
         let button_rounding = 8.0;
         let is_popup_open = ctx.memory(|mem| mem.is_popup_open(state.panel_popup_id));
+        let start_delay = self.start_delay(settings);
 
         let should_expand = state.panel_pinned || mouse_over_panel || is_popup_open;
 
@@ -94,6 +95,8 @@ impl GuiWasabiWindow {
                             midi.timer_mut().pause();
                             state.synth.reset();
                         }
+                        self.resume_position = None;
+                        self.save_file_states(state);
                     }
 
                     // Play/Pause button
@@ -190,10 +193,17 @@ impl GuiWasabiWindow {
                             let mut time = midi_file.timer().get_time().as_seconds_f64();
                             let time_prev = time;
 
-                            ui.add(
-                                egui::Slider::new(&mut time, -settings.midi.start_delay..=length)
+                            // Reserve a shape so the density graph is painted behind the slider
+                            let graph = ui.painter().add(egui::Shape::Noop);
+                            let slider = ui.add(
+                                egui::Slider::new(&mut time, -start_delay..=length)
                                     .show_value(false),
                             );
+                            if let Some(density) = midi_file.note_density() {
+                                let shape =
+                                    density_graph(ui, slider.rect, density, -start_delay, length);
+                                ui.painter().set(graph, shape);
+                            }
                             if (time_prev != time)
                                 && (midi_file.allows_seeking_backward() || time_prev < time)
                             {
@@ -221,25 +231,75 @@ impl GuiWasabiWindow {
                     let options = ui.add(egui::ImageButton::new(options_img));
 
                     if options.clicked() {
+                        settings.gui.prune_recent_files();
                         ui.memory_mut(|mem| mem.toggle_popup(state.panel_popup_id));
                     }
                     popup_below_widget(
                         ui,
                         state.panel_popup_id,
                         &options,
-                        PopupCloseBehavior::CloseOnClick,
+                        PopupCloseBehavior::CloseOnClickOutside,
                         |ui| {
                             ui.set_min_width(130.0);
 
+                            let recent = self.show_recent_menu(ui, settings, state);
                             if ui.button("Settings").clicked() {
                                 state.show_settings = true;
                             }
+                            if ui.button("Layers").clicked() {
+                                state.show_layers = true;
+                            }
+                            if ui.button("Markers").clicked() {
+                                state.show_markers = true;
+                            }
+                            if ui.button("Bookmarks").clicked() {
+                                state.show_bookmarks = true;
+                            }
+                            if ui.button("Mixer").clicked() {
+                                state.show_mixer = true;
+                            }
+                            if let Some(recording) = state.synth.is_recording() {
+                                let text = if recording {
+                                    "Stop Recording"
+                                } else {
+                                    "Start Recording"
+                                };
+                                if ui.button(text).clicked() {
+                                    Self::toggle_recording(state);
+                                }
+                            }
+                            if settings.synth.profiles.len() > 1 {
+                                ui.separator();
+                                ui.small("SoundFont Profile");
+                                let mut switch_to = None;
+                                for (i, profile) in settings.synth.profiles.iter().enumerate() {
+                                    if ui
+                                        .selectable_label(
+                                            i == settings.synth.active_profile,
+                                            &profile.name,
+                                        )
+                                        .clicked()
+                                    {
+                                        switch_to = Some(i);
+                                    }
+                                }
+                                if let Some(index) = switch_to {
+                                    self.settings_win
+                                        .switch_soundfont_profile(index, settings, state);
+                                }
+                                ui.separator();
+                            }
                             if ui.button("Shortcuts").clicked() {
                                 state.show_shortcuts = true;
                             }
                             if ui.button("About").clicked() {
                                 state.show_about = true;
                             }
+
+                            // Any click closes the popup, except for opening the recent files
+                            if ui.input(|i| i.pointer.any_click()) && !recent.clicked() {
+                                ui.memory_mut(|mem| mem.close_popup());
+                            }
                         },
                     );
 
@@ -267,3 +327,50 @@ impl GuiWasabiWindow {
         }
     }
 }
+
+/// Builds a graph of the notes per second between the start and end time,
+/// aligned with the rail of a slider occupying the given rect.
+fn density_graph(
+    ui: &egui::Ui,
+    rect: egui::Rect,
+    density: &NoteDensity,
+    start: f64,
+    end: f64,
+) -> egui::Shape {
+    const BAR_WIDTH: f32 = 2.0;
+
+    let max_nps = density.max_nps();
+    if max_nps <= 0.0 || end <= start {
+        return egui::Shape::Noop;
+    }
+
+    // The slider insets its rail by the radius of its handle
+    let x_range = rect.x_range().shrink(rect.height() / 2.5);
+    let width = x_range.span();
+    let color = ui.visuals().selection.bg_fill.gamma_multiply(0.4);
+
+    let mut shapes = Vec::new();
+    let mut x = 0.0;
+    while x < width {
+        let bar_start = start + (end - start) * (x / width) as f64;
+        let bar_end = start + (end - start) * ((x + BAR_WIDTH) / width).min(1.0) as f64;
+
+        if bar_end > 0.0 {
+            // Black MIDIs have huge spikes, so the graph is scaled to keep quieter parts visible
+            let nps = density.max_nps_between(bar_start.max(0.0), bar_end);
+            let height = rect.height() * (nps / max_nps).sqrt();
+
+            if height > 0.0 {
+                let bar = egui::Rect::from_min_max(
+                    egui::pos2(x_range.min + x, rect.bottom() - height),
+                    egui::pos2(x_range.min + x + BAR_WIDTH, rect.bottom()),
+                );
+                shapes.push(egui::Shape::rect_filled(bar, 0.0, color));
+            }
+        }
+
+        x += BAR_WIDTH;
+    }
+
+    egui::Shape::Vec(shapes)
+}
@@ -0,0 +1,83 @@
+use numfmt::{Formatter, Precision};
+
+use crate::{settings::WasabiSettings, state::WasabiState, utils::convert_seconds_to_time_string};
+
+use super::GuiWasabiWindow;
+
+impl GuiWasabiWindow {
+    /// Shows the recent files in a submenu, returning the response of its button
+    pub fn show_recent_menu(
+        &mut self,
+        ui: &mut egui::Ui,
+        settings: &mut WasabiSettings,
+        state: &mut WasabiState,
+    ) -> egui::Response {
+        let mut open = None;
+        let mut clear = false;
+
+        let response = ui
+            .menu_button("Open Recent", |ui| {
+                if settings.gui.recent_files.is_empty() {
+                    ui.label("No recent files");
+                    return;
+                }
+
+                let mut f = Formatter::new()
+                    .separator(',')
+                    .unwrap()
+                    .precision(Precision::Decimals(0));
+
+                for file in settings.gui.recent_files.iter() {
+                    let filename = file
+                        .path
+                        .file_name()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                        .to_string();
+                    let details = format!(
+                        "{}\n{:.1} MB, {} notes, {}",
+                        file.path.to_string_lossy(),
+                        file.size as f64 / 1_000_000.0,
+                        file.note_count
+                            .map(|n| f.fmt2(n).to_string())
+                            .unwrap_or_else(|| "-".to_string()),
+                        file.length
+                            .map(convert_seconds_to_time_string)
+                            .unwrap_or_else(|| "-".to_string()),
+                    );
+
+                    if ui.button(filename).on_hover_text(details).clicked() {
+                        open = Some(file.path.clone());
+                        ui.close_menu();
+                    }
+                }
+
+                ui.separator();
+                if ui.button("Clear").clicked() {
+                    clear = true;
+                    ui.close_menu();
+                }
+            })
+            .response;
+
+        if clear {
+            settings.gui.recent_files.clear();
+            settings
+                .save_to_file()
+                .unwrap_or_else(|e| state.errors.error(&e));
+        }
+
+        if let Some(path) = open {
+            if path.exists() {
+                self.load_midi(path, settings, state);
+            } else {
+                state
+                    .errors
+                    .warning(format!("{} no longer exists", path.to_string_lossy()));
+                settings.gui.prune_recent_files();
+            }
+        }
+
+        response
+    }
+}
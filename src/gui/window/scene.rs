@@ -6,6 +6,7 @@ use egui::{Image, Ui};
 use crate::{
     midi::{MIDIColor, MIDIFileUnion},
     scenes::SceneSwapchain,
+    settings::PercussionDisplay,
 };
 
 use self::{cake_system::CakeRenderer, note_list_system::NoteRenderer};
@@ -74,6 +75,7 @@ impl GuiRenderScene {
         key_view: &KeyboardView,
         midi_file: &mut MIDIFileUnion,
         view_range: f64,
+        percussion: PercussionDisplay,
     ) -> RenderResultData {
         let size = ui.available_size();
         let size = [size.x as u32, size.y as u32];
@@ -85,12 +87,12 @@ impl GuiRenderScene {
             MIDIFileUnion::InRam(file) => self
                 .draw_system
                 .get_note_renderer(state.renderer)
-                .draw(key_view, frame, file, view_range),
+                .draw(key_view, frame, file, view_range, percussion),
 
             MIDIFileUnion::Live(file) => self
                 .draw_system
                 .get_note_renderer(state.renderer)
-                .draw(key_view, frame, file, view_range),
+                .draw(key_view, frame, file, view_range, percussion),
 
             MIDIFileUnion::Cake(file) => self
                 .draw_system
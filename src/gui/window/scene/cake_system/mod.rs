@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{collections::VecDeque, sync::Arc};
 
 use bytemuck::{Pod, Zeroable};
 use vulkano::{
@@ -10,9 +10,10 @@ use vulkano::{
     },
     descriptor_set::{
         allocator::{StandardDescriptorSetAllocator, StandardDescriptorSetAllocatorCreateInfo},
+        layout::DescriptorSetLayout,
         DescriptorSet, WriteDescriptorSet,
     },
-    device::{Device, Queue},
+    device::Queue,
     format::Format,
     image::{view::ImageView, Image, ImageCreateInfo, ImageUsage},
     memory::allocator::{AllocationCreateInfo, MemoryTypeFilter, StandardMemoryAllocator},
@@ -36,16 +37,14 @@ use vulkano::{
 };
 
 use crate::{
-    gui::{
-        window::keyboard_layout::{KeyPosition, KeyboardView},
-        GuiRenderer,
-    },
-    midi::{CakeBlock, CakeMIDIFile, CakeSignature, IntVector4},
+    gui::{window::keyboard_layout::KeyboardView, GuiRenderer},
+    midi::{CakeMIDIFile, CakeSignature, CakeWindow, IntVector4, NOTE_COLUMNS},
 };
 
 use super::RenderResultData;
 
-const BUFFER_ARRAY_LEN: u64 = 256;
+/// The number of key buffers of a cake window, which are bound together for each window
+const BUFFER_ARRAY_LEN: u64 = NOTE_COLUMNS as u64;
 
 struct CakeBuffer {
     data: Subbuffer<[IntVector4]>,
@@ -53,8 +52,18 @@ struct CakeBuffer {
     end: i32,
 }
 
-struct BufferSet {
+struct WindowBuffers {
+    id: u64,
+    start: i32,
+    end: i32,
     buffers: Vec<CakeBuffer>,
+    descriptor: Arc<DescriptorSet>,
+}
+
+struct BufferSet {
+    windows: Vec<WindowBuffers>,
+    /// Bound to the unused slots of the buffer array
+    empty: Subbuffer<[IntVector4]>,
 }
 
 #[derive(Default, Debug, Copy, Clone, Zeroable, Pod, Vertex)]
@@ -75,18 +84,21 @@ struct CakeNoteColumn {
 }
 
 impl BufferSet {
-    fn new(_device: &Arc<Device>) -> Self {
-        Self { buffers: vec![] }
+    fn new(allocator: Arc<StandardMemoryAllocator>) -> Self {
+        let empty = Self::create_buffer(allocator, std::iter::once(IntVector4::new_empty()));
+
+        Self {
+            windows: vec![],
+            empty,
+        }
     }
 
-    fn add_buffer(
-        &mut self,
+    fn create_buffer(
         allocator: Arc<StandardMemoryAllocator>,
-        block: &CakeBlock,
-        _key: &KeyPosition,
-    ) {
-        let data = Buffer::from_iter(
-            allocator.clone(),
+        data: impl ExactSizeIterator<Item = IntVector4>,
+    ) -> Subbuffer<[IntVector4]> {
+        Buffer::from_iter(
+            allocator,
             BufferCreateInfo {
                 usage: BufferUsage::STORAGE_BUFFER,
                 ..Default::default()
@@ -95,21 +107,74 @@ impl BufferSet {
                 memory_type_filter: MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
                 ..Default::default()
             },
-            block.tree.iter().copied(),
+            data,
         )
-        .unwrap();
+        .unwrap()
+    }
 
-        let buffer = CakeBuffer {
-            data,
-            start: block.start_time as i32,
-            end: block.end_time as i32,
-        };
+    fn add_window(
+        &mut self,
+        allocator: Arc<StandardMemoryAllocator>,
+        sd_allocator: Arc<StandardDescriptorSetAllocator>,
+        desc_layout: Arc<DescriptorSetLayout>,
+        window: &CakeWindow,
+    ) {
+        let buffers: Vec<_> = window
+            .blocks
+            .iter()
+            .map(|block| CakeBuffer {
+                data: Self::create_buffer(allocator.clone(), block.tree.iter().copied()),
+                start: block.start_time as i32,
+                end: block.end_time as i32,
+            })
+            .collect();
+
+        let descriptor = DescriptorSet::new(
+            sd_allocator,
+            desc_layout,
+            [WriteDescriptorSet::buffer_array(
+                0,
+                0,
+                buffers
+                    .iter()
+                    .map(|b| b.data.clone())
+                    .chain(std::iter::repeat(self.empty.clone()))
+                    .take(BUFFER_ARRAY_LEN as usize),
+            )],
+            [],
+        )
+        .unwrap();
 
-        self.buffers.push(buffer);
+        self.windows.push(WindowBuffers {
+            id: window.id,
+            start: window.start_time as i32,
+            end: window.end_time as i32,
+            buffers,
+            descriptor,
+        });
     }
 
-    fn clear(&mut self) {
-        self.buffers.clear();
+    /// Uploads the windows that are new, and drops the ones that were evicted
+    fn sync_windows(
+        &mut self,
+        allocator: Arc<StandardMemoryAllocator>,
+        sd_allocator: Arc<StandardDescriptorSetAllocator>,
+        desc_layout: Arc<DescriptorSetLayout>,
+        windows: &VecDeque<CakeWindow>,
+    ) {
+        self.windows
+            .retain(|buffers| windows.iter().any(|w| w.id == buffers.id));
+
+        for window in windows {
+            if !self.windows.iter().any(|buffers| buffers.id == window.id) {
+                self.add_window(
+                    allocator.clone(),
+                    sd_allocator.clone(),
+                    desc_layout.clone(),
+                    window,
+                );
+            }
+        }
     }
 }
 
@@ -230,23 +295,11 @@ impl CakeRenderer {
         )
         .unwrap();
 
-        let buffers = Buffer::new_slice(
-            allocator.clone(),
-            BufferCreateInfo {
-                usage: BufferUsage::VERTEX_BUFFER,
-                ..Default::default()
-            },
-            AllocationCreateInfo {
-                memory_type_filter: MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
-                ..Default::default()
-            },
-            BUFFER_ARRAY_LEN,
-        )
-        .unwrap();
+        let buffers = Self::create_columns(allocator.clone(), 1);
 
         CakeRenderer {
             gfx_queue,
-            buffers: BufferSet::new(&renderer.device),
+            buffers: BufferSet::new(allocator.clone()),
             pipeline_clear,
             render_pass_clear,
             depth_buffer,
@@ -266,6 +319,26 @@ impl CakeRenderer {
         }
     }
 
+    /// The vertex buffer of the key columns, with room for the given number of windows
+    fn create_columns(
+        allocator: Arc<StandardMemoryAllocator>,
+        windows: usize,
+    ) -> Subbuffer<[CakeNoteColumn]> {
+        Buffer::new_slice(
+            allocator,
+            BufferCreateInfo {
+                usage: BufferUsage::VERTEX_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                ..Default::default()
+            },
+            windows.max(1) as u64 * BUFFER_ARRAY_LEN,
+        )
+        .unwrap()
+    }
+
     pub fn draw(
         &mut self,
         key_view: &KeyboardView,
@@ -291,14 +364,20 @@ impl CakeRenderer {
             .unwrap();
         }
 
+        midi_file.update_windows();
+
+        let pipeline = self.pipeline_clear.clone();
+        let pipeline_layout = pipeline.layout();
+
         let curr_signature = midi_file.cake_signature();
         if self.current_file_signature.as_ref() != Some(&curr_signature) {
             self.current_file_signature = Some(curr_signature);
-            self.buffers.clear();
-            for (i, block) in midi_file.key_blocks().iter().enumerate() {
-                let key = key_view.key(i);
-                self.buffers.add_buffer(self.allocator.clone(), block, &key);
-            }
+            self.buffers.sync_windows(
+                self.allocator.clone(),
+                self.sd_allocator.clone(),
+                pipeline_layout.set_layouts().first().unwrap().clone(),
+                midi_file.windows(),
+            );
         }
 
         let midi_time = midi_file.current_time().as_seconds_f64();
@@ -317,37 +396,45 @@ impl CakeRenderer {
             key_view.visible_range.len() as f32,
         ) as i32;
 
+        // Each window is drawn separately with its own key buffers bound,
+        // so only the ones that are on screen are needed
+        let visible_windows: Vec<&WindowBuffers> = self
+            .buffers
+            .windows
+            .iter()
+            .filter(|w| w.start < screen_end && screen_start < w.end)
+            .collect();
+
+        if self.buffers_init.len() < visible_windows.len() as u64 * BUFFER_ARRAY_LEN {
+            self.buffers_init = Self::create_columns(self.allocator.clone(), visible_windows.len());
+        }
+
         let mut buffer_instances = self.buffers_init.write().unwrap();
         let mut written_instances = 0;
-        // Black keys first, as they stencil out in the depth buffer
-        for (i, buffer) in self.buffers.buffers.iter().enumerate() {
-            let key = key_view.note(i);
-            if key.black {
-                buffer_instances[written_instances] = CakeNoteColumn {
-                    buffer_index: i as i32,
-                    border_width,
-                    start: buffer.start,
-                    end: buffer.end,
-                    left: key.left,
-                    right: key.right,
-                };
-                written_instances += 1;
-            }
-        }
-        // White keys second
-        for (i, buffer) in self.buffers.buffers.iter().enumerate() {
-            let key = key_view.note(i);
-            if !key.black {
-                buffer_instances[written_instances] = CakeNoteColumn {
-                    buffer_index: i as i32,
-                    border_width,
-                    start: buffer.start,
-                    end: buffer.end,
-                    left: key.left,
-                    right: key.right,
-                };
-                written_instances += 1;
+        let mut draws = Vec::with_capacity(visible_windows.len());
+        for window in visible_windows.iter() {
+            let first_instance = written_instances;
+            // Black keys first, as they stencil out in the depth buffer
+            for black in [true, false] {
+                for (i, buffer) in window.buffers.iter().enumerate() {
+                    let key = key_view.note(i);
+                    if key.black == black {
+                        buffer_instances[written_instances] = CakeNoteColumn {
+                            buffer_index: i as i32,
+                            border_width,
+                            start: buffer.start,
+                            end: buffer.end,
+                            left: key.left,
+                            right: key.right,
+                        };
+                        written_instances += 1;
+                    }
+                }
             }
+            draws.push((
+                window.descriptor.clone(),
+                first_instance as u64..written_instances as u64,
+            ));
         }
         drop(buffer_instances);
 
@@ -358,9 +445,8 @@ impl CakeRenderer {
         )
         .unwrap();
 
-        let (clears, pipeline, render_pass) = (
+        let (clears, render_pass) = (
             vec![Some([0.0, 0.0, 0.0, 0.0].into()), Some(1.0f32.into())],
-            &self.pipeline_clear,
             &self.render_pass_clear,
         );
 
@@ -373,21 +459,6 @@ impl CakeRenderer {
         )
         .unwrap();
 
-        let pipeline_layout = pipeline.layout();
-
-        let desc_layout = pipeline_layout.set_layouts().first().unwrap();
-        let data_descriptor = DescriptorSet::new(
-            self.sd_allocator.clone(),
-            desc_layout.clone(),
-            [WriteDescriptorSet::buffer_array(
-                0,
-                0,
-                self.buffers.buffers.iter().map(|b| b.data.clone()),
-            )],
-            [],
-        )
-        .unwrap();
-
         let subpassbegininfo = SubpassBeginInfo {
             contents: SubpassContents::Inline,
             ..Default::default()
@@ -403,34 +474,43 @@ impl CakeRenderer {
             )
             .unwrap();
 
-        unsafe {
-            command_buffer_builder
-                .bind_pipeline_graphics(pipeline.clone())
-                .unwrap()
-                .set_viewport(
-                    0,
-                    vec![Viewport {
-                        offset: [0.0, 0.0],
-                        extent: [img_dims[0] as f32, img_dims[1] as f32],
-                        depth_range: 0.0..=1.0,
-                    }]
-                    .into(),
-                )
-                .unwrap()
-                .push_constants(pipeline_layout.clone(), 0, push_constants)
-                .unwrap()
-                .bind_descriptor_sets(
-                    PipelineBindPoint::Graphics,
-                    pipeline_layout.clone(),
-                    0,
-                    data_descriptor,
-                )
-                .unwrap()
-                .bind_vertex_buffers(0, self.buffers_init.clone())
-                .unwrap()
-                .draw(written_instances as u32, 1, 0, 0)
-                .unwrap()
-        };
+        command_buffer_builder
+            .bind_pipeline_graphics(pipeline.clone())
+            .unwrap()
+            .set_viewport(
+                0,
+                vec![Viewport {
+                    offset: [0.0, 0.0],
+                    extent: [img_dims[0] as f32, img_dims[1] as f32],
+                    depth_range: 0.0..=1.0,
+                }]
+                .into(),
+            )
+            .unwrap()
+            .push_constants(pipeline_layout.clone(), 0, push_constants)
+            .unwrap();
+
+        for (descriptor, instances) in draws {
+            let count = (instances.end - instances.start) as u32;
+            if count == 0 {
+                continue;
+            }
+
+            unsafe {
+                command_buffer_builder
+                    .bind_descriptor_sets(
+                        PipelineBindPoint::Graphics,
+                        pipeline_layout.clone(),
+                        0,
+                        descriptor,
+                    )
+                    .unwrap()
+                    .bind_vertex_buffers(0, self.buffers_init.clone().slice(instances))
+                    .unwrap()
+                    .draw(count, 1, 0, 0)
+                    .unwrap()
+            };
+        }
 
         command_buffer_builder
             .end_render_pass(Default::default())
@@ -445,23 +525,35 @@ impl CakeRenderer {
 
         // Calculate the metadata before awaiting the future
         // to keep this more efficient
-        let colors = midi_file
-            .key_blocks()
-            .iter()
-            .map(|block| block.get_note_at(screen_start as u32).map(|n| n.color))
-            .collect();
-        let rendered_notes = midi_file
-            .key_blocks()
-            .iter()
-            .map(|block| {
-                let passed =
-                    block.get_notes_passed_at(screen_end) - block.get_notes_passed_at(screen_start);
-
-                if block.get_note_at(screen_start as u32).is_some() {
-                    passed as u64 + 1
-                } else {
-                    passed as u64
-                }
+        let windows = midi_file.windows().iter();
+
+        let colors = windows
+            .clone()
+            .find(|w| (w.start_time as i32) <= screen_start && screen_start < w.end_time as i32)
+            .map(|w| {
+                w.blocks
+                    .iter()
+                    .map(|block| block.get_note_at(screen_start as u32).map(|n| n.color))
+                    .collect()
+            })
+            .unwrap_or_else(|| vec![None; 256]);
+        let rendered_notes = windows
+            .flat_map(|w| {
+                let start = screen_start.max(w.start_time as i32);
+                let end = screen_end.min(w.end_time as i32);
+                w.blocks.iter().map(move |block| {
+                    if start >= end {
+                        return 0;
+                    }
+
+                    let passed = block.get_notes_passed_at(end) - block.get_notes_passed_at(start);
+
+                    if block.get_note_at(start as u32).is_some() {
+                        passed as u64 + 1
+                    } else {
+                        passed as u64
+                    }
+                })
             })
             .sum();
 
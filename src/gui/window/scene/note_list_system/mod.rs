@@ -7,7 +7,11 @@ use vulkano::image::view::ImageView;
 
 use crate::{
     gui::{window::keyboard_layout::KeyboardView, GuiRenderer},
-    midi::{DisplacedMIDINote, MIDIColor, MIDIFile, MIDINoteColumnView, MIDINoteViews},
+    midi::{
+        DisplacedMIDINote, MIDIColor, MIDIFile, MIDINoteColumnView, MIDINoteViews,
+        PERCUSSION_LANE_KEY,
+    },
+    settings::PercussionDisplay,
     utils,
 };
 
@@ -15,6 +19,63 @@ use self::notes_render_pass::{NotePassStatus, NoteRenderPass, NoteVertex};
 
 use super::RenderResultData;
 
+/// The height of percussion hit markers, relative to the height of the view
+const HIT_MARKER_HEIGHT: f32 = 0.01;
+
+/// The notes of a column that are drawn. Hidden percussion notes are left out by
+/// collecting the other notes first, as the buffer length is decided before the
+/// notes are drawn.
+enum VisibleNotes<'a, Iter: ExactSizeIterator<Item = DisplacedMIDINote>> {
+    All(Iter),
+    WithoutPercussion(std::vec::Drain<'a, DisplacedMIDINote>),
+}
+
+impl<'a, Iter: ExactSizeIterator<Item = DisplacedMIDINote>> VisibleNotes<'a, Iter> {
+    fn new<Column>(
+        column: &'a Column,
+        collected: &'a mut Vec<DisplacedMIDINote>,
+        hide_percussion: bool,
+    ) -> Self
+    where
+        Column: MIDINoteColumnView<Iter<'a> = Iter>,
+    {
+        if hide_percussion {
+            collected.clear();
+            collected.extend(
+                column
+                    .iterate_displaced_notes()
+                    .filter(|note| !note.percussion),
+            );
+            VisibleNotes::WithoutPercussion(collected.drain(..))
+        } else {
+            VisibleNotes::All(column.iterate_displaced_notes())
+        }
+    }
+}
+
+impl<Iter: ExactSizeIterator<Item = DisplacedMIDINote>> Iterator for VisibleNotes<'_, Iter> {
+    type Item = DisplacedMIDINote;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            VisibleNotes::All(iter) => iter.next(),
+            VisibleNotes::WithoutPercussion(iter) => iter.next(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self {
+            VisibleNotes::All(iter) => iter.size_hint(),
+            VisibleNotes::WithoutPercussion(iter) => iter.size_hint(),
+        }
+    }
+}
+
+impl<Iter: ExactSizeIterator<Item = DisplacedMIDINote>> ExactSizeIterator
+    for VisibleNotes<'_, Iter>
+{
+}
+
 #[derive(Default)]
 struct ColumnReturnData {
     polyphony: usize,
@@ -24,6 +85,8 @@ struct ColumnReturnData {
 pub struct NoteRenderer {
     render_pass: NoteRenderPass,
     thrad_pool: rayon::ThreadPool,
+    /// The notes of each column without the hidden percussion notes, reused every frame
+    visible_notes: Vec<Vec<DisplacedMIDINote>>,
 }
 
 struct UnsafeSyncCell<T>(UnsafeCell<T>);
@@ -47,6 +110,7 @@ impl NoteRenderer {
         NoteRenderer {
             render_pass: NoteRenderPass::new(renderer),
             thrad_pool: rayon::ThreadPoolBuilder::new().build().unwrap(),
+            visible_notes: Vec::new(),
         }
     }
 
@@ -56,13 +120,14 @@ impl NoteRenderer {
         final_image: Arc<ImageView>,
         midi_file: &mut impl MIDIFile,
         view_range: f64,
+        percussion: PercussionDisplay,
     ) -> RenderResultData {
         let note_views = midi_file.get_current_column_views(view_range);
 
         struct ColumnViewInfo<Iter: ExactSizeIterator<Item = DisplacedMIDINote> + Send> {
             offset: usize,
             iter: Iter,
-            key: u8,
+            key: u16,
             remaining: usize,
             color: Option<MIDIColor>,
             border_width: f32,
@@ -79,37 +144,30 @@ impl NoteRenderer {
             key_view.visible_range.len() as f32,
         );
 
-        // Black keys first
-        for (i, column) in columns.iter().enumerate() {
-            if key_view.key(i).black {
-                let iter = column.iterate_displaced_notes();
-                let length = iter.len();
-                columns_view_info.push(ColumnViewInfo {
-                    offset: total_notes,
-                    iter,
-                    key: i as u8,
-                    remaining: length,
-                    color: None,
-                    border_width,
-                });
-                total_notes += length;
-            }
-        }
-
-        // Then white keys after
-        for (i, column) in columns.iter().enumerate() {
-            if !key_view.key(i).black {
-                let iter = column.iterate_displaced_notes();
-                let length = iter.len();
-                columns_view_info.push(ColumnViewInfo {
-                    offset: total_notes,
-                    iter,
-                    key: i as u8,
-                    remaining: length,
-                    color: None,
-                    border_width,
-                });
-                total_notes += length;
+        let hide_percussion = percussion == PercussionDisplay::Hidden;
+        self.visible_notes.resize_with(columns.len(), Vec::new);
+        let mut column_notes: Vec<_> = columns
+            .iter()
+            .zip(self.visible_notes.iter_mut())
+            .map(|(column, collected)| Some(VisibleNotes::new(column, collected, hide_percussion)))
+            .collect();
+
+        // Black keys first, then white keys after
+        for black in [true, false] {
+            for (i, notes) in column_notes.iter_mut().enumerate() {
+                if key_view.key(i).black == black {
+                    let iter = notes.take().unwrap();
+                    let length = iter.len();
+                    columns_view_info.push(ColumnViewInfo {
+                        offset: total_notes,
+                        iter,
+                        key: i as u16,
+                        remaining: length,
+                        color: None,
+                        border_width,
+                    });
+                    total_notes += length;
+                }
             }
         }
 
@@ -119,6 +177,7 @@ impl NoteRenderer {
         let mut cycle = 0;
 
         let view_range = note_views.range().length() as f32;
+        let hit_marker_len = view_range * HIT_MARKER_HEIGHT;
 
         self.render_pass
             .draw(final_image, key_view, view_range, |buffer| {
@@ -157,15 +216,35 @@ impl NoteRenderer {
                             for i in 0..allowed_to_write {
                                 let next_note = column.iter.next();
                                 if let Some(note) = next_note {
+                                    let (start, len, key) = if note.percussion {
+                                        match percussion {
+                                            // Hidden notes were already left out
+                                            PercussionDisplay::Normal
+                                            | PercussionDisplay::Hidden => {
+                                                (note.start, note.len, column.key)
+                                            }
+                                            PercussionDisplay::HitMarkers => {
+                                                (note.start, hit_marker_len, column.key)
+                                            }
+                                            PercussionDisplay::Lane => (
+                                                note.start,
+                                                hit_marker_len,
+                                                PERCUSSION_LANE_KEY as u16,
+                                            ),
+                                        }
+                                    } else {
+                                        (note.start, note.len, column.key)
+                                    };
+
                                     buffer[i + offset] = NoteVertex::new(
-                                        note.start,
-                                        note.len,
-                                        column.key,
+                                        start,
+                                        len,
+                                        key,
                                         note.color.as_u32(),
                                         column.border_width as u32,
                                     );
 
-                                    if note.start <= 0.0 && note.start + note.len > 0.0 {
+                                    if key == column.key && start <= 0.0 && start + len > 0.0 {
                                         poly += 1;
                                         if column.color.is_none() {
                                             column.color = Some(note.color);
@@ -34,7 +34,10 @@ use vulkano::{
     sync::{self, future::FenceSignalFuture, GpuFuture},
 };
 
-use crate::gui::{window::keyboard_layout::KeyboardView, GuiRenderer};
+use crate::{
+    gui::{window::keyboard_layout::KeyboardView, GuiRenderer},
+    midi::NOTE_COLUMNS,
+};
 
 const NOTE_BUFFER_SIZE: u64 = 25000000;
 
@@ -44,17 +47,18 @@ pub struct NoteVertex {
     #[format(R32G32_SFLOAT)]
     pub start_length: [f32; 2],
     #[format(R32_UINT)]
-    pub key_color: u32,
+    pub color: u32,
+    /// The key column in the low 16 bits, and the border width in the high 16 bits
     #[format(R32_UINT)]
-    pub border_width: u32,
+    pub key_border_width: u32,
 }
 
 impl NoteVertex {
-    pub fn new(start: f32, len: f32, key: u8, color: u32, border_width: u32) -> Self {
+    pub fn new(start: f32, len: f32, key: u16, color: u32, border_width: u32) -> Self {
         Self {
             start_length: [start, len],
-            key_color: key as u32 | (color << 8),
-            border_width,
+            color,
+            key_border_width: key as u32 | (border_width.min(0xFFFF) << 16),
         }
     }
 }
@@ -119,7 +123,7 @@ pub struct NoteRenderPass {
     pipeline_draw_over: Arc<GraphicsPipeline>,
     render_pass_clear: Arc<RenderPass>,
     render_pass_draw_over: Arc<RenderPass>,
-    key_locations: Subbuffer<[[KeyPosition; 256]]>,
+    key_locations: Subbuffer<[[KeyPosition; NOTE_COLUMNS]]>,
     depth_buffer: Arc<ImageView>,
     allocator: Arc<StandardMemoryAllocator>,
     cb_allocator: Arc<StandardCommandBufferAllocator>,
@@ -209,7 +213,7 @@ impl NoteRenderPass {
                 memory_type_filter: MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
                 ..Default::default()
             },
-            [[Default::default(); 256]],
+            [[Default::default(); NOTE_COLUMNS]],
         )
         .unwrap();
 
@@ -491,17 +495,17 @@ mod vs {
         src: "
 #version 450
 layout(location = 0) in vec2 start_length;
-layout(location = 1) in uint key_color;
-layout(location = 2) in uint border_width;
+layout(location = 1) in uint color;
+layout(location = 2) in uint key_border_width;
 
 layout(location = 0) out vec2 v_start_length;
-layout(location = 1) out uint v_key_color;
-layout(location = 2) out uint v_border_width;
+layout(location = 1) out uint v_color;
+layout(location = 2) out uint v_key_border_width;
 
 void main() {
     v_start_length = start_length;
-    v_key_color = key_color;
-    v_border_width = border_width;
+    v_color = color;
+    v_key_border_width = key_border_width;
 }"
     }
 }
@@ -1,8 +1,11 @@
 use std::path::PathBuf;
+#[cfg(all(supported_os, not(target_os = "freebsd")))]
+use std::time::Instant;
 
 use soundfonts::EguiSFList;
 
 use crate::{
+    audio_playback,
     settings::{Colors, Synth, WasabiSettings},
     state::{SettingsTab, WasabiState},
     utils,
@@ -35,20 +38,24 @@ pub struct SettingsWindow {
     palettes: Vec<FilePalette>,
     #[cfg(all(supported_os, not(target_os = "freebsd")))]
     midi_devices: Vec<MidiDevice>,
+    #[cfg(all(supported_os, not(target_os = "freebsd")))]
+    midi_devices_loaded: Instant,
+    audio_devices: Vec<String>,
     sf_list: EguiSFList,
 }
 
 impl SettingsWindow {
     pub fn new(settings: &WasabiSettings) -> Self {
         let mut sf_list = EguiSFList::new();
-        for sf in settings.synth.soundfonts.iter() {
-            sf_list.add_item(sf.clone(), false);
-        }
+        sf_list.load(settings.synth.soundfonts());
 
         Self {
             palettes: Vec::new(),
             #[cfg(all(supported_os, not(target_os = "freebsd")))]
             midi_devices: Vec::new(),
+            #[cfg(all(supported_os, not(target_os = "freebsd")))]
+            midi_devices_loaded: Instant::now(),
+            audio_devices: Vec::new(),
             sf_list,
         }
     }
@@ -111,7 +118,16 @@ impl SettingsWindow {
                             let sf_kdmapi_check = false;
                             columns[3].vertical_centered_justified(|ui| {
                                 ui.add_enabled_ui(
-                                    settings.synth.synth == Synth::XSynth || sf_kdmapi_check,
+                                    settings.synth.synth == Synth::XSynth
+                                        || settings.synth.synth == Synth::XSynthRecording
+                                        || settings.synth.outputs.iter().any(|output| {
+                                            output.enabled
+                                                && matches!(
+                                                    output.synth,
+                                                    Synth::XSynth | Synth::XSynthRecording
+                                                )
+                                        })
+                                        || sf_kdmapi_check,
                                     |ui| {
                                         ui.selectable_value(
                                             &mut state.settings_tab,
@@ -162,7 +178,10 @@ impl SettingsWindow {
                             SettingsTab::Synth => {
                                 self.show_synth_settings(ui, settings, state, width)
                             }
-                            SettingsTab::SoundFonts => self.sf_list.show(ui, settings, state),
+                            SettingsTab::SoundFonts => {
+                                self.show_soundfont_profiles(ui, settings, state);
+                                self.sf_list.show(ui, settings, state)
+                            }
                         }
                     })
                 });
@@ -190,6 +209,11 @@ impl SettingsWindow {
         Ok(())
     }
 
+    pub fn load_audio_devices(&mut self) -> Result<(), WasabiError> {
+        self.audio_devices = audio_playback::audio_output_devices()?;
+        Ok(())
+    }
+
     #[cfg(any(not(supported_os), target_os = "freebsd"))]
     pub fn load_midi_devices(&mut self, _settings: &mut WasabiSettings) -> Result<(), WasabiError> {
         Ok(())
@@ -198,6 +222,7 @@ impl SettingsWindow {
     #[cfg(all(supported_os, not(target_os = "freebsd")))]
     pub fn load_midi_devices(&mut self, settings: &mut WasabiSettings) -> Result<(), WasabiError> {
         self.midi_devices.clear();
+        self.midi_devices_loaded = Instant::now();
         let con = midir::MidiOutput::new("wasabi")
             .map_err(|e| WasabiError::SynthError(format!("{e:?}")))?;
 
@@ -212,11 +237,12 @@ impl SettingsWindow {
             });
         }
 
-        // Select the device specified in settings if found, or select the first available
+        // Select the device specified in settings if found, or select the first available.
+        // A device that is missing is kept, so it can be reconnected when plugged back in.
         let saved = settings.synth.midi_device.clone();
         if let Some(found) = self.midi_devices.iter_mut().find(|d| d.name == saved) {
             found.selected = true;
-        } else if !self.midi_devices.is_empty() {
+        } else if saved.is_empty() && !self.midi_devices.is_empty() {
             self.midi_devices[0].selected = true;
             settings.synth.midi_device = self.midi_devices[0].name.clone();
         }
@@ -1,7 +1,7 @@
 use egui_extras::{Column, TableBuilder};
 
 use crate::{
-    settings::{Colors, MidiParsing, WasabiSettings},
+    settings::{Colors, LagPolicy, MidiParsing, NoteOverlap, WasabiSettings},
     state::WasabiState,
 };
 
@@ -35,6 +35,10 @@ impl SettingsWindow {
                   \0    The notes will be stored in binary trees and will be\n\
                   \0    displayed dynamically. This mode does not support\n\
                   \0    polyphony statistics.\n\
+                    - Cake (Live)\n\
+                  \0    Same as Cake, but the MIDI will be streamed from the\n\
+                  \0    disk and the trees will be built in time windows\n\
+                  \0    ahead of playback, keeping memory usage bounded.\n\
                     - Standard (RAM)\n\
                   \0    The MIDI will be loaded in the RAM and all the notes\n\
                   \0    will be rendered normally by the GPU.\n\
@@ -52,6 +56,11 @@ impl SettingsWindow {
                             MidiParsing::Cake,
                             MidiParsing::Cake.as_str(),
                         );
+                        ui.selectable_value(
+                            &mut settings.midi.parsing,
+                            MidiParsing::CakeLive,
+                            MidiParsing::CakeLive.as_str(),
+                        );
                         ui.selectable_value(
                             &mut settings.midi.parsing,
                             MidiParsing::Ram,
@@ -72,6 +81,83 @@ impl SettingsWindow {
                         .range(0.0..=100.0),
                 );
                 ui.end_row();
+
+                ui.horizontal(|ui| {
+                    ui.label("Overlapping Notes:");
+                    ui.monospace("\u{2139}").on_hover_text(
+                        "\
+                    Which note gets ended when a note off event is\n\
+                    received for a key that has multiple notes held\n\
+                    on the same track and channel.\n\
+                    - First In, First Out\n\
+                  \0    The note that started first is ended.\n\
+                    - Last In, First Out\n\
+                  \0    The note that started last is ended.\
+                    ",
+                    );
+                });
+                egui::ComboBox::from_id_salt("note_overlap_select")
+                    .selected_text(settings.midi.note_overlap.as_str())
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(
+                            &mut settings.midi.note_overlap,
+                            NoteOverlap::Fifo,
+                            NoteOverlap::Fifo.as_str(),
+                        );
+                        ui.selectable_value(
+                            &mut settings.midi.note_overlap,
+                            NoteOverlap::Lifo,
+                            NoteOverlap::Lifo.as_str(),
+                        );
+                    });
+                ui.end_row();
+
+                ui.horizontal(|ui| {
+                    ui.label("When Audio Lags:");
+                    ui.monospace("\u{2139}").on_hover_text(
+                        "\
+                    What happens when the synth falls behind the\n\
+                    playback by more than the allowed lag.\n\
+                    - Play Everything\n\
+                  \0    Every note is played, however late it is.\n\
+                    - Skip Notes\n\
+                  \0    New notes are skipped until the audio catches up,\n\
+                  \0    while the other events are still played.\n\
+                    - Pause Visuals\n\
+                  \0    The playback waits until the audio catches up.\
+                    ",
+                    );
+                });
+                egui::ComboBox::from_id_salt("lag_policy_select")
+                    .selected_text(settings.midi.lag_policy.as_str())
+                    .show_ui(ui, |ui| {
+                        for policy in [
+                            LagPolicy::None,
+                            LagPolicy::SkipNotes,
+                            LagPolicy::PauseVisuals,
+                        ] {
+                            ui.selectable_value(
+                                &mut settings.midi.lag_policy,
+                                policy,
+                                policy.as_str(),
+                            );
+                        }
+                    });
+                ui.end_row();
+
+                ui.horizontal(|ui| {
+                    ui.label("Allowed Audio Lag:");
+                    ui.monospace("\u{2139}").on_hover_text(
+                        "How late the audio can be before the lag policy applies.\nAfter seeking in Live mode, the events that are later than\nthis are skipped, whatever the policy is.",
+                    );
+                });
+                ui.add(
+                    egui::DragValue::new(&mut settings.midi.max_lag)
+                        .speed(1.0)
+                        .suffix(" ms")
+                        .range(0.0..=5000.0),
+                );
+                ui.end_row();
             });
 
         ui.horizontal(|ui| ui.add_space(width + 40.0));
@@ -2,3 +2,6 @@ mod list;
 pub use list::*;
 mod cfg;
 pub use cfg::*;
+mod inspector;
+mod profiles;
+pub use inspector::*;
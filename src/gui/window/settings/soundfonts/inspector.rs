@@ -0,0 +1,222 @@
+use std::{thread, time::Duration};
+
+use crossbeam_channel::Receiver;
+use egui::{Context, Window};
+use xsynth_core::AudioStreamParams;
+
+use crate::{
+    audio_playback::{self, SoundfontInfo},
+    settings::WasabiSoundfont,
+    utils,
+};
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+/// A window with the details of a soundfont of the list, read in the background
+pub struct SoundfontInspector {
+    pub id: usize,
+    pub open: bool,
+    soundfont: WasabiSoundfont,
+    stream_params: AudioStreamParams,
+    info: Option<Result<SoundfontInfo, String>>,
+    info_receiver: Option<Receiver<Result<SoundfontInfo, String>>>,
+    load_time: Option<Result<Duration, String>>,
+    load_receiver: Option<Receiver<Result<Duration, String>>>,
+}
+
+impl SoundfontInspector {
+    pub fn new(id: usize, soundfont: WasabiSoundfont, stream_params: AudioStreamParams) -> Self {
+        let (sender, receiver) = crossbeam_channel::bounded(1);
+        let path = soundfont.path.clone();
+        let sample_rate = stream_params.sample_rate;
+        thread::spawn(move || {
+            let info =
+                audio_playback::inspect_soundfont(&path, sample_rate).map_err(|e| e.to_string());
+            sender.send(info).ok();
+        });
+
+        Self {
+            id,
+            open: true,
+            soundfont,
+            stream_params,
+            info: None,
+            info_receiver: Some(receiver),
+            load_time: None,
+            load_receiver: None,
+        }
+    }
+
+    fn measure_load_time(&mut self) {
+        let (sender, receiver) = crossbeam_channel::bounded(1);
+        let soundfont = self.soundfont.clone();
+        let stream_params = self.stream_params;
+        thread::spawn(move || {
+            let time = audio_playback::measure_load_time(&soundfont, stream_params)
+                .map_err(|e| e.to_string());
+            sender.send(time).ok();
+        });
+
+        self.load_time = None;
+        self.load_receiver = Some(receiver);
+    }
+
+    pub fn show(&mut self, ctx: &Context) {
+        if let Some(info) = self.info_receiver.as_ref().and_then(|r| r.try_recv().ok()) {
+            self.info = Some(info);
+            self.info_receiver = None;
+        }
+        if let Some(time) = self.load_receiver.as_ref().and_then(|r| r.try_recv().ok()) {
+            self.load_time = Some(time);
+            self.load_receiver = None;
+        }
+
+        let title = if let Some(path) = self.soundfont.path.file_name() {
+            format!("Inspector for {path:?}")
+        } else {
+            format!("Inspector for {}", self.id)
+        };
+
+        let frame = utils::create_window_frame(ctx);
+        let mut open = self.open;
+        let mut measure = false;
+
+        Window::new(title)
+            .id(egui::Id::new(("sf_inspector", self.id)))
+            .collapsible(false)
+            .title_bar(true)
+            .enabled(true)
+            .frame(frame)
+            .open(&mut open)
+            .scroll([false, true])
+            .default_height(350.0)
+            .show(ctx, |ui| {
+                let info = match &self.info {
+                    Some(Ok(info)) => info,
+                    Some(Err(e)) => {
+                        ui.label(format!("The soundfont could not be read: {e}"));
+                        return;
+                    }
+                    None => {
+                        ui.horizontal(|ui| {
+                            ui.spinner();
+                            ui.label("Reading the soundfont...");
+                        });
+                        return;
+                    }
+                };
+
+                egui::Grid::new(("sf_inspector_grid", self.id))
+                    .num_columns(2)
+                    .min_col_width(160.0)
+                    .spacing(super::super::SPACING)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.label("Format:");
+                        ui.label(info.format.as_str());
+                        ui.end_row();
+
+                        ui.label("Presets:");
+                        if info.presets.is_empty() {
+                            ui.label("Single instrument");
+                        } else {
+                            ui.label(info.presets.len().to_string());
+                        }
+                        ui.end_row();
+
+                        ui.label("Samples:");
+                        ui.label(info.sample_count.to_string());
+                        ui.end_row();
+
+                        ui.horizontal(|ui| {
+                            ui.label("Estimated Memory:");
+                            ui.monospace("\u{2139}").on_hover_text(format!(
+                                "The size of the decoded samples at {} Hz.",
+                                info.sample_rate
+                            ));
+                        });
+                        ui.label(format_bytes(info.decoded_bytes));
+                        ui.end_row();
+
+                        ui.label("Load Time:");
+                        ui.horizontal(|ui| {
+                            if self.load_receiver.is_some() {
+                                ui.spinner();
+                            } else {
+                                match &self.load_time {
+                                    Some(Ok(time)) => {
+                                        ui.label(format!("{:.2} s", time.as_secs_f64()));
+                                    }
+                                    Some(Err(e)) => {
+                                        ui.label("Failed").on_hover_text(e);
+                                    }
+                                    None => {
+                                        ui.label("-");
+                                    }
+                                }
+                                if ui
+                                    .button("Measure")
+                                    .on_hover_text("Loads the soundfont once to time it")
+                                    .clicked()
+                                {
+                                    measure = true;
+                                }
+                            }
+                        });
+                        ui.end_row();
+                    });
+
+                if !info.presets.is_empty() {
+                    ui.add_space(super::super::CATEG_SPACE);
+                    ui.heading("Presets");
+                    ui.separator();
+                    egui::ScrollArea::vertical()
+                        .id_salt(("sf_inspector_presets", self.id))
+                        .max_height(200.0)
+                        .show(ui, |ui| {
+                            egui::Grid::new(("sf_inspector_presets_grid", self.id))
+                                .num_columns(3)
+                                .striped(true)
+                                .show(ui, |ui| {
+                                    ui.strong("Bank");
+                                    ui.strong("Program");
+                                    ui.strong("Name");
+                                    ui.end_row();
+
+                                    for preset in info.presets.iter() {
+                                        ui.label(preset.bank.to_string());
+                                        ui.label(preset.program.to_string());
+                                        ui.label(&preset.name);
+                                        ui.end_row();
+                                    }
+                                });
+                        });
+                }
+
+                if !info.warnings.is_empty() {
+                    ui.add_space(super::super::CATEG_SPACE);
+                    ui.heading("Warnings");
+                    ui.separator();
+                    let color = ui.visuals().warn_fg_color;
+                    for warning in info.warnings.iter() {
+                        ui.colored_label(color, format!("\u{26A0} {warning}"));
+                    }
+                }
+            });
+
+        self.open = open;
+        if measure {
+            self.measure_load_time();
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use std::{
-    io,
+    fs, io,
     path::{Path, PathBuf},
     thread,
 };
@@ -8,14 +8,16 @@ use crossbeam_channel::{Receiver, Sender};
 use egui::WidgetText;
 use egui_extras::{Column, TableBuilder};
 use serde::{Deserialize, Serialize};
+use xsynth_core::{AudioStreamParams, ChannelCount};
 
 use crate::{
     gui::window::WasabiError,
     settings::{WasabiSettings, WasabiSoundfont},
     state::WasabiState,
+    utils,
 };
 
-use super::show_sf_config;
+use super::{show_sf_config, SoundfontInspector};
 
 #[derive(Default, Clone, Serialize, Deserialize)]
 #[serde(default)]
@@ -31,16 +33,21 @@ pub struct EguiSFList {
     id_count: usize,
 
     sf_picker: (Sender<PathBuf>, Receiver<PathBuf>),
+    list_picker: (Sender<PathBuf>, Receiver<PathBuf>),
+    inspectors: Vec<SoundfontInspector>,
 }
 
 impl EguiSFList {
     pub fn new() -> Self {
         let sf_picker = crossbeam_channel::unbounded();
+        let list_picker = crossbeam_channel::unbounded();
 
         Self {
             list: Vec::new(),
             id_count: 0,
             sf_picker,
+            list_picker,
+            inspectors: Vec::new(),
         }
     }
 
@@ -55,6 +62,14 @@ impl EguiSFList {
         self.id_count += 1;
     }
 
+    /// Replaces the items with the given soundfonts
+    pub fn load(&mut self, soundfonts: &[WasabiSoundfont]) {
+        self.list.clear();
+        for sf in soundfonts {
+            self.add_item(sf.clone(), false);
+        }
+    }
+
     fn add_path(&mut self, path: PathBuf) -> Result<(), WasabiError> {
         if !path.exists() {
             return Err(WasabiError::FilesystemError(io::Error::new(
@@ -66,7 +81,7 @@ impl EguiSFList {
         let item = WasabiSoundfont {
             path,
             enabled: true,
-            options: Default::default(),
+            ..Default::default()
         };
 
         self.add_item(item, true);
@@ -74,6 +89,24 @@ impl EguiSFList {
         Ok(())
     }
 
+    /// Adds the soundfonts of an OmniMIDI or `.sflist` list. Returns the paths
+    /// that were skipped because they don't exist.
+    fn import_list(&mut self, path: &Path) -> Result<Vec<PathBuf>, WasabiError> {
+        let content = fs::read_to_string(path).map_err(WasabiError::FilesystemError)?;
+        let base = path.parent().unwrap_or(Path::new("./"));
+
+        let mut missing = Vec::new();
+        for sf in utils::parse_sf_list(&content, base)? {
+            if sf.path.exists() {
+                self.add_item(sf, false);
+            } else {
+                missing.push(sf.path);
+            }
+        }
+
+        Ok(missing)
+    }
+
     fn select_all(&mut self) {
         self.list = self
             .list
@@ -121,6 +154,60 @@ impl EguiSFList {
         self.list.iter().map(|sf| sf.item.clone()).collect()
     }
 
+    /// A grid of the soundfonts and the channels that use them
+    fn show_channel_routing(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Channel Routing")
+            .id_salt("sf_channel_routing")
+            .show(ui, |ui| {
+                ui.small(
+                    "Only applies to XSynth. Click a channel number to toggle the whole column.",
+                );
+                egui::ScrollArea::both().max_height(200.0).show(ui, |ui| {
+                    egui::Grid::new("sf_channel_grid")
+                        .striped(true)
+                        .show(ui, |ui| {
+                            ui.label("");
+                            for channel in 0..16 {
+                                if ui
+                                    .small_button(format!("{}", channel + 1))
+                                    .on_hover_text("Toggle Channel")
+                                    .clicked()
+                                {
+                                    let bit = 1 << channel;
+                                    let all =
+                                        self.list.iter().all(|sf| sf.item.channels & bit != 0);
+                                    for sf in self.list.iter_mut() {
+                                        if all {
+                                            sf.item.channels &= !bit;
+                                        } else {
+                                            sf.item.channels |= bit;
+                                        }
+                                    }
+                                }
+                            }
+                            ui.end_row();
+
+                            for sf in self.list.iter_mut() {
+                                let name = sf
+                                    .item
+                                    .path
+                                    .file_name()
+                                    .map(|name| name.to_string_lossy().to_string())
+                                    .unwrap_or_else(|| "error".into());
+                                ui.label(name);
+                                for channel in 0..16 {
+                                    let mut enabled = sf.item.channels & (1 << channel) != 0;
+                                    if ui.checkbox(&mut enabled, "").changed() {
+                                        sf.item.channels ^= 1 << channel;
+                                    }
+                                }
+                                ui.end_row();
+                            }
+                        });
+                });
+            });
+    }
+
     pub fn show(
         &mut self,
         ui: &mut egui::Ui,
@@ -168,6 +255,29 @@ impl EguiSFList {
             }
         }
 
+        // Check for lists sent by the import dialog
+        {
+            let recv = self.list_picker.1.clone();
+            if let Ok(path) = recv.try_recv() {
+                match self.import_list(&path) {
+                    Ok(missing) if !missing.is_empty() => {
+                        let paths = missing
+                            .iter()
+                            .map(|p| p.to_string_lossy())
+                            .collect::<Vec<_>>()
+                            .join("\n");
+                        state.errors.warning(format!(
+                            "These SoundFonts of the list were not found:\n{paths}"
+                        ));
+                    }
+                    Ok(_) => {}
+                    Err(err) => state
+                        .errors
+                        .warning(format!("Error importing the SoundFont list: {}", err)),
+                }
+            }
+        }
+
         // Show config windows
         for sf in self.list.iter_mut() {
             if sf.config_visible {
@@ -175,10 +285,19 @@ impl EguiSFList {
             }
         }
 
+        // Show inspectors, dropping the closed ones
+        for inspector in self.inspectors.iter_mut() {
+            inspector.show(ui.ctx());
+        }
+        self.inspectors.retain(|inspector| inspector.open);
+        let mut inspect = None;
+
         // Render action buttons
         egui::TopBottomPanel::bottom("bottom_panel")
             .resizable(false)
             .show_inside(ui, |ui| {
+                ui.add_space(5.0);
+                self.show_channel_routing(ui);
                 ui.add_space(5.0);
                 ui.columns(2, |columns| {
                     columns[0].horizontal(|ui| {
@@ -254,7 +373,7 @@ impl EguiSFList {
                         }
                     });
 
-                    columns[1].with_layout(egui::Layout::top_down(egui::Align::RIGHT), |ui| {
+                    columns[1].with_layout(egui::Layout::right_to_left(egui::Align::Min), |ui| {
                         if ui
                             .button(
                                 WidgetText::from(" \u{2705} ")
@@ -264,18 +383,90 @@ impl EguiSFList {
                             .clicked()
                         {
                             state.synth.set_soundfonts(
-                                &settings.synth.soundfonts,
+                                settings.synth.soundfonts(),
                                 state.loading_status.clone(),
                                 state.errors.clone(),
                             );
                         }
+
+                        if ui
+                            .button(
+                                WidgetText::from(" \u{1F4BE} ")
+                                    .text_style(egui::TextStyle::Name("monospace big".into())),
+                            )
+                            .on_hover_text("Export List")
+                            .clicked()
+                        {
+                            let list = self.as_vec();
+                            let errors = state.errors.clone();
+                            let last_sf_location = state.last_sf_location.clone();
+
+                            thread::spawn(move || {
+                                let path = rfd::FileDialog::new()
+                                    .add_filter("OmniMIDI List", &["txt"])
+                                    .add_filter("SoundFont List", &["sflist"])
+                                    .set_title("Export SoundFont List...")
+                                    .set_directory(
+                                        last_sf_location.parent().unwrap_or(Path::new("./")),
+                                    )
+                                    .save_file();
+
+                                if let Some(path) = path {
+                                    let content = if path
+                                        .extension()
+                                        .is_some_and(|ext| ext.eq_ignore_ascii_case("sflist"))
+                                    {
+                                        let (content, skipped) = utils::create_sflist(&list);
+                                        for sf in skipped {
+                                            errors.warning(format!(
+                                                "{} was not exported, as its path is not valid UTF-8",
+                                                sf.to_string_lossy()
+                                            ));
+                                        }
+                                        content
+                                    } else {
+                                        utils::create_om_sf_list(&list)
+                                    };
+                                    if let Err(e) = fs::write(&path, content) {
+                                        errors.error(&WasabiError::FilesystemError(e));
+                                    }
+                                }
+                            });
+                        }
+                        if ui
+                            .button(
+                                WidgetText::from(" \u{1F4C2} ")
+                                    .text_style(egui::TextStyle::Name("monospace big".into())),
+                            )
+                            .on_hover_text("Import List")
+                            .clicked()
+                        {
+                            let sender = self.list_picker.0.clone();
+                            let last_sf_location = state.last_sf_location.clone();
+
+                            thread::spawn(move || {
+                                let path = rfd::FileDialog::new()
+                                    .add_filter("SoundFont Lists", &["txt", "sflist", "json"])
+                                    .set_title("Import SoundFont List...")
+                                    .set_directory(
+                                        last_sf_location.parent().unwrap_or(Path::new("./")),
+                                    )
+                                    .pick_file();
+
+                                if let Some(path) = path {
+                                    sender.send(path).unwrap_or_default();
+                                }
+                            });
+                        }
                     });
                 });
                 ui.vertical_centered(|ui| {
                     ui.small(
                         "Loading order is bottom to top. \
                         Double click on a soundfont to modify its options. \
-                        Supported formats: SFZ, SF2",
+                        Supported formats: SFZ, SF2. \
+                        Lists can be imported from and exported to OmniMIDI and .sflist files. \
+                        Channel routing changes apply with the soundfont list.",
                     );
                 });
             });
@@ -291,6 +482,7 @@ impl EguiSFList {
                 .column(Column::exact(20.0).resizable(false))
                 .column(Column::remainder().at_least(50.0).clip(true))
                 .columns(Column::auto().at_least(40.0).clip(true).resizable(false), 2)
+                .column(Column::exact(30.0).resizable(false))
                 .header(20.0, |mut header| {
                     header.col(|_ui| {});
                     header.col(|ui| {
@@ -302,6 +494,7 @@ impl EguiSFList {
                     header.col(|ui| {
                         ui.strong("Preset");
                     });
+                    header.col(|_ui| {});
                 })
                 .body(|mut body| {
                     let row_height = super::super::SPACING[1] * 3.0;
@@ -342,12 +535,38 @@ impl EguiSFList {
                             row.col(|ui| {
                                 ui.label(preset_txt.to_string());
                             });
+
+                            row.col(|ui| {
+                                if ui
+                                    .small_button("\u{2139}")
+                                    .on_hover_text("Inspect SoundFont")
+                                    .clicked()
+                                {
+                                    inspect = Some((item.id, item.item.clone()));
+                                }
+                            });
                         });
                     }
                 });
             ui.allocate_space(ui.available_size());
         });
 
-        settings.synth.soundfonts = self.as_vec();
+        if let Some((id, soundfont)) = inspect {
+            if let Some(inspector) = self.inspectors.iter_mut().find(|i| i.id == id) {
+                inspector.open = true;
+            } else {
+                // Use the format of the running synth, so the memory estimate matches it
+                let stream_params = state.synth.stream_params().unwrap_or_else(|| {
+                    AudioStreamParams::new(
+                        settings.synth.xsynth.sample_rate.unwrap_or(48000),
+                        ChannelCount::Stereo,
+                    )
+                });
+                self.inspectors
+                    .push(SoundfontInspector::new(id, soundfont, stream_params));
+            }
+        }
+
+        settings.synth.profile_mut().soundfonts = self.as_vec();
     }
 }
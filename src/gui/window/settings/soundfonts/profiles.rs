@@ -0,0 +1,95 @@
+use crate::{
+    settings::{SoundfontProfile, WasabiSettings},
+    state::WasabiState,
+};
+
+use super::super::SettingsWindow;
+
+impl SettingsWindow {
+    /// Makes another soundfont profile active and loads its soundfonts
+    pub fn switch_soundfont_profile(
+        &mut self,
+        index: usize,
+        settings: &mut WasabiSettings,
+        state: &WasabiState,
+    ) {
+        settings.synth.active_profile = index.min(settings.synth.profiles.len() - 1);
+        self.sf_list.load(settings.synth.soundfonts());
+        state.synth.set_soundfonts(
+            settings.synth.soundfonts(),
+            state.loading_status.clone(),
+            state.errors.clone(),
+        );
+    }
+
+    pub fn show_soundfont_profiles(
+        &mut self,
+        ui: &mut egui::Ui,
+        settings: &mut WasabiSettings,
+        state: &WasabiState,
+    ) {
+        egui::TopBottomPanel::top("sf_profile_panel")
+            .resizable(false)
+            .show_inside(ui, |ui| {
+                let mut switch_to = None;
+
+                ui.horizontal(|ui| {
+                    ui.label("Profile:");
+                    egui::ComboBox::from_id_salt("sf_profile_select")
+                        .selected_text(settings.synth.profile().name.clone())
+                        .show_ui(ui, |ui| {
+                            for (i, profile) in settings.synth.profiles.iter().enumerate() {
+                                if ui
+                                    .selectable_label(
+                                        i == settings.synth.active_profile,
+                                        &profile.name,
+                                    )
+                                    .clicked()
+                                {
+                                    switch_to = Some(i);
+                                }
+                            }
+                        });
+
+                    ui.add(
+                        egui::TextEdit::singleline(&mut settings.synth.profile_mut().name)
+                            .desired_width(160.0),
+                    )
+                    .on_hover_text("Profile Name");
+
+                    if ui.button("New").clicked() {
+                        let name = format!("Profile {}", settings.synth.profiles.len() + 1);
+                        settings.synth.profiles.push(SoundfontProfile {
+                            name,
+                            soundfonts: Vec::new(),
+                        });
+                        switch_to = Some(settings.synth.profiles.len() - 1);
+                    }
+                    if ui.button("Duplicate").clicked() {
+                        let mut profile = settings.synth.profile().clone();
+                        profile.name += " (Copy)";
+                        settings.synth.profiles.push(profile);
+                        switch_to = Some(settings.synth.profiles.len() - 1);
+                    }
+                    if ui
+                        .add_enabled(
+                            settings.synth.profiles.len() > 1,
+                            egui::Button::new("Delete"),
+                        )
+                        .clicked()
+                    {
+                        settings
+                            .synth
+                            .profiles
+                            .remove(settings.synth.active_profile);
+                        switch_to = Some(settings.synth.active_profile);
+                    }
+                });
+                ui.add_space(4.0);
+
+                if let Some(index) = switch_to {
+                    self.switch_soundfont_profile(index, settings, state);
+                }
+            });
+    }
+}
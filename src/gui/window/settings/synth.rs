@@ -7,8 +7,11 @@ use super::SettingsWindow;
 
 #[cfg(supported_os)]
 mod kdmapi;
+mod limiter;
 #[cfg(all(supported_os, not(target_os = "freebsd")))]
 mod mididevice;
+mod network;
+mod outputs;
 mod xsynth;
 
 impl SettingsWindow {
@@ -16,7 +19,7 @@ impl SettingsWindow {
         &mut self,
         ui: &mut egui::Ui,
         settings: &mut WasabiSettings,
-        state: &WasabiState,
+        state: &mut WasabiState,
         width: f32,
     ) {
         egui::Grid::new("synth_settings_grid")
@@ -36,6 +39,11 @@ impl SettingsWindow {
                                 Synth::XSynth,
                                 Synth::XSynth.as_str(),
                             );
+                            ui.selectable_value(
+                                &mut settings.synth.synth,
+                                Synth::XSynthRecording,
+                                Synth::XSynthRecording.as_str(),
+                            );
                             #[cfg(supported_os)]
                             ui.selectable_value(
                                 &mut settings.synth.synth,
@@ -48,6 +56,11 @@ impl SettingsWindow {
                                 Synth::MidiDevice,
                                 Synth::MidiDevice.as_str(),
                             );
+                            ui.selectable_value(
+                                &mut settings.synth.synth,
+                                Synth::Network,
+                                Synth::Network.as_str(),
+                            );
                             ui.selectable_value(
                                 &mut settings.synth.synth,
                                 Synth::None,
@@ -72,6 +85,32 @@ impl SettingsWindow {
                     }
                 });
                 ui.end_row();
+
+                if let Some(latency) = settings.synth.latency_mut() {
+                    ui.horizontal(|ui| {
+                        ui.label("Latency Offset:");
+                        ui.monospace("\u{2139}").on_hover_text(
+                            "Sends the audio earlier by this many milliseconds,\nto make up for the latency of the synth.\nEach synth remembers its own offset.",
+                        );
+                    });
+                    ui.horizontal(|ui| {
+                        if ui
+                            .add(
+                                egui::DragValue::new(latency)
+                                    .speed(1.0)
+                                    .suffix(" ms")
+                                    .range(-1000.0..=1000.0),
+                            )
+                            .changed()
+                        {
+                            state.synth.configure(&settings.synth);
+                        }
+                        if ui.button("Calibrate").clicked() {
+                            state.show_calibration = true;
+                        }
+                    });
+                    ui.end_row();
+                }
             });
 
         ui.add_space(8.0);
@@ -83,14 +122,27 @@ impl SettingsWindow {
         ui.heading("Synth Settings");
 
         match settings.synth.synth {
-            Synth::XSynth => self.show_xsynth_settings(ui, settings, state, width),
+            Synth::XSynth | Synth::XSynthRecording => {
+                self.show_xsynth_settings(ui, settings, state, width)
+            }
             #[cfg(supported_os)]
             Synth::Kdmapi => self.show_kdmapi_settings(ui, settings, state, width),
             #[cfg(all(supported_os, not(target_os = "freebsd")))]
             Synth::MidiDevice => self.show_mididevice_settings(ui, settings, state, width),
+            Synth::Network => self.show_network_settings(ui, settings, state, width),
             Synth::None => {
                 ui.label("No Settings");
             }
         }
+
+        ui.add_space(super::CATEG_SPACE);
+        ui.heading("Note Limiter");
+        self.show_limiter_settings(ui, settings, state, width);
+
+        ui.add_space(super::CATEG_SPACE);
+        ui.heading("Outputs");
+        ui.small("The MIDI is played on every enabled output at once.");
+        ui.add_space(4.0);
+        self.show_output_settings(ui, settings, state, width);
     }
 }
@@ -0,0 +1,98 @@
+use crate::{
+    settings::{LimiterPriority, WasabiSettings},
+    state::WasabiState,
+};
+
+use super::SettingsWindow;
+
+impl SettingsWindow {
+    pub fn show_limiter_settings(
+        &mut self,
+        ui: &mut egui::Ui,
+        settings: &mut WasabiSettings,
+        state: &WasabiState,
+        width: f32,
+    ) {
+        egui::Grid::new("limiter_settings_grid")
+            .num_columns(2)
+            .spacing(super::super::SPACING)
+            .striped(true)
+            .min_col_width(width / 2.0)
+            .show(ui, |ui| {
+                let limiter = &mut settings.synth.limiter;
+                let mut changed = false;
+
+                ui.horizontal(|ui| {
+                    ui.label("Enable Limiter:");
+                    ui.monospace("\u{2139}").on_hover_text(
+                        "Drops the notes that go over the limits below, so the synth\ncan keep up with MIDIs that have millions of notes per second.\nThe dropped notes are counted in the \"Limited Notes\" statistic.",
+                    );
+                });
+                changed |= ui.checkbox(&mut limiter.enabled, "").changed();
+                ui.end_row();
+
+                ui.add_enabled_ui(limiter.enabled, |ui| ui.label("Time Slice:"));
+                changed |= ui
+                    .add_enabled(
+                        limiter.enabled,
+                        egui::DragValue::new(&mut limiter.slice)
+                            .speed(0.5)
+                            .suffix(" ms")
+                            .range(1.0..=1000.0),
+                    )
+                    .changed();
+                ui.end_row();
+
+                ui.add_enabled_ui(limiter.enabled, |ui| ui.label("Notes per Slice:"));
+                changed |= ui
+                    .add_enabled(
+                        limiter.enabled,
+                        egui::DragValue::new(&mut limiter.max_notes)
+                            .speed(10.0)
+                            .range(1..=u32::MAX),
+                    )
+                    .changed();
+                ui.end_row();
+
+                ui.add_enabled_ui(limiter.enabled, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("Notes per Key:");
+                        ui.monospace("\u{2139}").on_hover_text(
+                            "The notes played on each key of a channel in a time slice.",
+                        );
+                    });
+                });
+                changed |= ui
+                    .add_enabled(
+                        limiter.enabled,
+                        egui::DragValue::new(&mut limiter.max_per_key)
+                            .speed(1.0)
+                            .range(1..=u32::MAX),
+                    )
+                    .changed();
+                ui.end_row();
+
+                ui.add_enabled_ui(limiter.enabled, |ui| ui.label("Keep:"));
+                ui.add_enabled_ui(limiter.enabled, |ui| {
+                    egui::ComboBox::from_id_salt("limiter_priority_select")
+                        .selected_text(limiter.priority.as_str())
+                        .show_ui(ui, |ui| {
+                            for priority in [LimiterPriority::Loudest, LimiterPriority::Newest] {
+                                changed |= ui
+                                    .selectable_value(
+                                        &mut limiter.priority,
+                                        priority,
+                                        priority.as_str(),
+                                    )
+                                    .changed();
+                            }
+                        });
+                });
+                ui.end_row();
+
+                if changed {
+                    state.synth.configure(&settings.synth);
+                }
+            });
+    }
+}
@@ -1,9 +1,16 @@
+use std::time::Duration;
+
 use egui_extras::{Column, TableBuilder};
 
-use crate::{settings::WasabiSettings, state::WasabiState};
+use crate::{
+    settings::{BackpressurePolicy, WasabiSettings},
+    state::WasabiState,
+};
 
 use super::SettingsWindow;
 
+const MIDI_DEVICES_REFRESH: Duration = Duration::from_secs(2);
+
 impl SettingsWindow {
     pub fn show_mididevice_settings(
         &mut self,
@@ -12,6 +19,11 @@ impl SettingsWindow {
         state: &WasabiState,
         width: f32,
     ) {
+        // Keep the list up to date with the devices that are plugged in
+        if self.midi_devices_loaded.elapsed() >= MIDI_DEVICES_REFRESH {
+            self.load_midi_devices(settings).ok();
+        }
+
         egui::Frame::default()
             .corner_radius(egui::CornerRadius::same(8))
             .stroke(ui.style().visuals.widgets.noninteractive.bg_stroke)
@@ -56,9 +68,46 @@ impl SettingsWindow {
                     });
             });
         ui.add_space(4.0);
+        let device = &settings.synth.midi_device;
+        if !device.is_empty() && !self.midi_devices.iter().any(|d| &d.name == device) {
+            ui.label(format!(
+                "\"{device}\" is not connected. It will be used again when it is plugged in."
+            ));
+        }
         if ui.button("Refresh List").clicked() {
             self.load_midi_devices(settings)
                 .unwrap_or_else(|e| state.errors.error(&e));
         }
+
+        ui.add_space(8.0);
+        egui::Grid::new("mididevice_settings_grid")
+            .num_columns(2)
+            .spacing(super::super::SPACING)
+            .striped(true)
+            .min_col_width(width / 2.0)
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("When the device can't keep up*:");
+                    ui.monospace("\u{2139}").on_hover_text(
+                        "Dropping events keeps the playback in time.\nDropping note ons never leaves notes stuck.",
+                    );
+                });
+                egui::ComboBox::from_id_salt("backpressure_select")
+                    .selected_text(settings.synth.midi_device_backpressure.as_str())
+                    .show_ui(ui, |ui| {
+                        for policy in [
+                            BackpressurePolicy::DropOldest,
+                            BackpressurePolicy::DropNoteOns,
+                            BackpressurePolicy::Block,
+                        ] {
+                            ui.selectable_value(
+                                &mut settings.synth.midi_device_backpressure,
+                                policy,
+                                policy.as_str(),
+                            );
+                        }
+                    });
+                ui.end_row();
+            });
     }
 }
@@ -0,0 +1,89 @@
+use crate::{
+    settings::{OscAddressScheme, OscTimestamps, WasabiSettings},
+    state::WasabiState,
+};
+
+use super::SettingsWindow;
+
+impl SettingsWindow {
+    pub fn show_network_settings(
+        &mut self,
+        ui: &mut egui::Ui,
+        settings: &mut WasabiSettings,
+        state: &WasabiState,
+        width: f32,
+    ) {
+        egui::Grid::new("network_settings_grid")
+            .num_columns(2)
+            .spacing(super::super::SPACING)
+            .striped(true)
+            .min_col_width(width / 2.0)
+            .show(ui, |ui| {
+                let network = &mut settings.synth.network;
+                let mut changed = false;
+
+                ui.horizontal(|ui| {
+                    ui.label("Host*:");
+                    ui.monospace("\u{2139}").on_hover_text(
+                        "The address of the machine that receives the events.\nUse 127.0.0.1 to send them to another program on this computer.",
+                    );
+                });
+                ui.text_edit_singleline(&mut network.host);
+                ui.end_row();
+
+                ui.label("UDP Port*:");
+                ui.add(egui::DragValue::new(&mut network.port).speed(1.0));
+                ui.end_row();
+
+                ui.horizontal(|ui| {
+                    ui.label("OSC Address:");
+                    ui.monospace("\u{2139}").on_hover_text(
+                        "With raw MIDI, every event is sent to this address.\nOtherwise it is the prefix of the event addresses,\nlike /wasabi/midi/note_on, /note_off, /cc,\n/program, /pitch, /pressure and /poly_pressure.",
+                    );
+                });
+                changed |= ui.text_edit_singleline(&mut network.address).changed();
+                ui.end_row();
+
+                ui.label("Messages:");
+                egui::ComboBox::from_id_salt("osc_scheme_select")
+                    .selected_text(network.scheme.as_str())
+                    .show_ui(ui, |ui| {
+                        for scheme in [OscAddressScheme::Midi, OscAddressScheme::PerEvent] {
+                            changed |= ui
+                                .selectable_value(&mut network.scheme, scheme, scheme.as_str())
+                                .changed();
+                        }
+                    });
+                ui.end_row();
+
+                ui.horizontal(|ui| {
+                    ui.label("Timestamps:");
+                    ui.monospace("\u{2139}").on_hover_text(
+                        "Bundles send the events of each frame together in one packet.\nTimestamped bundles also carry the time they should be played at,\nwhich is the time they were sent plus the latency offset.",
+                    );
+                });
+                egui::ComboBox::from_id_salt("osc_timestamps_select")
+                    .selected_text(network.timestamps.as_str())
+                    .show_ui(ui, |ui| {
+                        for timestamps in [
+                            OscTimestamps::None,
+                            OscTimestamps::Immediate,
+                            OscTimestamps::WallClock,
+                        ] {
+                            changed |= ui
+                                .selectable_value(
+                                    &mut network.timestamps,
+                                    timestamps,
+                                    timestamps.as_str(),
+                                )
+                                .changed();
+                        }
+                    });
+                ui.end_row();
+
+                if changed {
+                    state.synth.configure(&settings.synth);
+                }
+            });
+    }
+}
@@ -0,0 +1,159 @@
+use crate::{
+    settings::{OutputRouting, Synth, SynthOutput, WasabiSettings},
+    state::WasabiState,
+};
+
+use super::SettingsWindow;
+
+impl SettingsWindow {
+    pub fn show_output_settings(
+        &mut self,
+        ui: &mut egui::Ui,
+        settings: &mut WasabiSettings,
+        state: &WasabiState,
+        width: f32,
+    ) {
+        egui::Grid::new("main_output_grid")
+            .num_columns(2)
+            .spacing(super::super::SPACING)
+            .striped(true)
+            .min_col_width(width / 2.0)
+            .show(ui, |ui| {
+                ui.label(format!("{}:", settings.synth.synth.as_str()));
+                ui.end_row();
+                Self::show_routing(ui, &mut settings.synth.routing, "main_output");
+            });
+
+        let mut remove = None;
+        let mut configure = false;
+
+        for (i, output) in settings.synth.outputs.iter_mut().enumerate() {
+            ui.add_space(8.0);
+            egui::Grid::new(("synth_output_grid", i))
+                .num_columns(2)
+                .spacing(super::super::SPACING)
+                .striped(true)
+                .min_col_width(width / 2.0)
+                .show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut output.enabled, "");
+                        egui::ComboBox::from_id_salt(("synth_output_select", i))
+                            .selected_text(output.synth.as_str())
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(
+                                    &mut output.synth,
+                                    Synth::XSynth,
+                                    Synth::XSynth.as_str(),
+                                );
+                                ui.selectable_value(
+                                    &mut output.synth,
+                                    Synth::XSynthRecording,
+                                    Synth::XSynthRecording.as_str(),
+                                );
+                                #[cfg(supported_os)]
+                                ui.selectable_value(
+                                    &mut output.synth,
+                                    Synth::Kdmapi,
+                                    Synth::Kdmapi.as_str(),
+                                );
+                                #[cfg(all(supported_os, not(target_os = "freebsd")))]
+                                ui.selectable_value(
+                                    &mut output.synth,
+                                    Synth::MidiDevice,
+                                    Synth::MidiDevice.as_str(),
+                                );
+                                ui.selectable_value(
+                                    &mut output.synth,
+                                    Synth::Network,
+                                    Synth::Network.as_str(),
+                                );
+                            });
+                    });
+                    if ui.button("Remove").clicked() {
+                        remove = Some(i);
+                    }
+                    ui.end_row();
+
+                    #[cfg(all(supported_os, not(target_os = "freebsd")))]
+                    if output.synth == Synth::MidiDevice {
+                        ui.label("Device*:");
+                        egui::ComboBox::from_id_salt(("synth_output_device", i))
+                            .selected_text(output.midi_device.as_str())
+                            .show_ui(ui, |ui| {
+                                for device in self.midi_devices.iter() {
+                                    ui.selectable_value(
+                                        &mut output.midi_device,
+                                        device.name.clone(),
+                                        device.name.as_str(),
+                                    );
+                                }
+                            });
+                        ui.end_row();
+                    }
+
+                    ui.label("Latency Offset:");
+                    if ui
+                        .add(
+                            egui::DragValue::new(&mut output.latency)
+                                .speed(1.0)
+                                .suffix(" ms")
+                                .range(-1000.0..=1000.0),
+                        )
+                        .changed()
+                    {
+                        configure = true;
+                    }
+                    ui.end_row();
+
+                    Self::show_routing(ui, &mut output.routing, ("synth_output", i));
+                });
+        }
+
+        if let Some(i) = remove {
+            settings.synth.outputs.remove(i);
+        }
+        if configure {
+            state.synth.configure(&settings.synth);
+        }
+
+        ui.add_space(8.0);
+        ui.horizontal(|ui| {
+            if ui.button("Add Output").clicked() {
+                settings.synth.outputs.push(SynthOutput::default());
+            }
+            if ui.button("Apply").clicked() {
+                state.synth.switch(
+                    &settings.synth,
+                    state.loading_status.clone(),
+                    state.errors.clone(),
+                );
+            }
+        });
+    }
+
+    fn show_routing(ui: &mut egui::Ui, routing: &mut OutputRouting, id: impl std::hash::Hash) {
+        ui.label("Channels*:");
+        ui.push_id(id, |ui| {
+            ui.horizontal_wrapped(|ui| {
+                for channel in 0..16 {
+                    let mut enabled = routing.channels & (1 << channel) != 0;
+                    if ui
+                        .toggle_value(&mut enabled, format!("{}", channel + 1))
+                        .changed()
+                    {
+                        routing.channels ^= 1 << channel;
+                    }
+                }
+            });
+        });
+        ui.end_row();
+
+        ui.horizontal(|ui| {
+            ui.label("Tracks*:");
+            ui.monospace("\u{2139}")
+                .on_hover_text("A list of track numbers and ranges, like \"1-4, 7\".\nLeave empty to send all tracks.");
+        });
+        ui.add(egui::TextEdit::singleline(&mut routing.tracks).hint_text("All"));
+        ui.end_row();
+    }
+}
@@ -4,6 +4,9 @@ use crate::{settings::WasabiSettings, state::WasabiState};
 
 use super::SettingsWindow;
 
+const SAMPLE_RATES: [u32; 5] = [44100, 48000, 88200, 96000, 192000];
+const BUFFER_SIZES: [u32; 7] = [64, 128, 256, 512, 1024, 2048, 4096];
+
 impl SettingsWindow {
     pub fn show_xsynth_settings(
         &mut self,
@@ -18,6 +21,91 @@ impl SettingsWindow {
             .striped(true)
             .min_col_width(width / 2.0)
             .show(ui, |ui| {
+                ui.label("Audio Device*:");
+                ui.horizontal(|ui| {
+                    let selected = if settings.synth.xsynth.audio_device.is_empty() {
+                        "Default"
+                    } else {
+                        settings.synth.xsynth.audio_device.as_str()
+                    };
+                    egui::ComboBox::from_id_salt("audio_device_select")
+                        .selected_text(selected.to_owned())
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(
+                                &mut settings.synth.xsynth.audio_device,
+                                String::new(),
+                                "Default",
+                            );
+                            for device in self.audio_devices.iter() {
+                                ui.selectable_value(
+                                    &mut settings.synth.xsynth.audio_device,
+                                    device.clone(),
+                                    device.as_str(),
+                                );
+                            }
+                        });
+                    if ui.button("Refresh").clicked() {
+                        self.load_audio_devices()
+                            .unwrap_or_else(|e| state.errors.error(&e));
+                    }
+                });
+                ui.end_row();
+
+                ui.horizontal(|ui| {
+                    ui.label("Sample Rate*:");
+                    ui.monospace("\u{2139}").on_hover_text(
+                        "If the device doesn't support the selected rate,\nthe default device and rate are used.",
+                    );
+                });
+                egui::ComboBox::from_id_salt("sample_rate_select")
+                    .selected_text(match settings.synth.xsynth.sample_rate {
+                        Some(rate) => format!("{rate} Hz"),
+                        None => "Default".to_owned(),
+                    })
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut settings.synth.xsynth.sample_rate, None, "Default");
+                        for rate in SAMPLE_RATES {
+                            ui.selectable_value(
+                                &mut settings.synth.xsynth.sample_rate,
+                                Some(rate),
+                                format!("{rate} Hz"),
+                            );
+                        }
+                    });
+                ui.end_row();
+
+                ui.horizontal(|ui| {
+                    ui.label("Buffer Size*:");
+                    ui.monospace("\u{2139}").on_hover_text(
+                        "The samples the device plays at once. Smaller buffers\nlower the latency, but the audio may crackle.\nIf the device doesn't support the selected size,\nthe default device and size are used.",
+                    );
+                });
+                egui::ComboBox::from_id_salt("buffer_size_select")
+                    .selected_text(match settings.synth.xsynth.buffer_size {
+                        Some(size) => format!("{size} samples"),
+                        None => "Default".to_owned(),
+                    })
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut settings.synth.xsynth.buffer_size, None, "Default");
+                        for size in BUFFER_SIZES {
+                            ui.selectable_value(
+                                &mut settings.synth.xsynth.buffer_size,
+                                Some(size),
+                                format!("{size} samples"),
+                            );
+                        }
+                    });
+                ui.end_row();
+
+                ui.horizontal(|ui| {
+                    ui.label("Sync to Audio Clock*:");
+                    ui.monospace("\u{2139}").on_hover_text(
+                        "Times the playback by the samples played on the audio device\ninstead of the system clock, so the visuals stay in sync\nwith the sound when the device clock drifts.",
+                    );
+                });
+                ui.checkbox(&mut settings.synth.xsynth.audio_clock, "");
+                ui.end_row();
+
                 let layer_limit_prev = settings.synth.xsynth.limit_layers;
                 let layer_count_prev = settings.synth.xsynth.layers;
 
@@ -1,7 +1,10 @@
 use egui::WidgetText;
 use egui_extras::{Column, TableBuilder};
 
-use crate::{settings::WasabiSettings, utils::NOTE_SPEED_RANGE};
+use crate::{
+    settings::{PercussionDisplay, WasabiSettings},
+    utils::NOTE_SPEED_RANGE,
+};
 
 use super::SettingsWindow;
 
@@ -86,6 +89,46 @@ impl SettingsWindow {
                         .logarithmic(true),
                 );
                 ui.end_row();
+
+                ui.horizontal(|ui| {
+                    ui.label("Percussion: ");
+                    ui.monospace("\u{2139}").on_hover_text(
+                        "\
+                    How the notes of the percussion channel (channel 10)\n\
+                    are displayed. In the Cake parsing modes, this is\n\
+                    applied when a new MIDI is loaded.\
+                    ",
+                    );
+                });
+                egui::ComboBox::from_id_salt("percussion_select")
+                    .selected_text(settings.scene.percussion.as_str())
+                    .show_ui(ui, |ui| {
+                        for percussion in [
+                            PercussionDisplay::Normal,
+                            PercussionDisplay::Hidden,
+                            PercussionDisplay::HitMarkers,
+                            PercussionDisplay::Lane,
+                        ] {
+                            ui.selectable_value(
+                                &mut settings.scene.percussion,
+                                percussion,
+                                percussion.as_str(),
+                            );
+                        }
+                    });
+                ui.end_row();
+
+                ui.horizontal(|ui| {
+                    ui.label("Show Markers and Lyrics: ");
+                    ui.monospace("\u{2139}").on_hover_text(
+                        "\
+                    Shows the current marker, lyrics and text\n\
+                    events of the MIDI over the notes.\
+                    ",
+                    );
+                });
+                ui.checkbox(&mut settings.scene.meta_overlay, "");
+                ui.end_row();
             });
 
         ui.add_space(super::CATEG_SPACE);
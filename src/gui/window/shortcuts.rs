@@ -58,6 +58,18 @@ impl GuiWasabiWindow {
                         ui.label("Ctrl + O");
                         ui.end_row();
 
+                        ui.label("Add Bookmark");
+                        ui.label("Ctrl + B");
+                        ui.end_row();
+
+                        ui.label("Jump to Bookmark");
+                        ui.label("1 - 9");
+                        ui.end_row();
+
+                        ui.label("Start / Stop Recording");
+                        ui.label("Ctrl + R");
+                        ui.end_row();
+
                         ui.label("Reset Synthesizer");
                         ui.label("Insert");
                         ui.end_row();
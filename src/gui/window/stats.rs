@@ -4,6 +4,7 @@ use egui::{Context, Frame, Pos2};
 use numfmt::{Formatter, Precision};
 
 use crate::{
+    audio_playback::SynthStats,
     gui::window::GuiWasabiWindow,
     midi::{MIDIFileBase, MIDIFileStats},
     settings::{Statistics, WasabiSettings},
@@ -15,7 +16,7 @@ pub struct GuiMidiStats {
     time_total: f64,
     notes_on_screen: u64,
     polyphony: Option<u64>,
-    voice_count: Option<u64>,
+    synth: SynthStats,
 }
 
 impl GuiMidiStats {
@@ -25,12 +26,12 @@ impl GuiMidiStats {
             time_total: 0.0,
             notes_on_screen: 0,
             polyphony: None,
-            voice_count: None,
+            synth: SynthStats::default(),
         }
     }
 
-    pub fn set_voice_count(&mut self, voices: Option<u64>) {
-        self.voice_count = voices;
+    pub fn set_synth_stats(&mut self, synth: SynthStats) {
+        self.synth = synth;
     }
 
     pub fn set_rendered_note_count(&mut self, notes: u64) {
@@ -95,6 +96,7 @@ impl GuiWasabiWindow {
                     .precision(Precision::Decimals(0));
 
                 let mut note_stats = MIDIFileStats::default();
+                let mut audio_lag = None;
                 if let Some(midi_file) = self.midi_file.as_mut() {
                     stats.time_total = midi_file.midi_length().unwrap_or(0.0);
                     let time = midi_file.timer().get_time().as_seconds_f64();
@@ -106,6 +108,7 @@ impl GuiWasabiWindow {
                     }
 
                     note_stats = midi_file.stats();
+                    audio_lag = Some(midi_file.timer().audio_lag());
                 }
 
                 for i in settings.scene.statistics.order.iter().filter(|i| i.1) {
@@ -137,7 +140,7 @@ impl GuiWasabiWindow {
                             });
                         }
                         Statistics::VoiceCount => {
-                            if let Some(voice_count) = stats.voice_count {
+                            if let Some(voice_count) = stats.synth.voice_count {
                                 ui.horizontal(|ui| {
                                     ui.monospace("Voice Count:");
                                     ui.with_layout(
@@ -187,6 +190,113 @@ impl GuiWasabiWindow {
                                 );
                             });
                         }
+                        Statistics::DroppedEvents => {
+                            if let Some(dropped) = stats.synth.dropped_events {
+                                ui.horizontal(|ui| {
+                                    ui.monospace("Dropped Events:");
+                                    ui.with_layout(
+                                        egui::Layout::right_to_left(egui::Align::Center),
+                                        |ui| {
+                                            ui.monospace(f.fmt2(dropped).to_string());
+                                        },
+                                    );
+                                });
+                            }
+                        }
+                        Statistics::EventsPerSecond => {
+                            ui.horizontal(|ui| {
+                                ui.monospace("Events/s:");
+                                ui.with_layout(
+                                    egui::Layout::right_to_left(egui::Align::Center),
+                                    |ui| {
+                                        self.eps.tick(stats.synth.events_pushed as i64);
+                                        ui.monospace(f.fmt2(self.eps.read()).to_string());
+                                    },
+                                );
+                            });
+                        }
+                        Statistics::IgnoredNotes => {
+                            if let Some(ignored) = stats.synth.ignored_notes {
+                                ui.horizontal(|ui| {
+                                    ui.monospace("Ignored Notes:");
+                                    ui.with_layout(
+                                        egui::Layout::right_to_left(egui::Align::Center),
+                                        |ui| {
+                                            ui.monospace(f.fmt2(ignored).to_string());
+                                        },
+                                    );
+                                });
+                            }
+                        }
+                        Statistics::LimitedNotes => {
+                            if let Some(limited) = stats.synth.limited_notes {
+                                ui.horizontal(|ui| {
+                                    ui.monospace("Limited Notes:");
+                                    ui.with_layout(
+                                        egui::Layout::right_to_left(egui::Align::Center),
+                                        |ui| {
+                                            ui.monospace(f.fmt2(limited).to_string());
+                                        },
+                                    );
+                                });
+                            }
+                        }
+                        Statistics::AudioLag => {
+                            if let Some(lag) = audio_lag {
+                                ui.horizontal(|ui| {
+                                    ui.monospace("Audio Lag:");
+                                    ui.with_layout(
+                                        egui::Layout::right_to_left(egui::Align::Center),
+                                        |ui| {
+                                            ui.monospace(format!("{:.0} ms", lag * 1000.0));
+                                        },
+                                    );
+                                });
+                            }
+                        }
+                        Statistics::RenderLoad => {
+                            if let Some(load) = stats.synth.render_load {
+                                ui.horizontal(|ui| {
+                                    ui.monospace("Render Load:");
+                                    ui.with_layout(
+                                        egui::Layout::right_to_left(egui::Align::Center),
+                                        |ui| {
+                                            ui.monospace(format!("{:.0}%", load * 100.0));
+                                        },
+                                    );
+                                });
+                            }
+                        }
+                        Statistics::BufferUnderruns => {
+                            if let Some(underruns) = stats.synth.buffer_underruns {
+                                ui.horizontal(|ui| {
+                                    ui.monospace("Underruns:");
+                                    ui.with_layout(
+                                        egui::Layout::right_to_left(egui::Align::Center),
+                                        |ui| {
+                                            ui.monospace(f.fmt2(underruns).to_string());
+                                        },
+                                    );
+                                });
+                            }
+                        }
+                        Statistics::ChannelVoices => {
+                            if let Some(channel_voices) = stats.synth.channel_voices {
+                                ui.monospace("Channel Voices:");
+                                egui::Grid::new("channel_voices_grid")
+                                    .num_columns(8)
+                                    .spacing([4.0, 0.0])
+                                    .show(ui, |ui| {
+                                        for (i, voices) in channel_voices.iter().enumerate() {
+                                            ui.small(f.fmt2(*voices).to_string())
+                                                .on_hover_text(format!("Channel {}", i + 1));
+                                            if i % 8 == 7 {
+                                                ui.end_row();
+                                            }
+                                        }
+                                    });
+                            }
+                        }
                         Statistics::Polyphony => {
                             if let Some(poly) = stats.polyphony {
                                 ui.horizontal(|ui| {
@@ -0,0 +1,302 @@
+use std::{
+    io::{self, BufRead, Write},
+    path::PathBuf,
+    process::ExitCode,
+    str::FromStr,
+    sync::Arc,
+    thread,
+    time::Duration,
+};
+
+use crossbeam_channel::{Receiver, TryRecvError};
+use time::Duration as TimeDuration;
+
+use crate::{
+    audio_playback::WasabiAudioPlayer,
+    gui::window::{GuiMessageSystem, LoadingStatus, WasabiError},
+    midi::{
+        CakeMIDIFile, InRamMIDIFile, LiveLoadMIDIFile, MIDIFile, MIDIFileBase, MIDIFileUnion,
+        MIDILayer,
+    },
+    settings::{MidiParsing, Synth, WasabiSettings},
+    utils,
+};
+
+const STATUS_INTERVAL: Duration = Duration::from_millis(100);
+const SEEK_STEP: f64 = 5.0;
+
+const USAGE: &str = "Usage: wasabi --headless [--synth <name>] [--parsing <mode>] <midi file>
+
+Plays a MIDI through the synth without opening a window.
+  --synth    xsynth, xsynthrecording, kdmapi, mididevice, network or none
+  --parsing  ram, live, cake or cakelive
+
+Type a command and press enter while playing:
+  p          pause or resume
+  f / b      seek 5 seconds forward / back
+  s <time>   seek to a time, in seconds or as mm:ss
+  r          restart
+  q          quit";
+
+/// The options of the audio only mode, read from the command line
+pub struct HeadlessArgs {
+    midi: PathBuf,
+    synth: Option<Synth>,
+    parsing: Option<MidiParsing>,
+}
+
+impl HeadlessArgs {
+    /// Returns `None` if the headless mode wasn't requested
+    pub fn parse(args: impl Iterator<Item = String>) -> Option<Result<Self, String>> {
+        let args: Vec<String> = args.collect();
+        if !args.iter().any(|arg| arg == "--headless") {
+            return None;
+        }
+
+        Some(Self::parse_options(
+            args.into_iter().filter(|arg| arg != "--headless"),
+        ))
+    }
+
+    fn parse_options(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut midi = None;
+        let mut synth = None;
+        let mut parsing = None;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--synth" => {
+                    let value = args.next().ok_or("--synth needs a value")?;
+                    synth = Some(Synth::from_str(&value)?);
+                }
+                "--parsing" => {
+                    let value = args.next().ok_or("--parsing needs a value")?;
+                    parsing = Some(MidiParsing::from_str(&value)?);
+                }
+                "-h" | "--help" => return Err(USAGE.into()),
+                _ if midi.is_none() => midi = Some(PathBuf::from(arg)),
+                _ => return Err(format!("Unexpected argument: {arg}\n\n{USAGE}")),
+            }
+        }
+
+        Ok(Self {
+            midi: midi.ok_or_else(|| format!("No MIDI file was given\n\n{USAGE}"))?,
+            synth,
+            parsing,
+        })
+    }
+}
+
+enum Command {
+    TogglePause,
+    Seek(f64),
+    SeekTo(f64),
+    Quit,
+}
+
+/// Parses a time like `90`, `1:30` or `1:30.5` to seconds
+fn parse_time(text: &str) -> Option<f64> {
+    match text.split_once(':') {
+        Some((min, sec)) => {
+            Some(min.trim().parse::<f64>().ok()? * 60.0 + sec.trim().parse::<f64>().ok()?)
+        }
+        None => text.trim().parse().ok(),
+    }
+}
+
+/// Reads the commands typed into the terminal
+fn spawn_input_reader() -> Receiver<Command> {
+    let (sender, receiver) = crossbeam_channel::unbounded();
+
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            let line = line.trim();
+            let command = match line.split_once(' ').unwrap_or((line, "")) {
+                ("p" | "", _) => Command::TogglePause,
+                ("f", _) => Command::Seek(SEEK_STEP),
+                ("b", _) => Command::Seek(-SEEK_STEP),
+                ("r", _) => Command::SeekTo(0.0),
+                ("s", time) => match parse_time(time) {
+                    Some(time) => Command::SeekTo(time),
+                    None => {
+                        eprintln!("\nInvalid time: {time}");
+                        continue;
+                    }
+                },
+                ("q", _) => Command::Quit,
+                _ => {
+                    eprintln!("\nUnknown command: {line}");
+                    continue;
+                }
+            };
+            if sender.send(command).is_err() {
+                break;
+            }
+        }
+    });
+
+    receiver
+}
+
+fn print_messages(errors: &GuiMessageSystem) {
+    for (title, message) in errors.take_messages() {
+        eprintln!("\n{title}: {message}");
+    }
+}
+
+fn time_string(seconds: f64) -> String {
+    utils::convert_seconds_to_time_string(seconds).replace('\0', "")
+}
+
+fn load_midi(
+    path: PathBuf,
+    settings: &WasabiSettings,
+    synth: Arc<WasabiAudioPlayer>,
+) -> Result<MIDIFileUnion, WasabiError> {
+    let layers = vec![MIDILayer::new(path)];
+    let percussion = settings.scene.percussion;
+    let midi = &settings.midi;
+
+    Ok(match midi.parsing {
+        MidiParsing::Ram => {
+            MIDIFileUnion::InRam(InRamMIDIFile::load_from_layers(&layers, synth, midi)?)
+        }
+        MidiParsing::Live => {
+            MIDIFileUnion::Live(LiveLoadMIDIFile::load_from_layers(&layers, synth, midi)?)
+        }
+        MidiParsing::Cake => MIDIFileUnion::Cake(CakeMIDIFile::load_from_layers(
+            &layers, synth, midi, percussion,
+        )?),
+        MidiParsing::CakeLive => MIDIFileUnion::Cake(CakeMIDIFile::load_streaming_from_layers(
+            &layers, synth, midi, percussion,
+        )?),
+    })
+}
+
+/// The notes that were played so far. The note views and the cake windows are
+/// what keep this count up to date, so they are updated even though nothing is drawn.
+fn passed_notes(midi: &mut MIDIFileUnion) -> Option<u64> {
+    match midi {
+        MIDIFileUnion::InRam(midi) => {
+            midi.get_current_column_views(0.0);
+        }
+        MIDIFileUnion::Live(midi) => {
+            midi.get_current_column_views(0.0);
+        }
+        // Also receives and evicts the windows when streaming
+        MIDIFileUnion::Cake(midi) => midi.update_windows(),
+    }
+    midi.stats().passed_notes
+}
+
+/// Plays a MIDI through the synth with no window, printing the progress to the terminal
+pub fn run(args: HeadlessArgs) -> ExitCode {
+    let errors = GuiMessageSystem::new();
+    let loading_status = LoadingStatus::new();
+    let synth = WasabiAudioPlayer::empty();
+
+    let mut settings = WasabiSettings::new_or_load().unwrap_or_else(|e| {
+        errors.error(&e);
+        WasabiSettings::default()
+    });
+    if let Some(s) = args.synth {
+        settings.synth.synth = s;
+    }
+    if let Some(parsing) = args.parsing {
+        settings.midi.parsing = parsing;
+    }
+
+    println!("Synth: {}", settings.synth.synth.as_str());
+    synth.switch(&settings.synth, loading_status.clone(), errors.clone());
+    if loading_status.is_loading() {
+        println!("Loading soundfonts...");
+        while loading_status.is_loading() {
+            thread::sleep(STATUS_INTERVAL);
+        }
+    }
+    print_messages(&errors);
+
+    println!("Parsing {:?}...", args.midi);
+    let mut midi = match load_midi(args.midi, &settings, synth.clone()) {
+        Ok(midi) => midi,
+        Err(e) => {
+            print_messages(&errors);
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let start = -settings.midi.start_delay;
+    let total_notes = midi.stats().total_notes;
+
+    println!("{}", &USAGE[USAGE.find("Type").unwrap_or(0)..]);
+    let commands = spawn_input_reader();
+    midi.timer_mut().play();
+
+    loop {
+        match commands.try_recv() {
+            Ok(Command::TogglePause) => midi.timer_mut().toggle_pause(),
+            Ok(Command::Seek(step)) => {
+                let time = midi.timer().get_time().as_seconds_f64();
+                if step > 0.0 || midi.allows_seeking_backward() {
+                    let time = (time + step).max(start);
+                    midi.timer_mut().seek(TimeDuration::seconds_f64(time));
+                }
+            }
+            Ok(Command::SeekTo(time)) => {
+                let current = midi.timer().get_time().as_seconds_f64();
+                if time >= current || midi.allows_seeking_backward() {
+                    let time = time.max(start);
+                    midi.timer_mut().seek(TimeDuration::seconds_f64(time));
+                }
+            }
+            Ok(Command::Quit) => break,
+            Err(TryRecvError::Empty) => {}
+            // Without a terminal, play until the end
+            Err(TryRecvError::Disconnected) => {}
+        }
+
+        print_messages(&errors);
+
+        let time = midi.timer().get_time().as_seconds_f64();
+        let length = midi.midi_length();
+        let passed = passed_notes(&mut midi);
+        let stats = synth.stats();
+
+        let mut status = format!(
+            "\r{} / {}",
+            time_string(time),
+            length.map(time_string).unwrap_or_else(|| "-".into())
+        );
+        if let Some(passed) = passed {
+            status += &format!(" | Notes: {passed}");
+            if let Some(total) = total_notes {
+                status += &format!(" / {total}");
+            }
+        }
+        if let Some(voices) = stats.voice_count {
+            status += &format!(" | Voices: {voices}");
+        }
+        if midi.timer().is_paused() {
+            status += " | Paused";
+        }
+        // Clear what is left of a longer previous line
+        print!("{status:<100}");
+        io::stdout().flush().ok();
+
+        if length.is_some_and(|length| time > length) && !midi.timer().is_paused() {
+            break;
+        }
+
+        thread::sleep(STATUS_INTERVAL);
+    }
+
+    println!();
+    midi.timer_mut().pause();
+    synth.reset();
+    print_messages(&errors);
+
+    ExitCode::SUCCESS
+}
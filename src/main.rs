@@ -5,6 +5,7 @@
 mod app;
 mod audio_playback;
 mod gui;
+mod headless;
 mod midi;
 mod renderer;
 mod scenes;
@@ -12,6 +13,8 @@ mod settings;
 mod state;
 mod utils;
 
+use std::process::ExitCode;
+
 use app::WasabiApplication;
 use vulkano::swapchain::PresentMode;
 
@@ -30,10 +33,22 @@ pub const PRESENT_MODE: PresentMode = PresentMode::Immediate;
 pub const WAYLAND_PRESENT_MODE: PresentMode = PresentMode::Mailbox;
 pub const VSYNC_PRESENT_MODE: PresentMode = PresentMode::Fifo;
 
-pub fn main() {
+pub fn main() -> ExitCode {
+    // The audio only mode runs without a window
+    if let Some(args) = headless::HeadlessArgs::parse(std::env::args().skip(1)) {
+        return match args {
+            Ok(args) => headless::run(args),
+            Err(e) => {
+                eprintln!("{e}");
+                ExitCode::FAILURE
+            }
+        };
+    }
+
     let event_loop = EventLoop::new().unwrap();
     event_loop.set_control_flow(ControlFlow::Poll);
 
     let mut app = WasabiApplication::new();
     event_loop.run_app(&mut app).unwrap();
+    ExitCode::SUCCESS
 }
@@ -10,6 +10,7 @@ use crate::{
     audio_playback::WasabiAudioPlayer,
     midi::shared::{
         audio::CompressedAudio,
+        lag::{LagAction, LagMonitor},
         timer::{TimeListener, UnpauseWaitResult, WaitResult},
     },
 };
@@ -18,6 +19,7 @@ pub struct LiveAudioPlayer {
     events: Receiver<CompressedAudio>,
     timer: TimeListener,
     player: Arc<WasabiAudioPlayer>,
+    lag: LagMonitor,
 }
 
 impl LiveAudioPlayer {
@@ -25,11 +27,13 @@ impl LiveAudioPlayer {
         events: Receiver<CompressedAudio>,
         timer: TimeListener,
         player: Arc<WasabiAudioPlayer>,
+        lag: LagMonitor,
     ) -> Self {
         LiveAudioPlayer {
             events,
-            timer,
+            timer: timer.with_offset(player.latency()),
             player,
+            lag,
         }
     }
 
@@ -37,10 +41,9 @@ impl LiveAudioPlayer {
         thread::spawn(move || {
             let mut seek_catching_up = false;
 
-            let max_fall_time = 0.1;
-
             let push_cc = |e: &CompressedAudio| {
-                self.player.push_events(e.iter_control_events());
+                self.player
+                    .push_track_events(e.track, e.iter_control_events());
             };
 
             for event in self.events.into_iter() {
@@ -49,7 +52,7 @@ impl LiveAudioPlayer {
                     match self.timer.wait_until_unpause() {
                         UnpauseWaitResult::Unpaused => push_cc(&event),
                         UnpauseWaitResult::UnpausedAndSeeked(time) => {
-                            if time.as_seconds_f64() - event.time > max_fall_time {
+                            if self.lag.is_late(time.as_seconds_f64(), event.time) {
                                 seek_catching_up = true;
                             }
                             continue;
@@ -60,7 +63,7 @@ impl LiveAudioPlayer {
 
                 if seek_catching_up {
                     let time = self.timer.get_time().as_seconds_f64();
-                    if time - event.time > max_fall_time {
+                    if self.lag.is_late(time, event.time) {
                         push_cc(&event);
                         continue;
                     } else {
@@ -68,15 +71,19 @@ impl LiveAudioPlayer {
                     }
                 }
 
+                let action = self.lag.check(&self.timer, event.time);
+
                 let time = Duration::seconds_f64(event.time);
                 match self.timer.wait_until(time) {
                     WaitResult::Ok => {}
                     WaitResult::Paused => {
+                        self.lag.reset(&self.timer);
                         continue;
                     }
                     WaitResult::Seeked(time) => {
                         self.player.reset();
-                        if time.as_seconds_f64() - event.time > max_fall_time {
+                        self.lag.reset(&self.timer);
+                        if self.lag.is_late(time.as_seconds_f64(), event.time) {
                             seek_catching_up = true;
                         }
                         continue;
@@ -87,7 +94,14 @@ impl LiveAudioPlayer {
                     }
                 }
 
-                self.player.push_events(event.iter_events());
+                match action {
+                    LagAction::Push => self
+                        .player
+                        .push_track_events(event.track, event.iter_events()),
+                    LagAction::SkipNotes => self
+                        .player
+                        .push_track_events(event.track, event.iter_events_without_notes()),
+                }
             }
         })
     }
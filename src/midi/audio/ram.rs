@@ -8,6 +8,7 @@ use crate::{
     audio_playback::WasabiAudioPlayer,
     midi::shared::{
         audio::CompressedAudio,
+        lag::{LagAction, LagMonitor},
         timer::{SeekWaitResult, TimeListener, UnpauseWaitResult, WaitResult},
     },
 };
@@ -16,6 +17,7 @@ pub struct InRamAudioPlayer {
     events: Vec<CompressedAudio>,
     timer: TimeListener,
     player: Arc<WasabiAudioPlayer>,
+    lag: LagMonitor,
     index: usize,
 }
 
@@ -24,11 +26,13 @@ impl InRamAudioPlayer {
         events: Vec<CompressedAudio>,
         timer: TimeListener,
         player: Arc<WasabiAudioPlayer>,
+        lag: LagMonitor,
     ) -> Self {
         InRamAudioPlayer {
             events,
-            timer,
+            timer: timer.with_offset(player.latency()),
             player,
+            lag,
             index: 0,
         }
     }
@@ -66,14 +70,18 @@ impl InRamAudioPlayer {
 
             let event = &self.events[self.index];
 
+            let action = self.lag.check(&self.timer, event.time);
+
             let time = Duration::seconds_f64(event.time);
             match self.timer.wait_until(time) {
                 WaitResult::Ok => {}
                 WaitResult::Paused => {
+                    self.lag.reset(&self.timer);
                     continue;
                 }
                 WaitResult::Seeked(time) => {
                     reset();
+                    self.lag.reset(&self.timer);
                     self.seek_to_time(time.as_seconds_f64());
                     continue;
                 }
@@ -83,7 +91,14 @@ impl InRamAudioPlayer {
                 }
             }
 
-            self.player.push_events(event.iter_events());
+            match action {
+                LagAction::Push => self
+                    .player
+                    .push_track_events(event.track, event.iter_events()),
+                LagAction::SkipNotes => self
+                    .player
+                    .push_track_events(event.track, event.iter_events_without_notes()),
+            }
             self.index += 1;
         })
     }
@@ -130,7 +145,7 @@ impl InRamAudioPlayer {
         self.player.reset();
         for i in 0..(self.index) {
             self.player
-                .push_events(self.events[i].iter_control_events());
+                .push_track_events(self.events[i].track, self.events[i].iter_control_events());
         }
     }
 }
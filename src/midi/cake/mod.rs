@@ -1,30 +1,37 @@
-use std::{path::PathBuf, sync::Arc, thread};
-use time::Duration;
-
-use midi_toolkit::{
-    events::{Event, MIDIEventEnum},
-    io::MIDIFile as TKMIDIFile,
-    pipe,
-    sequence::{
-        event::{cancel_tempo_events, scale_event_time, Delta, EventBatch, Track},
-        unwrap_items, TimeCaster,
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, RwLock,
     },
+    thread,
 };
+use time::Duration;
+
+use crossbeam_channel::Receiver;
+use midi_toolkit::events::{Event, MIDIEventEnum};
 
 use crate::{
     audio_playback::WasabiAudioPlayer,
     gui::window::WasabiError,
     midi::{
-        audio::ram::InRamAudioPlayer,
+        audio::{live::LiveAudioPlayer, ram::InRamAudioPlayer},
         cake::tree_threader::{NoteEvent, ThreadedTreeSerializers},
-        open_file_and_signature,
-        shared::{audio::CompressedAudio, timer::TimeKeeper},
-        MIDIColor,
+        shared::{
+            audio::CompressedAudio,
+            density::NoteDensity,
+            lag::LagMonitor,
+            layers::{MIDILayers, TrackEventBatch},
+            meta::MIDIMetaEvents,
+            timer::{TimeKeeper, WaitResult},
+            track_channel::PERCUSSION_CHANNEL,
+        },
+        MIDIColor, MIDILayer, PERCUSSION_LANE_KEY,
     },
-    settings::MidiSettings,
+    settings::{MidiSettings, PercussionDisplay},
 };
 
-use self::blocks::CakeBlock;
+use self::{blocks::CakeBlock, intvec4::IntVector4};
 
 use super::{MIDIFileBase, MIDIFileStats, MIDIFileUniqueSignature};
 
@@ -34,43 +41,261 @@ mod tree_serializer;
 mod tree_threader;
 mod unended_note_batch;
 
-pub struct CakeMIDIFile {
-    blocks: Vec<CakeBlock>,
-    timer: TimeKeeper,
+const TICKS_PER_SECOND: u32 = 10000;
+
+/// The length of percussion hit markers, in seconds. The trees are built ahead of
+/// time, so the markers have a fixed length in time rather than on the screen.
+const HIT_MARKER_LENGTH: f64 = 0.05;
+
+/// The length of each time window when streaming, in seconds
+const STREAM_WINDOW_LENGTH: f64 = 10.0;
+/// How far ahead of the playhead the windows are built when streaming, in seconds
+const STREAM_LOOKAHEAD: f64 = 20.0;
+
+/// Window IDs are unique across loaded files, so the renderer can tell when a window changes
+static NEXT_WINDOW_ID: AtomicU64 = AtomicU64::new(0);
+
+struct ParseStats {
     length: f64,
     note_count: u64,
+}
+
+/// A time window of the MIDI, holding one cake block for each key.
+pub struct CakeWindow {
+    pub id: u64,
+    pub start_time: u32,
+    pub end_time: u32,
+    pub note_count: u64,
+    pub blocks: Vec<CakeBlock>,
+    /// The start times of the hidden percussion notes, which still count as passed
+    pub hidden_notes: Vec<i32>,
+}
+
+impl CakeWindow {
+    fn new(
+        start_time: i32,
+        end_time: i32,
+        note_count: u64,
+        trees: Vec<Vec<IntVector4>>,
+        hidden_notes: Vec<i32>,
+    ) -> Self {
+        let blocks = trees
+            .into_iter()
+            .map(|tree| CakeBlock {
+                start_time: start_time as u32,
+                end_time: end_time as u32,
+                tree,
+            })
+            .collect();
+
+        CakeWindow {
+            id: NEXT_WINDOW_ID.fetch_add(1, Ordering::Relaxed),
+            start_time: start_time as u32,
+            end_time: end_time as u32,
+            note_count,
+            blocks,
+            hidden_notes,
+        }
+    }
+
+    /// Adds the notes of the window to the note density, counting them per density
+    /// bucket from how many notes have passed in each block at the bucket edges.
+    fn add_to_density(&self, density: &mut NoteDensity, ticks_per_second: u32) {
+        let tps = ticks_per_second as f64;
+        let start = self.start_time as f64 / tps;
+        let end = self.end_time as f64 / tps;
+
+        for bucket in NoteDensity::buckets_between(start, end) {
+            let (bucket_start, bucket_end) = NoteDensity::bucket_time_range(bucket);
+            let bucket_start = ((bucket_start * tps) as i32).max(self.start_time as i32);
+            let bucket_end = ((bucket_end * tps) as i32).min(self.end_time as i32);
+            if bucket_start >= bucket_end {
+                continue;
+            }
+
+            let count = self
+                .blocks
+                .iter()
+                .map(|b| {
+                    b.get_notes_passed_at(bucket_end)
+                        .saturating_sub(b.get_notes_passed_at(bucket_start))
+                })
+                .sum();
+            density.add_notes(bucket, count);
+        }
+
+        for &time in self.hidden_notes.iter() {
+            density.add_note(time as f64 / tps);
+        }
+    }
+
+    /// The number of notes in the window that have started at the given time
+    fn notes_passed_at(&self, time: i32) -> u64 {
+        let passed = self
+            .blocks
+            .iter()
+            .map(|b| b.get_notes_passed_at(time) as u64)
+            .sum::<u64>();
+        passed + self.hidden_notes.partition_point(|&t| t <= time) as u64
+    }
+}
+
+pub struct CakeMIDIFile {
+    windows: VecDeque<CakeWindow>,
+    window_reciever: Option<Receiver<CakeWindow>>,
+    evicted_notes: u64,
+    density: NoteDensity,
+    meta_events: MIDIMetaEvents,
+    timer: TimeKeeper,
+    stats: Arc<RwLock<Option<ParseStats>>>,
     ticks_per_second: u32,
     signature: MIDIFileUniqueSignature,
 }
 
+/// Writes the note events of the MIDI into the tree serializers, applying the
+/// percussion display mode to the percussion channel.
+struct NoteEventWriter {
+    colors: Vec<MIDIColor>,
+    percussion: PercussionDisplay,
+    hit_marker_ticks: i32,
+    /// The end time, key and channel/track of the hit markers that haven't ended yet
+    hit_marker_ends: VecDeque<(i32, usize, i32)>,
+    /// The start times of the hidden percussion notes since the last window
+    hidden_notes: Vec<i32>,
+}
+
+impl NoteEventWriter {
+    fn new(colors: Vec<MIDIColor>, percussion: PercussionDisplay, ticks_per_second: u32) -> Self {
+        NoteEventWriter {
+            colors,
+            percussion,
+            hit_marker_ticks: (HIT_MARKER_LENGTH * ticks_per_second as f64) as i32,
+            hit_marker_ends: VecDeque::new(),
+            hidden_notes: Vec::new(),
+        }
+    }
+
+    fn color(&self, channel_track: i32) -> i32 {
+        self.colors[channel_track as usize].as_u32() as i32
+    }
+
+    /// Ends the hit markers that end at or before the given time
+    fn end_hit_markers(&mut self, trees: &mut ThreadedTreeSerializers, time: i32) {
+        while let Some(&(end, key, channel_track)) = self.hit_marker_ends.front() {
+            if end > time {
+                break;
+            }
+
+            trees.push_event(
+                key,
+                NoteEvent::Off {
+                    time: end,
+                    channel_track,
+                    color: self.color(channel_track),
+                },
+            );
+            self.hit_marker_ends.pop_front();
+        }
+    }
+
+    /// Pushes the note events of a batch to the tree serializers, and returns the
+    /// number of notes that were started.
+    fn push_batch(
+        &mut self,
+        trees: &mut ThreadedTreeSerializers,
+        batch: &TrackEventBatch,
+        int_time: i32,
+    ) -> u64 {
+        fn channel_track(channel: u8, track: u32) -> i32 {
+            (channel as i32) + (track as i32) * 16
+        }
+
+        self.end_hit_markers(trees, int_time);
+
+        let mut note_count = 0;
+
+        for event in batch.iter_events() {
+            let track = event.track;
+            match event.as_event() {
+                Event::NoteOn(e) => {
+                    let channel_track = channel_track(e.channel, track);
+                    let percussion = e.channel == PERCUSSION_CHANNEL;
+
+                    let key = match self.percussion {
+                        PercussionDisplay::Hidden if percussion => {
+                            self.hidden_notes.push(int_time);
+                            note_count += 1;
+                            continue;
+                        }
+                        PercussionDisplay::Lane if percussion => PERCUSSION_LANE_KEY,
+                        _ => e.key as usize,
+                    };
+
+                    trees.push_event(
+                        key,
+                        NoteEvent::On {
+                            time: int_time,
+                            channel_track,
+                            color: self.color(channel_track),
+                        },
+                    );
+                    note_count += 1;
+
+                    if percussion && self.percussion != PercussionDisplay::Normal {
+                        self.hit_marker_ends.push_back((
+                            int_time + self.hit_marker_ticks,
+                            key,
+                            channel_track,
+                        ));
+                    }
+                }
+                Event::NoteOff(e) => {
+                    // Percussion notes are ended by their hit markers instead
+                    if e.channel == PERCUSSION_CHANNEL
+                        && self.percussion != PercussionDisplay::Normal
+                    {
+                        continue;
+                    }
+
+                    let channel_track = channel_track(e.channel, track);
+
+                    trees.push_event(
+                        e.key as usize,
+                        NoteEvent::Off {
+                            time: int_time,
+                            channel_track,
+                            color: self.color(channel_track),
+                        },
+                    );
+                }
+                _ => {}
+            }
+        }
+
+        note_count
+    }
+}
+
 impl CakeMIDIFile {
-    pub fn load_from_file(
-        path: impl Into<PathBuf>,
+    pub fn load_from_layers(
+        layers: &[MIDILayer],
         player: Arc<WasabiAudioPlayer>,
         settings: &MidiSettings,
+        percussion: PercussionDisplay,
     ) -> Result<Self, WasabiError> {
-        let ticks_per_second = 10000;
+        let ticks_per_second = TICKS_PER_SECOND;
 
-        let (file, signature) = open_file_and_signature(path)?;
-        let midi = TKMIDIFile::open_from_stream(file, None).map_err(WasabiError::MidiLoadError)?;
+        let layers = MIDILayers::open(layers, settings)?;
+        let meta_events = MIDIMetaEvents::new();
+        let merged = meta_events.collect_from(layers.iter_merged_batches());
+        let mut writer = NoteEventWriter::new(layers.colors(), percussion, ticks_per_second);
 
-        let ppq = midi.ppq();
-        let merged = pipe!(
-            midi.iter_all_track_events_merged_batches()
-            |>TimeCaster::<f64>::cast_event_delta()
-            |>cancel_tempo_events(250000)
-            |>scale_event_time(1.0 / ppq as f64)
-            |>unwrap_items()
-        );
-
-        let colors = MIDIColor::new_vec_from_settings(midi.track_count(), settings)?;
-
-        type Ev = Delta<f64, Track<EventBatch<Event>>>;
-        let (key_snd, key_rcv) = crossbeam_channel::bounded::<Arc<Ev>>(1000);
-        let (audio_snd, audio_rcv) = crossbeam_channel::bounded::<Arc<Ev>>(1000);
+        let (key_snd, key_rcv) = crossbeam_channel::bounded::<Arc<TrackEventBatch>>(1000);
+        let (audio_snd, audio_rcv) = crossbeam_channel::bounded::<Arc<TrackEventBatch>>(1000);
 
+        let overlap = settings.note_overlap;
         let key_join_handle = thread::spawn(move || {
-            let mut trees = ThreadedTreeSerializers::new();
+            let mut trees = ThreadedTreeSerializers::new(overlap);
 
             let mut time = 0.0;
 
@@ -80,56 +305,18 @@ impl CakeMIDIFile {
                 time += batch.delta;
 
                 let int_time = (time * ticks_per_second as f64) as i32;
-
-                fn channel_track(channel: u8, track: u32) -> i32 {
-                    (channel as i32) + (track as i32) * 16
-                }
-
-                for event in batch.iter_events() {
-                    let track = event.track;
-                    match event.as_event() {
-                        Event::NoteOn(e) => {
-                            let channel_track = channel_track(e.channel, track);
-
-                            trees.push_event(
-                                e.key as usize,
-                                NoteEvent::On {
-                                    time: int_time,
-                                    channel_track,
-                                    color: colors[channel_track as usize].as_u32() as i32,
-                                },
-                            );
-                            note_count += 1;
-                        }
-                        Event::NoteOff(e) => {
-                            let channel_track = channel_track(e.channel, track);
-
-                            trees.push_event(
-                                e.key as usize,
-                                NoteEvent::Off {
-                                    time: int_time,
-                                    channel_track,
-                                    color: colors[channel_track as usize].as_u32() as i32,
-                                },
-                            );
-                        }
-                        _ => {}
-                    }
-                }
+                note_count += writer.push_batch(&mut trees, &batch, int_time);
             }
             let final_time = (time * ticks_per_second as f64) as i32;
             let serialized = trees.seal(final_time);
 
-            let keys: Vec<_> = serialized
-                .into_iter()
-                .map(|s| CakeBlock {
-                    start_time: 0,
-                    end_time: final_time as u32,
-                    tree: s,
-                })
-                .collect();
-
-            (keys, note_count)
+            CakeWindow::new(
+                0,
+                final_time,
+                note_count,
+                serialized,
+                std::mem::take(&mut writer.hidden_notes),
+            )
         });
 
         let audio_join_handle = thread::spawn(|| {
@@ -150,25 +337,216 @@ impl CakeMIDIFile {
         drop(key_snd);
         drop(audio_snd);
 
-        let (keys, note_count) = key_join_handle.join().unwrap();
+        let window = key_join_handle.join().unwrap();
         let audio = audio_join_handle.join().unwrap();
 
-        let mut timer = TimeKeeper::new(settings.start_delay);
+        let mut timer =
+            TimeKeeper::new(settings.start_delay).with_audio_clock(player.audio_clock());
 
-        InRamAudioPlayer::new(audio, timer.get_listener(), player).spawn_playback();
+        InRamAudioPlayer::new(
+            audio,
+            timer.get_listener(),
+            player,
+            LagMonitor::new(settings),
+        )
+        .spawn_playback();
+
+        let stats = ParseStats {
+            length,
+            note_count: window.note_count,
+        };
+
+        let mut density = NoteDensity::new();
+        window.add_to_density(&mut density, ticks_per_second);
 
         Ok(CakeMIDIFile {
-            blocks: keys,
+            windows: VecDeque::from([window]),
+            window_reciever: None,
+            evicted_notes: 0,
+            density,
+            meta_events,
             timer,
-            length,
-            note_count,
+            stats: Arc::new(RwLock::new(Some(stats))),
+            ticks_per_second,
+            signature: layers.signature(),
+        })
+    }
+
+    /// Loads the MIDI by building the cake trees in time windows while it plays,
+    /// similarly to the live parser. Windows are built ahead of the playhead and
+    /// evicted once they are passed, so the file doesn't need to fit in memory.
+    pub fn load_streaming_from_layers(
+        layers: &[MIDILayer],
+        player: Arc<WasabiAudioPlayer>,
+        settings: &MidiSettings,
+        percussion: PercussionDisplay,
+    ) -> Result<Self, WasabiError> {
+        let ticks_per_second = TICKS_PER_SECOND;
+        let window_length = (STREAM_WINDOW_LENGTH * ticks_per_second as f64) as i32;
+
+        let layers = MIDILayers::open(layers, settings)?;
+
+        let stats_outer = Arc::new(RwLock::new(None));
+        let stats = stats_outer.clone();
+
+        let calculate_stats = layers.stats_calculator();
+        thread::spawn(move || {
+            if let Some(stats) = calculate_stats() {
+                let mut parser_stats = stats_outer.write().unwrap();
+                *parser_stats = Some(ParseStats {
+                    length: stats.length,
+                    note_count: stats.note_count,
+                });
+            }
+        });
+
+        let meta_events = MIDIMetaEvents::new();
+        let merged = meta_events.collect_from(layers.iter_merged_batches());
+        let mut writer = NoteEventWriter::new(layers.colors(), percussion, ticks_per_second);
+
+        let mut timer =
+            TimeKeeper::new(settings.start_delay).with_audio_clock(player.audio_clock());
+
+        let (key_snd, key_rcv) = crossbeam_channel::bounded::<Arc<TrackEventBatch>>(1000);
+        let (audio_snd, audio_rcv) = crossbeam_channel::bounded::<Arc<TrackEventBatch>>(1000);
+        let (window_snd, window_rcv) = crossbeam_channel::unbounded::<CakeWindow>();
+        let (audio_block_snd, audio_block_rcv) = crossbeam_channel::unbounded();
+
+        let overlap = settings.note_overlap;
+        thread::spawn(move || {
+            let mut trees = ThreadedTreeSerializers::new(overlap);
+
+            let mut time = 0.0;
+            let mut window_start = 0;
+            let mut window_end = window_length;
+            let mut window_notes = 0;
+
+            for batch in key_rcv.into_iter() {
+                time += batch.delta;
+
+                let int_time = (time * ticks_per_second as f64) as i32;
+
+                if int_time >= window_end {
+                    // Seal the window at the last boundary before this batch. Gaps
+                    // longer than a window are merged into the window being sealed.
+                    let boundary =
+                        window_end + (int_time - window_end) / window_length * window_length;
+
+                    writer.end_hit_markers(&mut trees, boundary);
+                    let (serialized, next) = trees.split_window(boundary);
+                    trees = next;
+
+                    let window = CakeWindow::new(
+                        window_start,
+                        boundary,
+                        window_notes,
+                        serialized,
+                        std::mem::take(&mut writer.hidden_notes),
+                    );
+                    if window_snd.send(window).is_err() {
+                        return;
+                    }
+
+                    window_start = boundary;
+                    window_end = boundary + window_length;
+                    window_notes = 0;
+                }
+
+                window_notes += writer.push_batch(&mut trees, &batch, int_time);
+            }
+
+            let final_time = ((time * ticks_per_second as f64) as i32).max(window_start);
+            let serialized = trees.seal(final_time);
+            let window = CakeWindow::new(
+                window_start,
+                final_time,
+                window_notes,
+                serialized,
+                std::mem::take(&mut writer.hidden_notes),
+            );
+            window_snd.send(window).ok();
+        });
+
+        thread::spawn(move || {
+            for block in CompressedAudio::build_blocks(audio_rcv.into_iter()) {
+                if audio_block_snd.send(block).is_err() {
+                    break;
+                }
+            }
+        });
+
+        LiveAudioPlayer::new(
+            audio_block_rcv,
+            timer.get_listener(),
+            player,
+            LagMonitor::new(settings),
+        )
+        .spawn_playback();
+
+        let mut parser_timer = timer.get_listener();
+        thread::spawn(move || {
+            let mut time = 0.0;
+            for batch in merged {
+                time += batch.delta;
+
+                // Don't build the windows too far ahead of the playhead
+                let playback_time = Duration::seconds_f64((time - STREAM_LOOKAHEAD).max(0.0));
+                while parser_timer.get_time() < playback_time {
+                    if let WaitResult::Killed = parser_timer.wait_until(playback_time) {
+                        return;
+                    }
+                }
+
+                let batch = Arc::new(batch);
+                if key_snd.send(batch.clone()).is_err() {
+                    break;
+                }
+                if audio_snd.send(batch).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(CakeMIDIFile {
+            windows: VecDeque::new(),
+            window_reciever: Some(window_rcv),
+            evicted_notes: 0,
+            density: NoteDensity::new(),
+            meta_events,
+            timer,
+            stats,
             ticks_per_second,
-            signature,
+            signature: layers.signature(),
         })
     }
 
-    pub fn key_blocks(&self) -> &[CakeBlock] {
-        &self.blocks
+    /// Receives the windows that finished building, and evicts the ones that
+    /// are behind the playhead when streaming.
+    pub fn update_windows(&mut self) {
+        let Some(reciever) = self.window_reciever.as_ref() else {
+            return;
+        };
+
+        for window in reciever.try_iter() {
+            window.add_to_density(&mut self.density, self.ticks_per_second);
+            self.windows.push_back(window);
+        }
+
+        let time = self.timer.get_time().as_seconds_f64();
+        let time_int = (time * self.ticks_per_second as f64) as i64;
+        while let Some(window) = self.windows.front() {
+            if (window.end_time as i64) < time_int {
+                self.evicted_notes += window.note_count;
+                self.windows.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// The windows that are currently loaded, in chronological order
+    pub fn windows(&self) -> &VecDeque<CakeWindow> {
+        &self.windows
     }
 
     pub fn ticks_per_second(&self) -> u32 {
@@ -182,28 +560,32 @@ impl CakeMIDIFile {
     pub fn cake_signature(&self) -> CakeSignature {
         CakeSignature {
             file_signature: self.signature.clone(),
-            note_count: self.note_count,
-            buffer_sizes: self.blocks.iter().map(|b| b.tree.len()).collect(),
+            window_ids: self.windows.iter().map(|w| w.id).collect(),
         }
     }
 }
 
-/// A struct that uniquely identifies a cake midi file.
-/// This lets the renderer know if the file has changed.
+/// A struct that uniquely identifies a cake midi file and its loaded windows.
+/// This lets the renderer know if the file or its windows have changed.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct CakeSignature {
-    file_signature: MIDIFileUniqueSignature,
-    note_count: u64,
-    buffer_sizes: Vec<usize>,
+    pub file_signature: MIDIFileUniqueSignature,
+    pub window_ids: Vec<u64>,
 }
 
 impl MIDIFileBase for CakeMIDIFile {
     fn midi_length(&self) -> Option<f64> {
-        Some(self.length)
+        let stats = self.stats.read().unwrap();
+        stats.as_ref().map(|stats| stats.length)
     }
 
     fn parsed_up_to(&self) -> Option<f64> {
-        None
+        if self.window_reciever.is_none() {
+            return None;
+        }
+
+        let end = self.windows.back().map(|w| w.end_time).unwrap_or(0);
+        Some(end as f64 / self.ticks_per_second as f64)
     }
 
     fn timer(&self) -> &TimeKeeper {
@@ -215,7 +597,8 @@ impl MIDIFileBase for CakeMIDIFile {
     }
 
     fn allows_seeking_backward(&self) -> bool {
-        true
+        // Passed windows are evicted when streaming
+        self.window_reciever.is_none()
     }
 
     fn stats(&self) -> MIDIFileStats {
@@ -223,18 +606,28 @@ impl MIDIFileBase for CakeMIDIFile {
         let time_int = (time * self.ticks_per_second as f64) as i32;
 
         let passed_notes = self
-            .key_blocks()
+            .windows
             .iter()
-            .map(|b| b.get_notes_passed_at(time_int) as u64)
-            .sum();
+            .map(|w| w.notes_passed_at(time_int))
+            .sum::<u64>();
+
+        let stats = self.stats.read().unwrap();
 
         MIDIFileStats {
-            total_notes: Some(self.note_count),
-            passed_notes: Some(passed_notes),
+            total_notes: stats.as_ref().map(|stats| stats.note_count),
+            passed_notes: Some(self.evicted_notes + passed_notes),
         }
     }
 
     fn signature(&self) -> &MIDIFileUniqueSignature {
         &self.signature
     }
+
+    fn note_density(&self) -> Option<&NoteDensity> {
+        Some(&self.density)
+    }
+
+    fn meta_events(&self) -> &MIDIMetaEvents {
+        &self.meta_events
+    }
 }
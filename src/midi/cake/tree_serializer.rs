@@ -1,5 +1,7 @@
 use std::collections::VecDeque;
 
+use crate::settings::NoteOverlap;
+
 use super::{intvec4::IntVector4, unended_note_batch::UnendedNotes};
 
 enum TreeFrame {
@@ -34,6 +36,8 @@ pub struct TreeSerializer {
 
     added_notes: u32,
     last_tree_time: i32,
+
+    overlap: NoteOverlap,
 }
 
 impl std::fmt::Debug for TreeSerializer {
@@ -43,17 +47,19 @@ impl std::fmt::Debug for TreeSerializer {
 }
 
 impl TreeSerializer {
-    pub fn new() -> TreeSerializer {
+    pub fn new(overlap: NoteOverlap) -> TreeSerializer {
         let written_values = vec![IntVector4::new_empty()];
 
         TreeSerializer {
-            note_stack: UnendedNotes::new(),
+            note_stack: UnendedNotes::new(overlap),
             tree_frames: VecDeque::new(),
 
             written_values,
 
             added_notes: 0,
             last_tree_time: 0,
+
+            overlap,
         }
     }
 
@@ -129,6 +135,23 @@ impl TreeSerializer {
         );
     }
 
+    /// Pushes a note that was carried over from a previous time window. The tree is
+    /// updated to the window start time, but the written note keeps its original
+    /// start so it continues seamlessly from the previous window.
+    fn start_carried_note(&mut self, time: i32, marker: NoteMarker) {
+        if time > self.last_tree_time {
+            self.process_change(time);
+        }
+
+        self.note_stack.push_note(
+            marker.track_channel,
+            NoteMarker {
+                written_pos: None,
+                ..marker
+            },
+        );
+    }
+
     /// Processes a note end. If the time is greater than the last tree time, the tree is
     /// updated to the new time. Then, the note is popped from the note stack, and the
     /// end for the note is also written.
@@ -163,6 +186,31 @@ impl TreeSerializer {
     /// and returns the array.
     pub fn complete_and_seal(mut self, time: i32) -> Vec<IntVector4> {
         self.end_all_notes(time);
+        self.seal_frames()
+    }
+
+    /// Seals the tree at the end of a time window and returns it alongside a new serializer
+    /// for the next window. Notes that are still held are written as continuing past the
+    /// window, and are carried over into the new serializer.
+    pub fn split_window(mut self, time: i32) -> (Vec<IntVector4>, TreeSerializer) {
+        let mut next = TreeSerializer::new(self.overlap);
+
+        if self.note_stack.len() > 0 {
+            self.process_change(time);
+            for marker in self.note_stack.drain_all() {
+                if let Some(index) = marker.written_pos {
+                    self.written_values[index as usize].set_note_end(i32::MAX);
+                }
+                next.start_carried_note(time, marker);
+            }
+        }
+
+        (self.seal_frames(), next)
+    }
+
+    /// Finishes all stack frames, inserts the address of the last item into the start of the array,
+    /// and returns the array.
+    fn seal_frames(mut self) -> Vec<IntVector4> {
         self.end_all_frames();
 
         if self.written_values.len() == 1 {
@@ -2,6 +2,8 @@ use std::sync::{Arc, Mutex};
 
 use rayon::iter::{IndexedParallelIterator, IntoParallelRefMutIterator, ParallelIterator};
 
+use crate::{midi::NOTE_COLUMNS, settings::NoteOverlap};
+
 use super::{intvec4::IntVector4, tree_serializer::TreeSerializer};
 
 pub struct MidiData {
@@ -34,11 +36,17 @@ pub struct ThreadedTreeSerializers {
 
 impl ThreadedTreeSerializers {
     fn make_vecs() -> Vec<Vec<NoteEvent>> {
-        (0..256).map(|_| Vec::new()).collect()
+        (0..NOTE_COLUMNS).map(|_| Vec::new()).collect()
+    }
+
+    pub fn new(overlap: NoteOverlap) -> ThreadedTreeSerializers {
+        let trees = (0..NOTE_COLUMNS)
+            .map(|_| TreeSerializer::new(overlap))
+            .collect::<Vec<_>>();
+        Self::from_trees(trees)
     }
 
-    pub fn new() -> ThreadedTreeSerializers {
-        let trees = (0..256).map(|_| TreeSerializer::new()).collect::<Vec<_>>();
+    fn from_trees(trees: Vec<TreeSerializer>) -> ThreadedTreeSerializers {
         let trees = Arc::new(Mutex::new(trees));
 
         let (snd_in, rcv_in) = crossbeam_channel::unbounded::<Vec<Vec<NoteEvent>>>();
@@ -105,7 +113,7 @@ impl ThreadedTreeSerializers {
         }
     }
 
-    pub fn seal(self, time: i32) -> Vec<Vec<IntVector4>> {
+    fn finish(self) -> Vec<TreeSerializer> {
         self.snd.send(self.current_vec).unwrap();
         drop(self.snd);
 
@@ -114,7 +122,27 @@ impl ThreadedTreeSerializers {
 
         self.join.join().unwrap();
 
-        let trees = Arc::try_unwrap(self.trees).unwrap().into_inner().unwrap();
+        Arc::try_unwrap(self.trees).unwrap().into_inner().unwrap()
+    }
+
+    /// Seals the trees of the current time window, and returns them alongside the
+    /// serializers for the next window. Held notes are carried over into the next window.
+    pub fn split_window(self, time: i32) -> (Vec<Vec<IntVector4>>, ThreadedTreeSerializers) {
+        let trees = self.finish();
+
+        let mut serialized = Vec::with_capacity(trees.len());
+        let mut next_trees = Vec::with_capacity(trees.len());
+        for tree in trees.into_iter() {
+            let (sealed, next) = tree.split_window(time);
+            serialized.push(sealed);
+            next_trees.push(next);
+        }
+
+        (serialized, Self::from_trees(next_trees))
+    }
+
+    pub fn seal(self, time: i32) -> Vec<Vec<IntVector4>> {
+        let trees = self.finish();
 
         let mut serialized = Vec::new();
         for tree in trees.into_iter() {
@@ -1,5 +1,7 @@
 use std::collections::{BTreeMap, VecDeque};
 
+use crate::settings::NoteOverlap;
+
 pub struct RemovedValue<T> {
     pub value: T,
     pub is_last: bool,
@@ -9,14 +11,16 @@ pub struct UnendedNotes<K: Ord, T> {
     id_counter: u32,
     notes: BTreeMap<u32, T>,
     ids: BTreeMap<K, VecDeque<u32>>,
+    overlap: NoteOverlap,
 }
 
 impl<K: Ord, T> UnendedNotes<K, T> {
-    pub fn new() -> Self {
+    pub fn new(overlap: NoteOverlap) -> Self {
         UnendedNotes {
             id_counter: 0,
             notes: BTreeMap::new(),
             ids: BTreeMap::new(),
+            overlap,
         }
     }
 
@@ -31,7 +35,10 @@ impl<K: Ord, T> UnendedNotes<K, T> {
 
     pub fn get_note_for(&mut self, key: K) -> Option<RemovedValue<T>> {
         let ids = self.ids.get_mut(&key)?;
-        let id = ids.pop_front()?;
+        let id = match self.overlap {
+            NoteOverlap::Fifo => ids.pop_front()?,
+            NoteOverlap::Lifo => ids.pop_back()?,
+        };
         let last_key = *self.notes.last_entry()?.key();
 
         let note = self.notes.remove(&id)?;
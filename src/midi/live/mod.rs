@@ -1,11 +1,8 @@
 use std::{
-    path::PathBuf,
     sync::{Arc, RwLock},
     thread,
 };
 
-use midi_toolkit::{io::MIDIFile as TKMIDIFile, sequence::event::get_channels_array_statistics};
-
 use crate::{audio_playback::WasabiAudioPlayer, gui::window::WasabiError, settings::MidiSettings};
 
 use self::{
@@ -14,8 +11,9 @@ use self::{
 };
 
 use super::{
-    open_file_and_signature, shared::timer::TimeKeeper, MIDIColor, MIDIFile, MIDIFileBase,
-    MIDIFileStats, MIDIFileUniqueSignature, MIDIViewRange,
+    shared::{lag::LagMonitor, layers::MIDILayers, meta::MIDIMetaEvents, timer::TimeKeeper},
+    MIDIFile, MIDIFileBase, MIDIFileStats, MIDIFileUniqueSignature, MIDILayer, MIDIViewRange,
+    NoteDensity,
 };
 
 pub mod block;
@@ -32,47 +30,51 @@ pub struct LiveLoadMIDIFile {
     view_data: LiveNoteViewData,
     timer: TimeKeeper,
     stats: Arc<RwLock<Option<ParseStats>>>,
+    meta_events: MIDIMetaEvents,
     signature: MIDIFileUniqueSignature,
 }
 
 impl LiveLoadMIDIFile {
-    pub fn load_from_file(
-        path: impl Into<PathBuf>,
+    pub fn load_from_layers(
+        layers: &[MIDILayer],
         player: Arc<WasabiAudioPlayer>,
         settings: &MidiSettings,
     ) -> Result<Self, WasabiError> {
-        let (file, signature) = open_file_and_signature(path)?;
-
-        let midi = TKMIDIFile::open_from_stream(file, None).map_err(WasabiError::MidiLoadError)?;
+        let layers = MIDILayers::open(layers, settings)?;
 
         let stats_outer = Arc::new(RwLock::new(None));
         let stats = stats_outer.clone();
 
-        let ppq = midi.ppq();
-        let tracks = midi.iter_all_tracks().collect();
+        let calculate_stats = layers.stats_calculator();
         thread::spawn(move || {
-            let stats = get_channels_array_statistics(tracks);
-            if let Ok(stats) = stats {
+            if let Some(stats) = calculate_stats() {
                 let mut parser_stats = stats_outer.write().unwrap();
                 *parser_stats = Some(ParseStats {
-                    length: stats.calculate_total_duration(ppq).as_secs_f64(),
-                    note_count: stats.note_count(),
+                    length: stats.length,
+                    note_count: stats.note_count,
                 });
             }
         });
 
-        let mut timer = TimeKeeper::new(settings.start_delay);
+        let mut timer =
+            TimeKeeper::new(settings.start_delay).with_audio_clock(player.audio_clock());
 
-        let colors = MIDIColor::new_vec_from_settings(midi.track_count(), settings)?;
-
-        let parser = LiveMidiParser::init(&midi, player, &mut timer);
-        let file = LiveNoteViewData::new(parser, colors);
+        let meta_events = MIDIMetaEvents::new();
+        let parser = LiveMidiParser::init(
+            meta_events.collect_from(layers.iter_merged_batches()),
+            player,
+            &mut timer,
+            settings.note_overlap,
+            LagMonitor::new(settings),
+        );
+        let file = LiveNoteViewData::new(parser, layers.colors());
 
         Ok(LiveLoadMIDIFile {
             view_data: file,
             timer,
             stats,
-            signature,
+            meta_events,
+            signature: layers.signature(),
         })
     }
 }
@@ -111,6 +113,14 @@ impl MIDIFileBase for LiveLoadMIDIFile {
     fn signature(&self) -> &MIDIFileUniqueSignature {
         &self.signature
     }
+
+    fn note_density(&self) -> Option<&NoteDensity> {
+        None
+    }
+
+    fn meta_events(&self) -> &MIDIMetaEvents {
+        &self.meta_events
+    }
 }
 
 impl MIDIFile for LiveLoadMIDIFile {
@@ -6,22 +6,17 @@ use time::Duration;
 
 use atomic_float::AtomicF64;
 use crossbeam_channel::Receiver;
-use midi_toolkit::{
-    events::Event,
-    io::{DiskReader, MIDIFile as TKMIDIFile},
-    pipe,
-    sequence::{
-        event::{cancel_tempo_events, scale_event_time, Delta, EventBatch, Track},
-        unwrap_items, TimeCaster,
-    },
-};
 
 use crate::{
     audio_playback::WasabiAudioPlayer,
     midi::{
         audio::live::LiveAudioPlayer,
-        shared::timer::{TimeKeeper, WaitResult},
+        shared::{
+            lag::LagMonitor,
+            timer::{TimeKeeper, WaitResult},
+        },
     },
+    settings::NoteOverlap,
 };
 
 use self::notes::LiveNoteBlockWithKey;
@@ -29,7 +24,7 @@ use self::notes::LiveNoteBlockWithKey;
 mod audio;
 mod notes;
 
-pub type TrackEventBatch = Delta<f64, Track<EventBatch<Event>>>;
+pub use crate::midi::shared::layers::TrackEventBatch;
 
 pub struct ThreadManager {
     parse_time: Arc<AtomicF64>,
@@ -45,26 +40,19 @@ pub struct LiveMidiParser {
 
 impl LiveMidiParser {
     pub fn init(
-        midi: &TKMIDIFile<DiskReader>,
+        merged: impl Iterator<Item = TrackEventBatch> + Send + 'static,
         player: Arc<WasabiAudioPlayer>,
         timer: &mut TimeKeeper,
+        overlap: NoteOverlap,
+        lag: LagMonitor,
     ) -> Self {
-        let ppq = midi.ppq();
-        let merged = pipe!(
-            midi.iter_all_track_events_merged_batches()
-            |>TimeCaster::<f64>::cast_event_delta()
-            |>cancel_tempo_events(250000)
-            |>scale_event_time(1.0 / ppq as f64)
-            |>unwrap_items()
-        );
-
         let (note_snd, note_rcv) = crossbeam_channel::bounded::<Arc<TrackEventBatch>>(1000);
         let (audio_snd, audio_rcv) = crossbeam_channel::bounded::<Arc<TrackEventBatch>>(1000);
 
-        let notes = notes::init_note_manager(note_rcv);
+        let notes = notes::init_note_manager(note_rcv, overlap);
         let audio = audio::init_audio_manager(audio_rcv);
 
-        LiveAudioPlayer::new(audio.reciever, timer.get_listener(), player).spawn_playback();
+        LiveAudioPlayer::new(audio.reciever, timer.get_listener(), player, lag).spawn_playback();
 
         let mut parser_timer = timer.get_listener();
 
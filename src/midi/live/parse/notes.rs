@@ -7,9 +7,12 @@ use atomic_float::AtomicF64;
 use crossbeam_channel::{Receiver, Sender};
 use midi_toolkit::events::{Event, MIDIEventEnum};
 
-use crate::midi::{
-    live::block::{LiveNoteEnderHandle, LiveRefNoteBlock},
-    shared::track_channel::TrackAndChannel,
+use crate::{
+    midi::{
+        live::block::{LiveNoteEnderHandle, LiveRefNoteBlock},
+        shared::track_channel::TrackAndChannel,
+    },
+    settings::NoteOverlap,
 };
 
 use super::{ThreadManager, TrackEventBatch};
@@ -21,10 +24,11 @@ pub struct LiveNoteBlockWithKey {
 
 struct TrackUnendedNotes {
     queues: Box<[VecDeque<LiveNoteEnderHandle>]>,
+    overlap: NoteOverlap,
 }
 
 impl TrackUnendedNotes {
-    fn new() -> Self {
+    fn new(overlap: NoteOverlap) -> Self {
         let mut queues = Vec::with_capacity(16 * 256);
         for _ in 0..16 * 256 {
             queues.push(VecDeque::new());
@@ -32,6 +36,7 @@ impl TrackUnendedNotes {
 
         TrackUnendedNotes {
             queues: queues.into_boxed_slice(),
+            overlap,
         }
     }
 
@@ -49,7 +54,11 @@ impl TrackUnendedNotes {
 
     fn end_note(&mut self, key: u8, channel: u8, time: f64) {
         let index = self.get_index(key, channel);
-        if let Some(mut note) = self.queues[index].pop_front() {
+        let note = match self.overlap {
+            NoteOverlap::Fifo => self.queues[index].pop_front(),
+            NoteOverlap::Lifo => self.queues[index].pop_back(),
+        };
+        if let Some(mut note) = note {
             note.end(time);
         }
     }
@@ -62,12 +71,14 @@ impl TrackUnendedNotes {
 
 struct UnendedNotesHandler {
     unended_notes: Vec<Option<TrackUnendedNotes>>,
+    overlap: NoteOverlap,
 }
 
 impl UnendedNotesHandler {
-    pub fn new() -> Self {
+    pub fn new(overlap: NoteOverlap) -> Self {
         UnendedNotesHandler {
             unended_notes: Vec::new(),
+            overlap,
         }
     }
 
@@ -76,7 +87,8 @@ impl UnendedNotesHandler {
             self.unended_notes.push(None);
         }
 
-        self.unended_notes[track as usize].get_or_insert_with(TrackUnendedNotes::new)
+        let overlap = self.overlap;
+        self.unended_notes[track as usize].get_or_insert_with(|| TrackUnendedNotes::new(overlap))
     }
 
     fn end_all_notes(&mut self, time: f64) {
@@ -93,13 +105,13 @@ struct ParserState {
 }
 
 impl ParserState {
-    fn new(sender: Sender<LiveNoteBlockWithKey>) -> Self {
+    fn new(sender: Sender<LiveNoteBlockWithKey>, overlap: NoteOverlap) -> Self {
         let mut keys = Vec::with_capacity(256);
         for _ in 0..256 {
             keys.push(Vec::new());
         }
         ParserState {
-            unended_notes: UnendedNotesHandler::new(),
+            unended_notes: UnendedNotesHandler::new(overlap),
             keys: keys.into_boxed_slice(),
             sender,
         }
@@ -145,13 +157,16 @@ pub struct NoteParserResult {
     pub manager: ThreadManager,
 }
 
-pub fn init_note_manager(blocks: Receiver<Arc<TrackEventBatch>>) -> NoteParserResult {
+pub fn init_note_manager(
+    blocks: Receiver<Arc<TrackEventBatch>>,
+    overlap: NoteOverlap,
+) -> NoteParserResult {
     let (sender, reciever) = crossbeam_channel::unbounded();
     let parse_time_outer = Arc::new(AtomicF64::default());
 
     let parse_time = parse_time_outer.clone();
 
-    let mut state = ParserState::new(sender);
+    let mut state = ParserState::new(sender, overlap);
     let join_handle = std::thread::spawn(move || {
         let mut time: f64 = 0.0;
         for block in blocks.into_iter() {
@@ -15,17 +15,30 @@ use image::{DynamicImage, GenericImageView, ImageReader};
 use palette::{convert::FromColorUnclamped, Hsv, Srgb};
 use rand::seq::IteratorRandom;
 use rand::Rng;
+use serde_derive::{Deserialize, Serialize};
 
-pub use cake::{blocks::CakeBlock, intvec4::IntVector4, CakeMIDIFile, CakeSignature};
+pub use cake::{blocks::CakeBlock, intvec4::IntVector4, CakeMIDIFile, CakeSignature, CakeWindow};
 pub use live::LiveLoadMIDIFile;
 pub use ram::InRamMIDIFile;
+pub use shared::{
+    density::NoteDensity,
+    layers::MIDILayer,
+    meta::{MIDIMetaEvents, MetaEvent, MetaEventKind},
+    timer::{TimeKeeper, WaitResult},
+};
 
 use crate::{
     gui::window::WasabiError,
     settings::{Colors, MidiSettings},
 };
 
-use self::shared::timer::TimeKeeper;
+/// The note column that percussion notes are moved to when they are drawn in a
+/// dedicated lane. It comes after the last key, so no real key is moved into the
+/// lane, and the keyboard view places it next to the keys.
+pub const PERCUSSION_LANE_KEY: usize = 256;
+
+/// The note columns of the cake trees, one for each key and one for the percussion lane
+pub const NOTE_COLUMNS: usize = PERCUSSION_LANE_KEY + 1;
 
 #[derive(Debug, Clone, Copy, Default)]
 pub struct MIDIFileStats {
@@ -50,13 +63,19 @@ impl MIDIViewRange {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct MIDIFileUniqueSignature {
     pub filepath: PathBuf,
     pub length_in_bytes: u64,
     pub last_modified: u128,
 }
 
+impl MIDIFileUniqueSignature {
+    pub fn from_path(path: impl Into<PathBuf>) -> Result<Self, WasabiError> {
+        open_file_and_signature(path).map(|(_, signature)| signature)
+    }
+}
+
 fn open_file_and_signature(
     path: impl Into<PathBuf>,
 ) -> Result<(File, MIDIFileUniqueSignature), WasabiError> {
@@ -146,11 +165,12 @@ impl MIDIColor {
         }
     }
 
-    pub fn new_vec_from_settings(
+    pub fn new_vec_with_colors(
         tracks: usize,
+        colors: Colors,
         settings: &MidiSettings,
     ) -> Result<Vec<Self>, WasabiError> {
-        match settings.colors {
+        match colors {
             Colors::Rainbow => Ok(MIDIColor::new_vec(tracks)),
             Colors::Random => Ok(MIDIColor::new_random_vec(tracks)),
             Colors::Palette => {
@@ -222,6 +242,12 @@ pub trait MIDIFileBase {
     fn allows_seeking_backward(&self) -> bool;
 
     fn signature(&self) -> &MIDIFileUniqueSignature;
+
+    /// The notes per second over the length of the MIDI, if it is known
+    fn note_density(&self) -> Option<&NoteDensity>;
+
+    /// The text, marker and lyric meta events that have been parsed so far
+    fn meta_events(&self) -> &MIDIMetaEvents;
 }
 
 /// This trait contains a function to retrieve the column view of the midi
@@ -254,6 +280,7 @@ pub struct DisplacedMIDINote {
     pub start: f32,
     pub len: f32,
     pub color: MIDIColor,
+    pub percussion: bool,
 }
 
 #[enum_dispatch(MIDIFileBase)]
@@ -1,8 +1,8 @@
 use self::view::{InRamCurrentNoteViews, InRamNoteViewData};
 
 use super::{
-    shared::timer::TimeKeeper, MIDIFile, MIDIFileBase, MIDIFileStats, MIDIFileUniqueSignature,
-    MIDIViewRange,
+    shared::{density::NoteDensity, meta::MIDIMetaEvents, timer::TimeKeeper},
+    MIDIFile, MIDIFileBase, MIDIFileStats, MIDIFileUniqueSignature, MIDIViewRange,
 };
 
 pub mod block;
@@ -15,6 +15,8 @@ pub struct InRamMIDIFile {
     timer: TimeKeeper,
     length: f64,
     note_count: u64,
+    density: NoteDensity,
+    meta_events: MIDIMetaEvents,
     signature: MIDIFileUniqueSignature,
 }
 
@@ -51,6 +53,14 @@ impl MIDIFileBase for InRamMIDIFile {
     fn signature(&self) -> &MIDIFileUniqueSignature {
         &self.signature
     }
+
+    fn note_density(&self) -> Option<&NoteDensity> {
+        Some(&self.density)
+    }
+
+    fn meta_events(&self) -> &MIDIMetaEvents {
+        &self.meta_events
+    }
 }
 
 impl MIDIFile for InRamMIDIFile {
@@ -1,14 +1,6 @@
-use std::{collections::VecDeque, path::PathBuf, sync::Arc, thread};
-
-use midi_toolkit::{
-    events::{Event, MIDIEventEnum},
-    io::MIDIFile as TKMIDIFile,
-    pipe,
-    sequence::{
-        event::{cancel_tempo_events, scale_event_time, Delta, EventBatch, Track},
-        unwrap_items, TimeCaster,
-    },
-};
+use std::{collections::VecDeque, sync::Arc, thread};
+
+use midi_toolkit::events::{Event, MIDIEventEnum};
 use rustc_hash::FxHashMap;
 
 use crate::{
@@ -16,12 +8,19 @@ use crate::{
     gui::window::WasabiError,
     midi::{
         audio::ram::InRamAudioPlayer,
-        open_file_and_signature,
         ram::{column::InRamNoteColumn, view::InRamNoteViewData},
-        shared::{audio::CompressedAudio, timer::TimeKeeper, track_channel::TrackAndChannel},
-        MIDIColor,
+        shared::{
+            audio::CompressedAudio,
+            density::NoteDensity,
+            lag::LagMonitor,
+            layers::{MIDILayers, TrackEventBatch},
+            meta::MIDIMetaEvents,
+            timer::TimeKeeper,
+            track_channel::TrackAndChannel,
+        },
+        MIDILayer,
     },
-    settings::MidiSettings,
+    settings::{MidiSettings, NoteOverlap},
 };
 
 use super::{block::InRamNoteBlock, InRamMIDIFile};
@@ -35,14 +34,16 @@ struct Key {
     column: Vec<InRamNoteBlock>,
     block_builder: Vec<TrackAndChannel>,
     unended_notes: FxHashMap<TrackAndChannel, VecDeque<UnendedNote>>,
+    overlap: NoteOverlap,
 }
 
 impl Key {
-    fn new() -> Self {
+    fn new(overlap: NoteOverlap) -> Self {
         Key {
             column: Vec::new(),
             block_builder: Vec::new(),
             unended_notes: FxHashMap::default(),
+            overlap,
         }
     }
 
@@ -58,10 +59,14 @@ impl Key {
     }
 
     pub fn end_note(&mut self, track_chan: TrackAndChannel, time: f64) {
-        let note = self
-            .unended_notes
-            .get_mut(&track_chan)
-            .and_then(|unended_queue| unended_queue.pop_front());
+        let overlap = self.overlap;
+        let note =
+            self.unended_notes
+                .get_mut(&track_chan)
+                .and_then(|unended_queue| match overlap {
+                    NoteOverlap::Fifo => unended_queue.pop_front(),
+                    NoteOverlap::Lifo => unended_queue.pop_back(),
+                });
 
         if let Some(note) = note {
             if note.column_index == self.column.len() {
@@ -95,33 +100,26 @@ impl Key {
 }
 
 impl InRamMIDIFile {
-    pub fn load_from_file(
-        path: impl Into<PathBuf>,
+    pub fn load_from_layers(
+        layers: &[MIDILayer],
         player: Arc<WasabiAudioPlayer>,
         settings: &MidiSettings,
     ) -> Result<Self, WasabiError> {
-        let (file, signature) = open_file_and_signature(path)?;
-        let midi = TKMIDIFile::open_from_stream(file, None).map_err(WasabiError::MidiLoadError)?;
+        let layers = MIDILayers::open(layers, settings)?;
+        let meta_events = MIDIMetaEvents::new();
+        let merged = meta_events.collect_from(layers.iter_merged_batches());
 
-        let ppq = midi.ppq();
-        let merged = pipe!(
-            midi.iter_all_track_events_merged_batches()
-            |>TimeCaster::<f64>::cast_event_delta()
-            |>cancel_tempo_events(250000)
-            |>scale_event_time(1.0 / ppq as f64)
-            |>unwrap_items()
-        );
+        let (key_snd, key_rcv) = crossbeam_channel::bounded::<Arc<TrackEventBatch>>(1000);
+        let (audio_snd, audio_rcv) = crossbeam_channel::bounded::<Arc<TrackEventBatch>>(1000);
 
-        type Ev = Delta<f64, Track<EventBatch<Event>>>;
-        let (key_snd, key_rcv) = crossbeam_channel::bounded::<Arc<Ev>>(1000);
-        let (audio_snd, audio_rcv) = crossbeam_channel::bounded::<Arc<Ev>>(1000);
-
-        let key_join_handle = thread::spawn(|| {
-            let mut keys: Vec<Key> = (0..256).map(|_| Key::new()).collect();
+        let overlap = settings.note_overlap;
+        let key_join_handle = thread::spawn(move || {
+            let mut keys: Vec<Key> = (0..256).map(|_| Key::new(overlap)).collect();
 
             let mut time = 0.0;
 
             let mut notes = 0;
+            let mut density = NoteDensity::new();
 
             fn flush_keys(time: f64, keys: &mut [Key]) {
                 for key in keys.iter_mut() {
@@ -142,6 +140,7 @@ impl InRamMIDIFile {
                             let track_chan = TrackAndChannel::new(track, e.channel);
                             keys[e.key as usize].add_note(track_chan);
                             notes += 1;
+                            density.add_note(time);
                         }
                         Event::NoteOff(e) => {
                             let track_chan = TrackAndChannel::new(track, e.channel);
@@ -158,7 +157,7 @@ impl InRamMIDIFile {
                 key.end_all(time);
             }
 
-            (keys, notes)
+            (keys, notes, density)
         });
 
         let audio_join_handle = thread::spawn(|| {
@@ -179,26 +178,33 @@ impl InRamMIDIFile {
         drop(key_snd);
         drop(audio_snd);
 
-        let (keys, note_count) = key_join_handle.join().unwrap();
+        let (keys, note_count, density) = key_join_handle.join().unwrap();
         let audio = audio_join_handle.join().unwrap();
 
-        let mut timer = TimeKeeper::new(settings.start_delay);
+        let mut timer =
+            TimeKeeper::new(settings.start_delay).with_audio_clock(player.audio_clock());
 
-        InRamAudioPlayer::new(audio, timer.get_listener(), player).spawn_playback();
+        InRamAudioPlayer::new(
+            audio,
+            timer.get_listener(),
+            player,
+            LagMonitor::new(settings),
+        )
+        .spawn_playback();
 
         let columns = keys
             .into_iter()
             .map(|key| InRamNoteColumn::new(key.column))
             .collect();
 
-        let colors = MIDIColor::new_vec_from_settings(midi.track_count(), settings)?;
-
         Ok(InRamMIDIFile {
-            view_data: InRamNoteViewData::new(columns, colors),
+            view_data: InRamNoteViewData::new(columns, layers.colors()),
             timer,
             length,
             note_count,
-            signature,
+            density,
+            meta_events,
+            signature: layers.signature(),
         })
     }
 }
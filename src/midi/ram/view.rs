@@ -189,6 +189,7 @@ impl<'a> MIDINoteColumnView for InRamNoteColumnView<'a> {
                             start,
                             len: note.len,
                             color: colors[note.track_chan.as_usize()],
+                            percussion: note.track_chan.is_percussion(),
                         };
                     }
                 }
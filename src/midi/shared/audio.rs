@@ -8,6 +8,7 @@ use midi_toolkit::{
 
 pub struct CompressedAudio {
     pub time: f64,
+    pub track: u32,
     data: Vec<u8>,
     control_only_data: Option<Vec<u8>>,
 }
@@ -104,6 +105,7 @@ impl CompressedAudio {
                         data: new_vec,
                         control_only_data: new_control_vec,
                         time,
+                        track: block.track,
                     };
                 }
             },
@@ -114,6 +116,12 @@ impl CompressedAudio {
         CompressedAudio::iter_events_from_vec(self.data.iter().cloned())
     }
 
+    /// The events without the note ons, for when the audio is catching up
+    pub fn iter_events_without_notes(&self) -> impl '_ + Iterator<Item = u32> {
+        self.iter_events()
+            .filter(|ev| ev & 0xF0 != EV_ON as u32 || (ev >> 16) & 0x7F == 0)
+    }
+
     pub fn iter_control_events(&self) -> impl '_ + Iterator<Item = u32> {
         CompressedAudio::iter_events_from_vec(self.control_only_data.iter().flatten().cloned())
     }
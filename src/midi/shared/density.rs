@@ -0,0 +1,62 @@
+use std::ops::RangeInclusive;
+
+/// The length of each density bucket, in seconds
+const BUCKET_LENGTH: f64 = 0.25;
+
+/// The number of notes started over time in a MIDI, counted in fixed length buckets
+#[derive(Debug, Clone, Default)]
+pub struct NoteDensity {
+    buckets: Vec<u32>,
+}
+
+impl NoteDensity {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn bucket_at(time: f64) -> usize {
+        (time.max(0.0) / BUCKET_LENGTH) as usize
+    }
+
+    /// The range of buckets that overlap the given time range
+    pub fn buckets_between(start: f64, end: f64) -> RangeInclusive<usize> {
+        Self::bucket_at(start)..=Self::bucket_at(end)
+    }
+
+    /// The start and end time of a bucket, in seconds
+    pub fn bucket_time_range(bucket: usize) -> (f64, f64) {
+        let start = bucket as f64 * BUCKET_LENGTH;
+        (start, start + BUCKET_LENGTH)
+    }
+
+    pub fn add_note(&mut self, time: f64) {
+        self.add_notes(Self::bucket_at(time), 1);
+    }
+
+    pub fn add_notes(&mut self, bucket: usize, count: u32) {
+        if count == 0 {
+            return;
+        }
+
+        if self.buckets.len() <= bucket {
+            self.buckets.resize(bucket + 1, 0);
+        }
+        self.buckets[bucket] += count;
+    }
+
+    /// The highest notes per second of the buckets overlapping the given time range
+    pub fn max_nps_between(&self, start: f64, end: f64) -> f32 {
+        let range = Self::buckets_between(start, end);
+        let start = (*range.start()).min(self.buckets.len());
+        let end = (*range.end() + 1).min(self.buckets.len());
+
+        let max = self.buckets[start..end].iter().max().copied().unwrap_or(0);
+        (max as f64 / BUCKET_LENGTH) as f32
+    }
+
+    /// The highest notes per second of the whole MIDI
+    pub fn max_nps(&self) -> f32 {
+        let max = self.buckets.iter().max().copied().unwrap_or(0);
+        (max as f64 / BUCKET_LENGTH) as f32
+    }
+}
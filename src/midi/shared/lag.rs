@@ -0,0 +1,70 @@
+use crate::settings::{LagPolicy, MidiSettings};
+
+use super::timer::TimeListener;
+
+pub enum LagAction {
+    /// Push all events of the block
+    Push,
+    /// Push the block without its note ons
+    SkipNotes,
+}
+
+/// Measures how far an audio player is behind its timer, and decides
+/// how it catches up based on the lag policy
+pub struct LagMonitor {
+    policy: LagPolicy,
+    max_lag: f64,
+    /// Whether the clock is frozen until the audio catches up
+    frozen: bool,
+}
+
+impl LagMonitor {
+    pub fn new(settings: &MidiSettings) -> Self {
+        Self {
+            policy: settings.lag_policy,
+            max_lag: settings.max_lag.max(0.0) / 1000.0,
+            frozen: false,
+        }
+    }
+
+    /// Whether an event is later than the allowed lag at the given time
+    pub fn is_late(&self, now: f64, time: f64) -> bool {
+        now - time > self.max_lag
+    }
+
+    /// Checks the lag of a block before waiting for its time
+    pub fn check(&mut self, timer: &TimeListener, time: f64) -> LagAction {
+        let now = timer.get_time().as_seconds_f64();
+        if self.frozen && time >= now {
+            // The audio caught up with the frozen clock
+            timer.unfreeze();
+            self.frozen = false;
+        }
+
+        let lag = (now - time).max(0.0);
+        timer.set_audio_lag(lag);
+
+        match self.policy {
+            LagPolicy::None => LagAction::Push,
+            LagPolicy::SkipNotes if self.is_late(now, time) => LagAction::SkipNotes,
+            LagPolicy::SkipNotes => LagAction::Push,
+            LagPolicy::PauseVisuals => {
+                // Frozen right away, so the visuals never go back in time
+                if !self.frozen && self.is_late(now, time) {
+                    timer.freeze();
+                    self.frozen = true;
+                }
+                LagAction::Push
+            }
+        }
+    }
+
+    /// Forgets the lag after a pause or a seek
+    pub fn reset(&mut self, timer: &TimeListener) {
+        if self.frozen {
+            timer.unfreeze();
+            self.frozen = false;
+        }
+        timer.set_audio_lag(0.0);
+    }
+}
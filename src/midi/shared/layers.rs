@@ -0,0 +1,212 @@
+use std::path::PathBuf;
+
+use gen_iter::GenIter;
+use midi_toolkit::{
+    events::Event,
+    io::{DiskReader, MIDIFile as TKMIDIFile},
+    pipe,
+    sequence::{
+        event::{
+            cancel_tempo_events, get_channels_array_statistics, scale_event_time, Delta,
+            EventBatch, Track,
+        },
+        unwrap_items, TimeCaster,
+    },
+};
+
+use crate::{
+    gui::window::WasabiError,
+    midi::{open_file_and_signature, MIDIColor, MIDIFileUniqueSignature},
+    settings::{Colors, MidiSettings},
+};
+
+pub type TrackEventBatch = Delta<f64, Track<EventBatch<Event>>>;
+
+type BoxedBatches = Box<dyn Iterator<Item = TrackEventBatch> + Send>;
+
+/// A MIDI file that is played as a layer of the current session
+#[derive(Debug, Clone)]
+pub struct MIDILayer {
+    pub path: PathBuf,
+    /// The time offset of the layer in seconds
+    pub offset: f64,
+    /// The colors used for this layer. If `None`, the colors from the settings are used.
+    pub colors: Option<Colors>,
+}
+
+impl MIDILayer {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            offset: 0.0,
+            colors: None,
+        }
+    }
+}
+
+pub struct LayerStats {
+    pub length: f64,
+    pub note_count: u64,
+}
+
+struct OpenedLayer {
+    midi: TKMIDIFile<DiskReader>,
+    offset: f64,
+    track_offset: u32,
+}
+
+/// The opened files of all the layers in a session. The events of all the layers are
+/// merged into a single stream, with the track numbers of each layer shifted so they
+/// don't overlap, which lets the parsers and the audio treat them as a single MIDI.
+pub struct MIDILayers {
+    layers: Vec<OpenedLayer>,
+    colors: Vec<MIDIColor>,
+    signature: MIDIFileUniqueSignature,
+}
+
+impl MIDILayers {
+    pub fn open(layers: &[MIDILayer], settings: &MidiSettings) -> Result<Self, WasabiError> {
+        let mut opened = Vec::with_capacity(layers.len());
+        let mut colors = Vec::new();
+        let mut signature = None;
+        let mut track_offset = 0;
+
+        for layer in layers {
+            let (file, layer_signature) = open_file_and_signature(&layer.path)?;
+            let midi =
+                TKMIDIFile::open_from_stream(file, None).map_err(WasabiError::MidiLoadError)?;
+
+            let layer_colors = layer.colors.unwrap_or(settings.colors);
+            colors.extend(MIDIColor::new_vec_with_colors(
+                midi.track_count(),
+                layer_colors,
+                settings,
+            )?);
+
+            let track_count = midi.track_count() as u32;
+            opened.push(OpenedLayer {
+                midi,
+                offset: layer.offset.max(0.0),
+                track_offset,
+            });
+            track_offset += track_count;
+
+            // The session is identified by its first layer
+            signature.get_or_insert(layer_signature);
+        }
+
+        let signature =
+            signature.ok_or_else(|| WasabiError::Other("No MIDI files to load".into()))?;
+
+        Ok(Self {
+            layers: opened,
+            colors,
+            signature,
+        })
+    }
+
+    pub fn colors(&self) -> Vec<MIDIColor> {
+        self.colors.clone()
+    }
+
+    pub fn signature(&self) -> MIDIFileUniqueSignature {
+        self.signature.clone()
+    }
+
+    /// Iterates the events of all the layers, merged in time and scaled to seconds
+    pub fn iter_merged_batches(&self) -> impl Iterator<Item = TrackEventBatch> + Send + 'static {
+        let layers = self
+            .layers
+            .iter()
+            .map(|layer| {
+                let ppq = layer.midi.ppq();
+                let merged = pipe!(
+                    layer.midi.iter_all_track_events_merged_batches()
+                    |>TimeCaster::<f64>::cast_event_delta()
+                    |>cancel_tempo_events(250000)
+                    |>scale_event_time(1.0 / ppq as f64)
+                    |>unwrap_items()
+                );
+
+                (
+                    Box::new(merged) as BoxedBatches,
+                    layer.offset,
+                    layer.track_offset,
+                )
+            })
+            .collect();
+
+        merge_layer_batches(layers)
+    }
+
+    /// Returns a function that calculates the combined length and note count of the
+    /// layers. This reads through the files, so it should be called from a separate thread.
+    pub fn stats_calculator(&self) -> impl FnOnce() -> Option<LayerStats> + Send + 'static {
+        let layers: Vec<_> = self
+            .layers
+            .iter()
+            .map(|layer| {
+                let tracks = layer.midi.iter_all_tracks().collect();
+                (tracks, layer.midi.ppq(), layer.offset)
+            })
+            .collect();
+
+        move || {
+            let mut length: f64 = 0.0;
+            let mut note_count = 0;
+
+            for (tracks, ppq, offset) in layers {
+                let stats = get_channels_array_statistics(tracks).ok()?;
+                length = length.max(stats.calculate_total_duration(ppq).as_secs_f64() + offset);
+                note_count += stats.note_count();
+            }
+
+            Some(LayerStats { length, note_count })
+        }
+    }
+}
+
+/// Merges the event batches of the layers in time order, applying the time
+/// offset and the track offset of each layer.
+fn merge_layer_batches(
+    layers: Vec<(BoxedBatches, f64, u32)>,
+) -> impl Iterator<Item = TrackEventBatch> + Send + 'static {
+    GenIter(
+        #[coroutine]
+        move || {
+            let mut iters = Vec::with_capacity(layers.len());
+            let mut heads = Vec::with_capacity(layers.len());
+
+            for (mut iter, offset, track_offset) in layers {
+                let head = iter.next().map(|batch| (offset + batch.delta, batch));
+                heads.push(head);
+                iters.push((iter, track_offset));
+            }
+
+            let mut last_time = 0.0;
+
+            loop {
+                // Pick the layer with the earliest next batch, preferring the first layers
+                let next = heads
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, head)| head.as_ref().map(|(time, _)| (i, *time)))
+                    .min_by(|a, b| a.1.total_cmp(&b.1));
+
+                let Some((i, time)) = next else {
+                    break;
+                };
+
+                let (iter, track_offset) = &mut iters[i];
+                let (_, mut batch) = heads[i].take().unwrap();
+                heads[i] = iter.next().map(|next| (time + next.delta, next));
+
+                batch.delta = time - last_time;
+                batch.track += *track_offset;
+                last_time = time;
+
+                yield batch;
+            }
+        },
+    )
+}
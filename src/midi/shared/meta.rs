@@ -0,0 +1,189 @@
+use std::sync::{Arc, RwLock};
+
+use midi_toolkit::events::{Event, MIDIEventEnum, TextEventKind};
+
+use super::layers::TrackEventBatch;
+
+/// How long text and copyright events stay in the overlay, in seconds
+const TEXT_DISPLAY_TIME: f64 = 4.0;
+/// Lyrics older than this are not considered part of the current line, in seconds
+const LYRIC_LINE_TIMEOUT: f64 = 8.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetaEventKind {
+    Text,
+    Copyright,
+    Lyric,
+    Marker,
+    CuePoint,
+}
+
+impl MetaEventKind {
+    fn from_text_kind(kind: &TextEventKind) -> Option<Self> {
+        match kind {
+            TextEventKind::TextEvent => Some(Self::Text),
+            TextEventKind::CopyrightNotice => Some(Self::Copyright),
+            TextEventKind::Lyric => Some(Self::Lyric),
+            TextEventKind::Marker => Some(Self::Marker),
+            TextEventKind::CuePoint => Some(Self::CuePoint),
+            _ => None,
+        }
+    }
+
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Text => "Text",
+            Self::Copyright => "Copyright",
+            Self::Lyric => "Lyric",
+            Self::Marker => "Marker",
+            Self::CuePoint => "Cue Point",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MetaEvent {
+    /// The time of the event in seconds
+    pub time: f64,
+    pub kind: MetaEventKind,
+    pub text: String,
+}
+
+/// The text, marker and lyric meta events of a MIDI, in chronological order.
+/// The events are shared, so they can be collected by the parser threads while
+/// the file is being played.
+#[derive(Debug, Clone, Default)]
+pub struct MIDIMetaEvents {
+    events: Arc<RwLock<Vec<MetaEvent>>>,
+}
+
+impl MIDIMetaEvents {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wraps an iterator of batches, collecting their meta events as they pass through
+    pub fn collect_from(
+        &self,
+        iter: impl Iterator<Item = TrackEventBatch> + Send + 'static,
+    ) -> impl Iterator<Item = TrackEventBatch> + Send + 'static {
+        let events = self.clone();
+        let mut time = 0.0;
+
+        iter.map(move |batch| {
+            time += batch.delta;
+            events.add_batch(time, &batch);
+            batch
+        })
+    }
+
+    fn add_batch(&self, time: f64, batch: &TrackEventBatch) {
+        let mut new_events = batch.iter_events().filter_map(|event| {
+            if let Event::Text(e) = event.as_event() {
+                let kind = MetaEventKind::from_text_kind(&e.kind)?;
+                let text = String::from_utf8_lossy(&e.bytes).into_owned();
+                Some(MetaEvent { time, kind, text })
+            } else {
+                None
+            }
+        });
+
+        // Avoid locking for the batches without meta events, which are most of them
+        if let Some(first) = new_events.next() {
+            let mut events = self.events.write().unwrap();
+            events.push(first);
+            events.extend(new_events);
+        }
+    }
+
+    fn events_until(events: &[MetaEvent], time: f64) -> &[MetaEvent] {
+        let end = events.partition_point(|e| e.time <= time);
+        &events[..end]
+    }
+
+    /// The markers and cue points of the MIDI, used for navigation
+    pub fn markers(&self) -> Vec<MetaEvent> {
+        let events = self.events.read().unwrap();
+        events
+            .iter()
+            .filter(|e| matches!(e.kind, MetaEventKind::Marker | MetaEventKind::CuePoint))
+            .map(|e| MetaEvent {
+                text: e.text.trim().to_owned(),
+                ..e.clone()
+            })
+            .collect()
+    }
+
+    /// The text of the last marker before the given time
+    pub fn current_marker(&self, time: f64) -> Option<String> {
+        let events = self.events.read().unwrap();
+        Self::events_until(&events, time)
+            .iter()
+            .rev()
+            .find(|e| e.kind == MetaEventKind::Marker)
+            .map(|e| e.text.trim().to_owned())
+            .filter(|text| !text.is_empty())
+    }
+
+    /// The text and copyright events that were shown recently before the given time
+    pub fn current_texts(&self, time: f64) -> Vec<String> {
+        let events = self.events.read().unwrap();
+        Self::events_until(&events, time)
+            .iter()
+            .rev()
+            .take_while(|e| time - e.time < TEXT_DISPLAY_TIME)
+            .filter(|e| matches!(e.kind, MetaEventKind::Text | MetaEventKind::Copyright))
+            .map(|e| e.text.trim().to_owned())
+            .filter(|text| !text.is_empty())
+            .collect::<Vec<_>>()
+            .into_iter()
+            .rev()
+            .collect()
+    }
+
+    /// The line of lyrics sung up to the given time. Lines are split on the
+    /// line breaks and the `/` and `\` prefixes used by karaoke files.
+    pub fn current_lyrics(&self, time: f64) -> Option<String> {
+        fn starts_line(text: &str) -> bool {
+            text.starts_with(['\r', '\n', '/', '\\'])
+        }
+
+        fn ends_line(text: &str) -> bool {
+            text.ends_with(['\r', '\n'])
+        }
+
+        let events = self.events.read().unwrap();
+
+        let mut line = Vec::new();
+        let mut last_time = time;
+        for e in Self::events_until(&events, time)
+            .iter()
+            .rev()
+            .filter(|e| e.kind == MetaEventKind::Lyric)
+        {
+            if last_time - e.time > LYRIC_LINE_TIMEOUT || (!line.is_empty() && ends_line(&e.text)) {
+                break;
+            }
+
+            line.push(e.text.as_str());
+            last_time = e.time;
+
+            if starts_line(&e.text) {
+                break;
+            }
+        }
+
+        let line: String = line
+            .into_iter()
+            .rev()
+            .map(|text| text.trim_matches(['\r', '\n', '/', '\\']))
+            .collect();
+
+        let line = line.trim();
+        if line.is_empty() {
+            None
+        } else {
+            Some(line.to_owned())
+        }
+    }
+}
@@ -1,3 +1,7 @@
 pub mod audio;
+pub mod density;
+pub mod lag;
+pub mod layers;
+pub mod meta;
 pub mod timer;
 pub mod track_channel;
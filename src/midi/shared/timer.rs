@@ -1,8 +1,14 @@
 #![allow(dead_code)]
 
-use std::time::Instant;
+use atomic_float::AtomicF64;
+use std::{
+    sync::{atomic::Ordering, Arc},
+    time::Instant,
+};
 use time::Duration;
 
+use crate::audio_playback::AudioClock;
+
 struct NotifySignal {
     new_state: TimerState,
     has_seeked: bool,
@@ -11,7 +17,8 @@ struct NotifySignal {
 #[derive(Debug, Clone)]
 enum TimerState {
     Running {
-        continue_time: Instant,
+        /// The reading of the clock when the timer was started
+        continue_time: f64,
         time_offset: Duration,
     },
     Paused {
@@ -20,12 +27,12 @@ enum TimerState {
 }
 
 impl TimerState {
-    fn get_time(&self) -> Duration {
+    fn get_time(&self, clock: &SharedClock) -> Duration {
         match self {
             TimerState::Running {
                 continue_time,
                 time_offset,
-            } => continue_time.elapsed() + *time_offset,
+            } => Duration::seconds_f64(clock.now() - continue_time) + *time_offset,
             TimerState::Paused { time_offset } => *time_offset,
         }
     }
@@ -35,10 +42,70 @@ impl TimerState {
     }
 }
 
+/// Shared by the timer and its listeners, so the audio players can hold back the clock
+#[derive(Debug)]
+struct SharedClock {
+    epoch: Instant,
+    /// The audio clock that the time follows, or `None` to follow the wall clock
+    audio: Option<Arc<AudioClock>>,
+    /// The time in seconds that the clock was held back by
+    stall: AtomicF64,
+    /// The reading that the clock is frozen at, or NaN if it's running
+    frozen: AtomicF64,
+    /// How far the audio is behind the clock, in seconds
+    audio_lag: AtomicF64,
+}
+
+impl SharedClock {
+    fn new(audio: Option<Arc<AudioClock>>) -> Self {
+        Self {
+            epoch: Instant::now(),
+            audio,
+            stall: AtomicF64::new(0.0),
+            frozen: AtomicF64::new(f64::NAN),
+            audio_lag: AtomicF64::new(0.0),
+        }
+    }
+
+    fn read(&self) -> f64 {
+        match self.audio.as_ref() {
+            Some(audio) => audio.now(),
+            None => self.epoch.elapsed().as_secs_f64(),
+        }
+    }
+
+    /// The current reading of the clock in seconds, without the time it was frozen for
+    fn now(&self) -> f64 {
+        let frozen = self.frozen.load(Ordering::Relaxed);
+        if frozen.is_nan() {
+            self.read() - self.stall.load(Ordering::Relaxed)
+        } else {
+            frozen
+        }
+    }
+
+    fn freeze(&self) {
+        if self.frozen.load(Ordering::Relaxed).is_nan() {
+            self.frozen.store(self.now(), Ordering::Relaxed);
+        }
+    }
+
+    fn unfreeze(&self) {
+        let frozen = self.frozen.load(Ordering::Relaxed);
+        if !frozen.is_nan() {
+            // Carry on from the frozen reading. The stall is set first, so
+            // readers never see the time jump ahead.
+            self.stall.store(self.read() - frozen, Ordering::Relaxed);
+            self.frozen.store(f64::NAN, Ordering::Relaxed);
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct TimeKeeper {
     current_state: TimerState,
     listeners: Vec<crossbeam_channel::Sender<NotifySignal>>,
+    clock: Arc<SharedClock>,
 }
 
 impl TimeKeeper {
@@ -49,11 +116,25 @@ impl TimeKeeper {
                 time_offset: -start_delay,
             },
             listeners: Vec::new(),
+            clock: Arc::new(SharedClock::new(None)),
         }
     }
 
+    /// Makes the time follow the samples played by an audio device instead of
+    /// the wall clock. Must be set before any listeners are created.
+    pub fn with_audio_clock(mut self, clock: Arc<AudioClock>) -> Self {
+        debug_assert!(self.listeners.is_empty());
+        self.clock = Arc::new(SharedClock::new(Some(clock)));
+        self
+    }
+
     pub fn get_time(&self) -> Duration {
-        self.current_state.get_time()
+        self.current_state.get_time(&self.clock)
+    }
+
+    /// How far the audio is behind the time, in seconds
+    pub fn audio_lag(&self) -> f64 {
+        self.clock.audio_lag.load(Ordering::Relaxed)
     }
 
     pub fn is_paused(&self) -> bool {
@@ -66,6 +147,8 @@ impl TimeKeeper {
         TimeListener {
             reciever: rcv,
             current: self.current_state.clone(),
+            offset: None,
+            clock: self.clock.clone(),
         }
     }
 
@@ -90,11 +173,11 @@ impl TimeKeeper {
     }
 
     pub fn toggle_pause(&mut self) {
-        let now = self.get_time();
+        let now = self.current_state.get_time(&self.clock);
         match self.current_state {
             TimerState::Paused { .. } => {
                 self.current_state = TimerState::Running {
-                    continue_time: Instant::now(),
+                    continue_time: self.clock.now(),
                     time_offset: now,
                 };
             }
@@ -107,15 +190,15 @@ impl TimeKeeper {
     }
 
     pub fn pause(&mut self) {
-        let now = self.get_time();
+        let now = self.current_state.get_time(&self.clock);
         self.current_state = TimerState::Paused { time_offset: now };
         self.notify_listeners(false);
     }
 
     pub fn play(&mut self) {
-        let now = self.get_time();
+        let now = self.current_state.get_time(&self.clock);
         self.current_state = TimerState::Running {
-            continue_time: Instant::now(),
+            continue_time: self.clock.now(),
             time_offset: now,
         };
         self.notify_listeners(false);
@@ -126,7 +209,7 @@ impl TimeKeeper {
             self.current_state = TimerState::Paused { time_offset: time };
         } else {
             self.current_state = TimerState::Running {
-                continue_time: Instant::now(),
+                continue_time: self.clock.now(),
                 time_offset: time,
             };
         }
@@ -137,6 +220,9 @@ impl TimeKeeper {
 pub struct TimeListener {
     reciever: crossbeam_channel::Receiver<NotifySignal>,
     current: TimerState,
+    /// An offset in seconds that is added to the time, which can change while listening
+    offset: Option<Arc<AtomicF64>>,
+    clock: Arc<SharedClock>,
 }
 
 #[must_use]
@@ -161,12 +247,43 @@ pub enum SeekWaitResult {
 }
 
 impl TimeListener {
+    /// Shifts the time of this listener ahead by the given offset. This is used to
+    /// send audio early, to make up for the latency of the synth.
+    pub fn with_offset(mut self, offset: Arc<AtomicF64>) -> Self {
+        self.offset = Some(offset);
+        self
+    }
+
+    fn time(&self) -> Duration {
+        let offset = self
+            .offset
+            .as_ref()
+            .map(|offset| offset.load(Ordering::Relaxed))
+            .unwrap_or(0.0);
+        self.current.get_time(&self.clock) + Duration::seconds_f64(offset)
+    }
+
+    /// Stops the clock of the timer and all of its listeners, until it is unfrozen
+    pub fn freeze(&self) {
+        self.clock.freeze();
+    }
+
+    /// Lets the clock run again from the time it was frozen at
+    pub fn unfreeze(&self) {
+        self.clock.unfreeze();
+    }
+
+    /// Reports how far the audio is behind the clock, in seconds
+    pub fn set_audio_lag(&self, seconds: f64) {
+        self.clock.audio_lag.store(seconds, Ordering::Relaxed);
+    }
+
     pub fn is_paused(&self) -> bool {
         self.current.is_paused()
     }
 
     pub fn wait_until(&mut self, time: Duration) -> WaitResult {
-        let curr_time = self.current.get_time();
+        let curr_time = self.time();
         if curr_time >= time {
             return WaitResult::Ok;
         }
@@ -180,7 +297,7 @@ impl TimeListener {
             Ok(signal) => {
                 self.current = signal.new_state;
                 if signal.has_seeked {
-                    WaitResult::Seeked(self.current.get_time())
+                    WaitResult::Seeked(self.time())
                 } else if self.current.is_paused() {
                     WaitResult::Paused
                 } else {
@@ -208,7 +325,7 @@ impl TimeListener {
                 Ok(signal) => {
                     self.current = signal.new_state;
                     if signal.has_seeked {
-                        seeked = Some(self.current.get_time());
+                        seeked = Some(self.time());
                     }
 
                     if !self.current.is_paused() {
@@ -237,7 +354,7 @@ impl TimeListener {
                     }
 
                     if seeked && !self.current.is_paused() {
-                        return SeekWaitResult::UnpausedAndSeeked(self.current.get_time());
+                        return SeekWaitResult::UnpausedAndSeeked(self.time());
                     }
                 }
                 Err(_) => return SeekWaitResult::Killed,
@@ -246,6 +363,6 @@ impl TimeListener {
     }
 
     pub fn get_time(&self) -> Duration {
-        self.current.get_time()
+        self.time()
     }
 }
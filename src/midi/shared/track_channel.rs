@@ -1,5 +1,8 @@
 #![allow(dead_code)]
 
+/// The channel used for percussion by General MIDI (channel 10)
+pub const PERCUSSION_CHANNEL: u8 = 9;
+
 #[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
 pub struct TrackAndChannel(u32);
 
@@ -16,6 +19,10 @@ impl TrackAndChannel {
         (self.0 % 16) as u8
     }
 
+    pub fn is_percussion(&self) -> bool {
+        self.channel() == PERCUSSION_CHANNEL
+    }
+
     pub fn as_u32(&self) -> u32 {
         self.0
     }
@@ -10,6 +10,7 @@ pub enum MidiParsing {
     Ram = 0,
     Live = 1,
     Cake = 2,
+    CakeLive = 3,
 }
 
 impl MidiParsing {
@@ -18,6 +19,7 @@ impl MidiParsing {
             MidiParsing::Ram => "Standard (RAM)",
             MidiParsing::Live => "Standard (Live)",
             MidiParsing::Cake => "Cake",
+            MidiParsing::CakeLive => "Cake (Live)",
         }
     }
 }
@@ -30,8 +32,81 @@ impl FromStr for MidiParsing {
             "ram" => Ok(MidiParsing::Ram),
             "live" => Ok(MidiParsing::Live),
             "cake" => Ok(MidiParsing::Cake),
+            "cakelive" => Ok(MidiParsing::CakeLive),
             s => Err(format!(
-                "{} was not expected. Expected one of `ram`, `live` or `cake`",
+                "{} was not expected. Expected one of `ram`, `live`, `cake` or `cakelive`",
+                s
+            )),
+        }
+    }
+}
+
+#[repr(usize)]
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Eq, Clone, Copy, FromPrimitive)]
+#[serde(rename_all = "lowercase")]
+pub enum NoteOverlap {
+    #[default]
+    Fifo = 0,
+    Lifo = 1,
+}
+
+impl NoteOverlap {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            NoteOverlap::Fifo => "First In, First Out",
+            NoteOverlap::Lifo => "Last In, First Out",
+        }
+    }
+}
+
+impl FromStr for NoteOverlap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "fifo" => Ok(NoteOverlap::Fifo),
+            "lifo" => Ok(NoteOverlap::Lifo),
+            s => Err(format!(
+                "{} was not expected. Expected one of `fifo` or `lifo`",
+                s
+            )),
+        }
+    }
+}
+
+#[repr(usize)]
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Eq, Clone, Copy, FromPrimitive)]
+#[serde(rename_all = "lowercase")]
+pub enum PercussionDisplay {
+    #[default]
+    Normal = 0,
+    Hidden = 1,
+    HitMarkers = 2,
+    Lane = 3,
+}
+
+impl PercussionDisplay {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            PercussionDisplay::Normal => "Normal",
+            PercussionDisplay::Hidden => "Hidden",
+            PercussionDisplay::HitMarkers => "Hit Markers",
+            PercussionDisplay::Lane => "Dedicated Lane",
+        }
+    }
+}
+
+impl FromStr for PercussionDisplay {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "normal" => Ok(PercussionDisplay::Normal),
+            "hidden" => Ok(PercussionDisplay::Hidden),
+            "hitmarkers" => Ok(PercussionDisplay::HitMarkers),
+            "lane" => Ok(PercussionDisplay::Lane),
+            s => Err(format!(
+                "{} was not expected. Expected one of `normal`, `hidden`, `hitmarkers` or `lane`",
                 s
             )),
         }
@@ -50,6 +125,8 @@ pub enum Synth {
     #[cfg(all(supported_os, not(target_os = "freebsd")))]
     MidiDevice = 2,
     None = 3,
+    XSynthRecording = 4,
+    Network = 5,
 }
 
 impl Synth {
@@ -62,6 +139,8 @@ impl Synth {
             #[cfg(all(supported_os, not(target_os = "freebsd")))]
             Synth::MidiDevice => "MIDI Device",
             Synth::None => "None",
+            Synth::XSynthRecording => "Built-In (XSynth) + Recording",
+            Synth::Network => "Network (OSC)",
         }
     }
 }
@@ -77,8 +156,203 @@ impl FromStr for Synth {
             #[cfg(all(supported_os, not(target_os = "freebsd")))]
             "mididevice" => Ok(Synth::MidiDevice),
             "none" => Ok(Synth::None),
+            "xsynthrecording" => Ok(Synth::XSynthRecording),
+            "network" => Ok(Synth::Network),
+            s => Err(format!(
+                "{} was not expected. Expected one of `xsynth`, `kdmapi`, `mididevice`, `none`, `xsynthrecording` or `network`",
+                s
+            )),
+        }
+    }
+}
+
+/// What a MIDI device output does when the device can't keep up with the events
+#[repr(usize)]
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, FromPrimitive)]
+#[serde(rename_all = "lowercase")]
+pub enum BackpressurePolicy {
+    DropOldest = 0,
+    #[default]
+    DropNoteOns = 1,
+    Block = 2,
+}
+
+impl BackpressurePolicy {
+    #[inline]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            BackpressurePolicy::DropOldest => "Drop Oldest Events",
+            BackpressurePolicy::DropNoteOns => "Drop Note Ons",
+            BackpressurePolicy::Block => "Wait",
+        }
+    }
+}
+
+impl FromStr for BackpressurePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "dropoldest" => Ok(BackpressurePolicy::DropOldest),
+            "dropnoteons" => Ok(BackpressurePolicy::DropNoteOns),
+            "block" => Ok(BackpressurePolicy::Block),
+            s => Err(format!(
+                "{} was not expected. Expected one of `dropoldest`, `dropnoteons` or `block`",
+                s
+            )),
+        }
+    }
+}
+
+/// What the audio players do when they fall behind the playback time
+#[repr(usize)]
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, FromPrimitive)]
+#[serde(rename_all = "lowercase")]
+pub enum LagPolicy {
+    /// Every event is played, however late it is
+    #[default]
+    None = 0,
+    /// The note ons are skipped until the audio is on time again
+    SkipNotes = 1,
+    /// The playback time is held until the audio is on time again
+    PauseVisuals = 2,
+}
+
+impl LagPolicy {
+    #[inline]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            LagPolicy::None => "Play Everything",
+            LagPolicy::SkipNotes => "Skip Notes",
+            LagPolicy::PauseVisuals => "Pause Visuals",
+        }
+    }
+}
+
+impl FromStr for LagPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "none" => Ok(LagPolicy::None),
+            "skipnotes" => Ok(LagPolicy::SkipNotes),
+            "pausevisuals" => Ok(LagPolicy::PauseVisuals),
+            s => Err(format!(
+                "{} was not expected. Expected one of `none`, `skipnotes` or `pausevisuals`",
+                s
+            )),
+        }
+    }
+}
+
+/// Which notes the note limiter keeps when there are too many
+#[repr(usize)]
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, FromPrimitive)]
+#[serde(rename_all = "lowercase")]
+pub enum LimiterPriority {
+    #[default]
+    Loudest = 0,
+    Newest = 1,
+}
+
+impl LimiterPriority {
+    #[inline]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            LimiterPriority::Loudest => "Loudest Notes",
+            LimiterPriority::Newest => "Newest Notes",
+        }
+    }
+}
+
+impl FromStr for LimiterPriority {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "loudest" => Ok(LimiterPriority::Loudest),
+            "newest" => Ok(LimiterPriority::Newest),
+            s => Err(format!(
+                "{} was not expected. Expected one of `loudest` or `newest`",
+                s
+            )),
+        }
+    }
+}
+
+/// How the OSC addresses of the network output are formed
+#[repr(usize)]
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, FromPrimitive)]
+#[serde(rename_all = "lowercase")]
+pub enum OscAddressScheme {
+    /// Every event is sent to the same address as a raw MIDI message
+    #[default]
+    Midi = 0,
+    /// Every event type has its own address, like `/wasabi/note_on`, with integer arguments
+    PerEvent = 1,
+}
+
+impl OscAddressScheme {
+    #[inline]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            OscAddressScheme::Midi => "Raw MIDI",
+            OscAddressScheme::PerEvent => "Address Per Event",
+        }
+    }
+}
+
+impl FromStr for OscAddressScheme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "midi" => Ok(OscAddressScheme::Midi),
+            "perevent" => Ok(OscAddressScheme::PerEvent),
+            s => Err(format!(
+                "{} was not expected. Expected one of `midi` or `perevent`",
+                s
+            )),
+        }
+    }
+}
+
+/// How the network output timestamps the OSC messages
+#[repr(usize)]
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, FromPrimitive)]
+#[serde(rename_all = "lowercase")]
+pub enum OscTimestamps {
+    /// Every event is sent as a plain message
+    None = 0,
+    /// The events are sent in bundles that are handled immediately
+    #[default]
+    Immediate = 1,
+    /// The events are sent in bundles tagged with the wall clock time they are played at,
+    /// which is the time they were sent plus the latency offset
+    WallClock = 2,
+}
+
+impl OscTimestamps {
+    #[inline]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            OscTimestamps::None => "None",
+            OscTimestamps::Immediate => "Immediate Bundles",
+            OscTimestamps::WallClock => "Timestamped Bundles",
+        }
+    }
+}
+
+impl FromStr for OscTimestamps {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "none" => Ok(OscTimestamps::None),
+            "immediate" => Ok(OscTimestamps::Immediate),
+            "wallclock" => Ok(OscTimestamps::WallClock),
             s => Err(format!(
-                "{} was not expected. Expected one of `xsynth`, `kdmapi`, `mididevice` or `none`",
+                "{} was not expected. Expected one of `none`, `immediate` or `wallclock`",
                 s
             )),
         }
@@ -97,6 +371,15 @@ pub enum Statistics {
     NoteCount = 4,
     Polyphony = 5,
     Nps = 6,
+    DroppedEvents = 7,
+    EventsPerSecond = 8,
+    IgnoredNotes = 9,
+    RenderLoad = 10,
+    BufferUnderruns = 11,
+    #[serde(alias = "channelnotes")]
+    ChannelVoices = 12,
+    LimitedNotes = 13,
+    AudioLag = 14,
 }
 
 impl Statistics {
@@ -110,11 +393,33 @@ impl Statistics {
             Statistics::NoteCount => "Note Count",
             Statistics::Polyphony => "Polyphony",
             Statistics::Nps => "NPS",
+            Statistics::DroppedEvents => "Dropped Events",
+            Statistics::EventsPerSecond => "Events/s",
+            Statistics::IgnoredNotes => "Ignored Notes",
+            Statistics::RenderLoad => "Render Load",
+            Statistics::BufferUnderruns => "Buffer Underruns",
+            Statistics::ChannelVoices => "Channel Voices",
+            Statistics::LimitedNotes => "Limited Notes",
+            Statistics::AudioLag => "Audio Lag",
         }
     }
 
+    /// Whether the statistic is shown when it is first added to the list
+    pub const fn default_visible(self) -> bool {
+        !matches!(
+            self,
+            Statistics::EventsPerSecond
+                | Statistics::IgnoredNotes
+                | Statistics::RenderLoad
+                | Statistics::BufferUnderruns
+                | Statistics::ChannelVoices
+                | Statistics::LimitedNotes
+                | Statistics::AudioLag
+        )
+    }
+
     pub fn iter() -> Iter<'static, Statistics> {
-        static STATISTICS: [Statistics; 7] = [
+        static STATISTICS: [Statistics; 15] = [
             Statistics::Time,
             Statistics::Fps,
             Statistics::Rendered,
@@ -122,6 +427,14 @@ impl Statistics {
             Statistics::Polyphony,
             Statistics::VoiceCount,
             Statistics::NoteCount,
+            Statistics::DroppedEvents,
+            Statistics::EventsPerSecond,
+            Statistics::IgnoredNotes,
+            Statistics::RenderLoad,
+            Statistics::BufferUnderruns,
+            Statistics::ChannelVoices,
+            Statistics::LimitedNotes,
+            Statistics::AudioLag,
         ];
         STATISTICS.iter()
     }
@@ -139,6 +452,14 @@ impl FromStr for Statistics {
             "notecount" => Ok(Statistics::NoteCount),
             "polyphony" => Ok(Statistics::Polyphony),
             "nps" => Ok(Statistics::Nps),
+            "droppedevents" => Ok(Statistics::DroppedEvents),
+            "eventspersecond" => Ok(Statistics::EventsPerSecond),
+            "ignorednotes" => Ok(Statistics::IgnoredNotes),
+            "renderload" => Ok(Statistics::RenderLoad),
+            "bufferunderruns" => Ok(Statistics::BufferUnderruns),
+            "channelvoices" | "channelnotes" => Ok(Statistics::ChannelVoices),
+            "limitednotes" => Ok(Statistics::LimitedNotes),
+            "audiolag" => Ok(Statistics::AudioLag),
             s => Err(format!("{} was not expected.", s)),
         }
     }
@@ -0,0 +1,96 @@
+use serde_derive::{Deserialize, Serialize};
+use std::{fs, path::PathBuf};
+
+use crate::{gui::window::WasabiError, midi::MIDIFileUniqueSignature};
+
+use super::{MidiParsing, MidiSettings, WasabiSettings};
+
+/// The playback state that is remembered for each opened MIDI file
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MIDIFileState {
+    pub signature: MIDIFileUniqueSignature,
+    /// The last playback position in seconds
+    pub position: f64,
+    pub start_delay: f64,
+    pub parsing: MidiParsing,
+    /// Bookmarked timestamps in seconds, in chronological order
+    pub bookmarks: Vec<f64>,
+}
+
+impl MIDIFileState {
+    pub fn add_bookmark(&mut self, time: f64) {
+        let index = self.bookmarks.partition_point(|b| *b < time);
+        if self.bookmarks.get(index) != Some(&time) {
+            self.bookmarks.insert(index, time);
+        }
+    }
+}
+
+/// The remembered states of the recently opened MIDI files, persisted next to the settings
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct MIDIFileStates {
+    files: Vec<MIDIFileState>,
+}
+
+impl MIDIFileStates {
+    /// The maximum number of files to remember, the least recently opened are forgotten first
+    const MAX_FILES: usize = 200;
+
+    pub fn load() -> Result<Self, WasabiError> {
+        let path = Self::get_path();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let states = fs::read_to_string(&path).map_err(WasabiError::FilesystemError)?;
+        serde_json::from_str(&states).map_err(|e| WasabiError::SettingsError(e.to_string()))
+    }
+
+    pub fn save(&self) -> Result<(), WasabiError> {
+        let states = serde_json::to_string_pretty(&self)
+            .map_err(|e| WasabiError::SettingsError(e.to_string()))?;
+        fs::write(Self::get_path(), states).map_err(WasabiError::FilesystemError)
+    }
+
+    fn get_path() -> PathBuf {
+        let mut path = WasabiSettings::get_config_dir();
+        path.push("wasabi-files.json");
+
+        path
+    }
+
+    pub fn get(&self, signature: &MIDIFileUniqueSignature) -> Option<&MIDIFileState> {
+        self.files.iter().find(|f| &f.signature == signature)
+    }
+
+    pub fn get_mut(&mut self, signature: &MIDIFileUniqueSignature) -> Option<&mut MIDIFileState> {
+        self.files.iter_mut().find(|f| &f.signature == signature)
+    }
+
+    /// Marks the file as the most recently opened one, creating its state from
+    /// the current settings if it isn't remembered yet.
+    pub fn open(
+        &mut self,
+        signature: &MIDIFileUniqueSignature,
+        settings: &MidiSettings,
+    ) -> &mut MIDIFileState {
+        let state = match self.files.iter().position(|f| &f.signature == signature) {
+            Some(index) => self.files.remove(index),
+            None => MIDIFileState {
+                signature: signature.clone(),
+                position: 0.0,
+                start_delay: settings.start_delay,
+                parsing: settings.parsing,
+                bookmarks: Vec::new(),
+            },
+        };
+
+        self.files.push(state);
+        if self.files.len() > Self::MAX_FILES {
+            self.files.remove(0);
+        }
+
+        self.files.last_mut().unwrap()
+    }
+}
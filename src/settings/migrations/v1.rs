@@ -5,8 +5,8 @@ use xsynth_realtime::{ChannelInitOptions, XSynthRealtimeConfig};
 
 use super::serializers::{color32_serde, range_serde};
 use crate::settings::{
-    MidiParsing, MidiSettings, SceneSettings, Synth, SynthSettings, WasabiSettings,
-    WasabiSoundfont, XSynthSettings,
+    MidiParsing, MidiSettings, SceneSettings, SoundfontProfile, Synth, SynthSettings,
+    WasabiSettings, WasabiSoundfont, XSynthSettings,
 };
 
 #[allow(dead_code)]
@@ -69,6 +69,7 @@ impl WasabiConfigFileV1 {
                 statistics: Default::default(),
                 note_speed: cfg.midi.note_speed,
                 key_range: cfg.midi.key_range,
+                ..Default::default()
             },
             midi: MidiSettings {
                 parsing: cfg.midi.midi_loading,
@@ -76,10 +77,13 @@ impl WasabiConfigFileV1 {
             },
             synth: SynthSettings {
                 synth: cfg.synth.synth,
-                soundfonts: vec![WasabiSoundfont {
-                    path: cfg.synth.sfz_path.into(),
-                    enabled: true,
-                    options: Default::default(),
+                profiles: vec![SoundfontProfile {
+                    soundfonts: vec![WasabiSoundfont {
+                        path: cfg.synth.sfz_path.into(),
+                        enabled: true,
+                        ..Default::default()
+                    }],
+                    ..Default::default()
                 }],
                 xsynth: XSynthSettings {
                     layers: cfg.synth.layer_count,
@@ -1,4 +1,4 @@
-use directories::BaseDirs;
+use directories::{BaseDirs, UserDirs};
 use egui::Color32;
 use serde_derive::{Deserialize, Serialize};
 use std::{
@@ -12,9 +12,11 @@ use xsynth_core::soundfont::SoundfontInitOptions;
 use xsynth_realtime::XSynthRealtimeConfig;
 
 mod enums;
+mod file_states;
 mod migrations;
 
 pub use enums::*;
+pub use file_states::*;
 
 use crate::gui::window::WasabiError;
 
@@ -27,6 +29,7 @@ pub struct GuiSettings {
     pub vsync: bool,
     pub skip_control: f64,
     pub speed_control: f64,
+    pub recent_files: Vec<RecentMIDIFile>,
 }
 
 impl Default for GuiSettings {
@@ -36,10 +39,37 @@ impl Default for GuiSettings {
             vsync: true,
             skip_control: 1.0,
             speed_control: 0.05,
+            recent_files: Vec::new(),
         }
     }
 }
 
+impl GuiSettings {
+    const MAX_RECENT_FILES: usize = 10;
+
+    /// Moves the file to the top of the recent files, or adds it if it's not there
+    pub fn add_recent_file(&mut self, file: RecentMIDIFile) {
+        self.recent_files.retain(|f| f.path != file.path);
+        self.recent_files.insert(0, file);
+        self.recent_files.truncate(Self::MAX_RECENT_FILES);
+    }
+
+    /// Removes the recent files that no longer exist
+    pub fn prune_recent_files(&mut self) {
+        self.recent_files.retain(|f| f.path.exists());
+    }
+}
+
+/// A recently opened MIDI file. The note count and length are kept
+/// so they can be shown without parsing the file again.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RecentMIDIFile {
+    pub path: PathBuf,
+    pub size: u64,
+    pub note_count: Option<u64>,
+    pub length: Option<f64>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(default)]
 pub struct StatisticsSettings {
@@ -49,13 +79,34 @@ pub struct StatisticsSettings {
     pub order: Vec<(Statistics, bool)>,
 }
 
+impl StatisticsSettings {
+    /// Adds the statistics that are missing from the order, like the ones
+    /// added after the settings were saved, and removes duplicates
+    pub fn add_missing(&mut self) {
+        let mut seen = Vec::new();
+        self.order.retain(|(stat, _)| {
+            let new = !seen.contains(stat);
+            seen.push(*stat);
+            new
+        });
+
+        for stat in Statistics::iter() {
+            if !self.order.iter().any(|(s, _)| s == stat) {
+                self.order.push((*stat, stat.default_visible()));
+            }
+        }
+    }
+}
+
 impl Default for StatisticsSettings {
     fn default() -> Self {
         Self {
             border: true,
             floating: true,
             opacity: 0.5,
-            order: Statistics::iter().map(|i| (*i, true)).collect(),
+            order: Statistics::iter()
+                .map(|i| (*i, i.default_visible()))
+                .collect(),
         }
     }
 }
@@ -68,6 +119,8 @@ pub struct SceneSettings {
     pub statistics: StatisticsSettings,
     pub note_speed: f64,
     pub key_range: RangeInclusive<u8>,
+    pub percussion: PercussionDisplay,
+    pub meta_overlay: bool,
 }
 
 impl Default for SceneSettings {
@@ -78,6 +131,8 @@ impl Default for SceneSettings {
             statistics: Default::default(),
             note_speed: 0.25,
             key_range: 0..=127,
+            percussion: PercussionDisplay::Normal,
+            meta_overlay: true,
         }
     }
 }
@@ -91,9 +146,14 @@ impl Default for SceneSettings {
 pub struct MidiSettings {
     pub parsing: MidiParsing,
     pub start_delay: f64,
+    pub note_overlap: NoteOverlap,
     pub colors: Colors,
     pub randomize_palette: bool,
     pub palette_path: PathBuf,
+    /// What the audio does when it falls behind by more than `max_lag`
+    pub lag_policy: LagPolicy,
+    /// The allowed audio lag in milliseconds, also used to skip late events after a seek
+    pub max_lag: f64,
 }
 
 impl Default for MidiSettings {
@@ -101,9 +161,12 @@ impl Default for MidiSettings {
         Self {
             parsing: MidiParsing::Cake,
             start_delay: 2.0,
+            note_overlap: NoteOverlap::Fifo,
             colors: Colors::Rainbow,
             randomize_palette: false,
             palette_path: PathBuf::new(),
+            lag_policy: LagPolicy::default(),
+            max_lag: 100.0,
         }
     }
 }
@@ -118,6 +181,14 @@ pub struct XSynthSettings {
     pub config: XSynthRealtimeConfig,
     pub limit_layers: bool,
     pub layers: usize,
+    /// The name of the audio output device, or empty for the default device
+    pub audio_device: String,
+    /// The output sample rate, or `None` for the default rate of the device
+    pub sample_rate: Option<u32>,
+    /// The samples per channel that the device plays at once, or `None` for the default size
+    pub buffer_size: Option<u32>,
+    /// Whether the playback time follows the samples played on the audio device
+    pub audio_clock: bool,
 }
 
 impl Default for XSynthSettings {
@@ -126,6 +197,10 @@ impl Default for XSynthSettings {
             config: Default::default(),
             limit_layers: true,
             layers: 4,
+            audio_device: String::new(),
+            sample_rate: None,
+            buffer_size: None,
+            audio_clock: false,
         }
     }
 }
@@ -136,33 +211,289 @@ pub struct KdmapiSettings {
     pub use_om_sflist: bool,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(default)]
 pub struct WasabiSoundfont {
     pub path: PathBuf,
     pub enabled: bool,
     pub options: SoundfontInitOptions,
+    /// A bit mask of the channels that use the soundfont, with channel 1 as the lowest bit.
+    /// Only applies to XSynth.
+    pub channels: u16,
+}
+
+impl Default for WasabiSoundfont {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::new(),
+            enabled: false,
+            options: Default::default(),
+            channels: u16::MAX,
+        }
+    }
+}
+
+/// A named soundfont list that can be switched to from the playback panel
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct SoundfontProfile {
+    pub name: String,
+    pub soundfonts: Vec<WasabiSoundfont>,
+}
+
+impl Default for SoundfontProfile {
+    fn default() -> Self {
+        Self {
+            name: "Default".into(),
+            soundfonts: Vec::new(),
+        }
+    }
+}
+
+/// The output latency of each synth in milliseconds
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct LatencySettings {
+    pub xsynth: f64,
+    pub kdmapi: f64,
+    pub midi_device: f64,
+    pub network: f64,
+}
+
+/// Where and how the network output sends the events
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct NetworkSettings {
+    pub host: String,
+    pub port: u16,
+    /// The OSC address of the messages, or the prefix of the addresses
+    pub address: String,
+    pub scheme: OscAddressScheme,
+    pub timestamps: OscTimestamps,
+}
+
+impl Default for NetworkSettings {
+    fn default() -> Self {
+        Self {
+            host: "127.0.0.1".into(),
+            port: 9000,
+            address: "/wasabi/midi".into(),
+            scheme: Default::default(),
+            timestamps: Default::default(),
+        }
+    }
+}
+
+/// The volumes of the mixer, as multipliers of the volumes set by the MIDI
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct MixerSettings {
+    pub master: f32,
+    pub channels: [f32; 16],
+}
+
+impl Default for MixerSettings {
+    fn default() -> Self {
+        Self {
+            master: 1.0,
+            channels: [1.0; 16],
+        }
+    }
+}
+
+/// Caps the note ons sent to the synths, so that they don't fall behind on very dense MIDIs
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct NoteLimiterSettings {
+    pub enabled: bool,
+    /// The length of a time slice in milliseconds
+    pub slice: f64,
+    /// The note ons sent in each time slice
+    pub max_notes: u32,
+    /// The note ons sent on each key of a channel in each time slice
+    pub max_per_key: u32,
+    pub priority: LimiterPriority,
+}
+
+impl Default for NoteLimiterSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            slice: 10.0,
+            max_notes: 2000,
+            max_per_key: 4,
+            priority: LimiterPriority::default(),
+        }
+    }
+}
+
+/// Which channels and tracks of the MIDI are sent to a synth output
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct OutputRouting {
+    /// A bit mask of the channels that are sent, with channel 1 as the lowest bit
+    pub channels: u16,
+    /// The tracks that are sent, as a list of numbers and ranges like `1-4, 7`.
+    /// All tracks are sent if empty.
+    pub tracks: String,
+}
+
+impl Default for OutputRouting {
+    fn default() -> Self {
+        Self {
+            channels: u16::MAX,
+            tracks: String::new(),
+        }
+    }
+}
+
+impl OutputRouting {
+    /// The zero-based track ranges that are sent. Invalid parts of the list are skipped.
+    pub fn track_ranges(&self) -> Vec<RangeInclusive<u32>> {
+        self.tracks
+            .split(',')
+            .filter_map(|part| {
+                let part = part.trim();
+                let (start, end) = part.split_once('-').unwrap_or((part, part));
+                let start = start.trim().parse::<u32>().ok()?.checked_sub(1)?;
+                let end = end.trim().parse::<u32>().ok()?.checked_sub(1)?;
+                Some(start..=end)
+            })
+            .collect()
+    }
+}
+
+/// A synth that receives the MIDI events along with the main synth
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct SynthOutput {
+    pub enabled: bool,
+    pub synth: Synth,
+    /// The name of the device, if the synth is a MIDI device
+    pub midi_device: String,
+    pub routing: OutputRouting,
+    /// The output latency in milliseconds
+    pub latency: f64,
+}
+
+impl Default for SynthOutput {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            synth: Synth::XSynth,
+            midi_device: String::new(),
+            routing: Default::default(),
+            latency: 0.0,
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(default)]
 pub struct SynthSettings {
     pub synth: Synth,
-    pub soundfonts: Vec<WasabiSoundfont>,
+    /// The single soundfont list of older configs, moved to a profile on load
+    #[serde(rename = "soundfonts", skip_serializing)]
+    legacy_soundfonts: Vec<WasabiSoundfont>,
+    pub profiles: Vec<SoundfontProfile>,
+    pub active_profile: usize,
 
     pub xsynth: XSynthSettings,
     pub kdmapi: KdmapiSettings,
     pub midi_device: String,
+    pub midi_device_backpressure: BackpressurePolicy,
+    pub network: NetworkSettings,
+    pub latency: LatencySettings,
+    pub mixer: MixerSettings,
+    pub limiter: NoteLimiterSettings,
+    pub routing: OutputRouting,
+    /// The additional outputs that play alongside the main synth
+    pub outputs: Vec<SynthOutput>,
+}
+
+impl SynthSettings {
+    pub fn profile(&self) -> &SoundfontProfile {
+        &self.profiles[self.active_profile]
+    }
+
+    pub fn profile_mut(&mut self) -> &mut SoundfontProfile {
+        &mut self.profiles[self.active_profile]
+    }
+
+    /// The soundfont list of the active profile
+    pub fn soundfonts(&self) -> &[WasabiSoundfont] {
+        &self.profile().soundfonts
+    }
+
+    /// Moves the soundfont list of older configs to the first profile, and
+    /// makes sure there is a valid active profile
+    pub fn migrate_profiles(&mut self) {
+        if self.profiles.is_empty() {
+            self.profiles.push(SoundfontProfile::default());
+        }
+        if !self.legacy_soundfonts.is_empty() {
+            self.profiles[0].soundfonts = std::mem::take(&mut self.legacy_soundfonts);
+            self.active_profile = 0;
+        }
+        self.active_profile = self.active_profile.min(self.profiles.len() - 1);
+    }
+
+    /// The latency offset of the current synth in milliseconds
+    pub fn latency_mut(&mut self) -> Option<&mut f64> {
+        match self.synth {
+            Synth::XSynth | Synth::XSynthRecording => Some(&mut self.latency.xsynth),
+            #[cfg(supported_os)]
+            Synth::Kdmapi => Some(&mut self.latency.kdmapi),
+            #[cfg(all(supported_os, not(target_os = "freebsd")))]
+            Synth::MidiDevice => Some(&mut self.latency.midi_device),
+            Synth::Network => Some(&mut self.latency.network),
+            Synth::None => None,
+        }
+    }
+
+    /// The latency offset of an output in milliseconds, `None` being the main synth
+    pub fn output_latency_mut(&mut self, output: Option<usize>) -> Option<&mut f64> {
+        match output {
+            None => self.latency_mut(),
+            Some(i) => self.outputs.get_mut(i).map(|output| &mut output.latency),
+        }
+    }
+
+    /// The latency offset of an output in seconds, `None` being the main synth
+    pub fn output_latency(&self, output: Option<usize>) -> f64 {
+        let latency = match output {
+            None => match self.synth {
+                Synth::XSynth | Synth::XSynthRecording => self.latency.xsynth,
+                #[cfg(supported_os)]
+                Synth::Kdmapi => self.latency.kdmapi,
+                #[cfg(all(supported_os, not(target_os = "freebsd")))]
+                Synth::MidiDevice => self.latency.midi_device,
+                Synth::Network => self.latency.network,
+                Synth::None => 0.0,
+            },
+            Some(i) => self.outputs.get(i).map_or(0.0, |output| output.latency),
+        };
+        latency / 1000.0
+    }
 }
 
 impl Default for SynthSettings {
     fn default() -> Self {
         Self {
             synth: Synth::XSynth,
-            soundfonts: Vec::new(),
+            legacy_soundfonts: Vec::new(),
+            profiles: vec![SoundfontProfile::default()],
+            active_profile: 0,
             xsynth: Default::default(),
             kdmapi: Default::default(),
             midi_device: String::new(),
+            midi_device_backpressure: Default::default(),
+            network: Default::default(),
+            latency: Default::default(),
+            mixer: Default::default(),
+            limiter: Default::default(),
+            routing: Default::default(),
+            outputs: Vec::new(),
         }
     }
 }
@@ -200,9 +531,8 @@ impl WasabiSettings {
                 let offset = Self::VERSION_TEXT.len();
                 match serde_json::from_str::<WasabiSettings>(&config[offset..]) {
                     Ok(mut config) => {
-                        if config.scene.statistics.order.len() != Statistics::iter().len() {
-                            config.scene.statistics.order = StatisticsSettings::default().order;
-                        }
+                        config.scene.statistics.add_missing();
+                        config.synth.migrate_profiles();
                         return Ok(config);
                     }
                     Err(e) => err = WasabiError::SettingsError(e.to_string()),
@@ -276,6 +606,16 @@ impl WasabiSettings {
         path
     }
 
+    pub fn get_recordings_dir() -> PathBuf {
+        let mut path = UserDirs::new()
+            .and_then(|dirs| dirs.audio_dir().map(|dir| dir.to_path_buf()))
+            .unwrap_or_else(Self::get_config_dir);
+        path.push("Wasabi Recordings");
+        std::fs::create_dir_all(&path).unwrap_or_default();
+
+        path
+    }
+
     pub fn get_palettes_dir() -> PathBuf {
         let mut path = Self::get_config_dir();
         path.push("palettes");
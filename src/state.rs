@@ -30,6 +30,11 @@ pub struct WasabiState {
     pub show_settings: bool,
     pub show_shortcuts: bool,
     pub show_about: bool,
+    pub show_layers: bool,
+    pub show_markers: bool,
+    pub show_bookmarks: bool,
+    pub show_calibration: bool,
+    pub show_mixer: bool,
 
     pub settings_tab: SettingsTab,
 
@@ -58,6 +63,11 @@ impl WasabiState {
             show_settings: false,
             show_shortcuts: false,
             show_about: false,
+            show_layers: false,
+            show_markers: false,
+            show_bookmarks: false,
+            show_calibration: false,
+            show_mixer: false,
 
             settings_tab: SettingsTab::default(),
 
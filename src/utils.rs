@@ -1,11 +1,13 @@
 use reqwest::blocking::ClientBuilder;
 use serde_json::Value;
 use std::thread;
-use std::{collections::HashMap, ops::RangeInclusive};
+use std::{
+    collections::HashMap,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+};
 
-#[cfg(supported_os)]
-use crate::settings::WasabiSoundfont;
-use crate::{gui::window::WasabiError, state::WasabiState};
+use crate::{gui::window::WasabiError, settings::WasabiSoundfont, state::WasabiState};
 
 pub const WIN_MARGIN: egui::Margin = egui::Margin::same(12);
 pub const NOTE_SPEED_RANGE: RangeInclusive<f64> = 10.0..=0.01;
@@ -76,7 +78,6 @@ pub fn check_for_updates(state: &WasabiState) {
     });
 }
 
-#[cfg(supported_os)]
 pub fn create_om_sf_list(list: &[WasabiSoundfont]) -> String {
     let mut out = String::new();
 
@@ -103,7 +104,192 @@ pub fn create_om_sf_list(list: &[WasabiSoundfont]) -> String {
     out
 }
 
-#[cfg(supported_os)]
+/// Creates a list in the plain `.sflist` format, with the disabled soundfonts
+/// prefixed by `@`. Paths that aren't valid UTF-8 can't be written, so they are
+/// skipped and returned alongside the list.
+pub fn create_sflist(list: &[WasabiSoundfont]) -> (String, Vec<PathBuf>) {
+    let mut out = String::new();
+    let mut skipped = Vec::new();
+
+    for sf in list {
+        let Some(path) = sf.path.to_str() else {
+            skipped.push(sf.path.clone());
+            continue;
+        };
+
+        if !sf.enabled {
+            out += "@";
+        }
+        if let (Some(bank), Some(preset)) = (sf.options.bank, sf.options.preset) {
+            out += &format!("p{bank},{preset}={bank},{preset}|");
+        }
+        out += path;
+        out += "\n";
+    }
+
+    (out, skipped)
+}
+
+/// Parses a bank or preset number, where -1 means all of them
+fn parse_sf_number(value: &str) -> Option<u8> {
+    value
+        .trim()
+        .parse::<i32>()
+        .ok()
+        .and_then(|n| u8::try_from(n).ok())
+}
+
+fn resolve_sf_path(path: &str, base: &Path) -> PathBuf {
+    let path = PathBuf::from(path.trim().trim_matches('"'));
+    if path.is_relative() {
+        base.join(path)
+    } else {
+        path
+    }
+}
+
+/// Reads a soundfont list in the OmniMIDI format, or in the plain or JSON `.sflist`
+/// format. Relative paths are resolved from `base`, the folder of the list.
+pub fn parse_sf_list(content: &str, base: &Path) -> Result<Vec<WasabiSoundfont>, WasabiError> {
+    if content.trim_start().starts_with('{') {
+        parse_json_sflist(content, base)
+    } else if content.lines().any(|line| line.trim() == "sf.start") {
+        Ok(parse_om_sf_list(content, base))
+    } else {
+        Ok(parse_sflist(content, base))
+    }
+}
+
+fn parse_om_sf_list(content: &str, base: &Path) -> Vec<WasabiSoundfont> {
+    let mut out = Vec::new();
+    let mut current: Option<WasabiSoundfont> = None;
+
+    for line in content.lines().map(str::trim) {
+        match line {
+            "sf.start" => {
+                current = Some(WasabiSoundfont {
+                    enabled: true,
+                    ..Default::default()
+                })
+            }
+            "sf.end" => {
+                if let Some(mut sf) = current.take() {
+                    if !sf.path.as_os_str().is_empty() {
+                        if sf.options.bank.is_none() || sf.options.preset.is_none() {
+                            sf.options.bank = None;
+                            sf.options.preset = None;
+                        }
+                        out.push(sf);
+                    }
+                }
+            }
+            _ => {
+                let (Some(sf), Some((key, value))) = (current.as_mut(), line.split_once('='))
+                else {
+                    continue;
+                };
+                match key.trim() {
+                    "sf.path" => sf.path = resolve_sf_path(value, base),
+                    "sf.enabled" => sf.enabled = value.trim() != "0",
+                    "sf.srcb" => sf.options.bank = parse_sf_number(value),
+                    "sf.srcp" => sf.options.preset = parse_sf_number(value),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    out
+}
+
+/// Lines are paths, optionally prefixed with a preset mapping like `p0,1=0,0|`.
+/// Lines starting with `@` are disabled soundfonts.
+fn parse_sflist(content: &str, base: &Path) -> Vec<WasabiSoundfont> {
+    let mut out = Vec::new();
+
+    for line in content.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (enabled, line) = match line.strip_prefix('@') {
+            Some(line) => (false, line),
+            None => (true, line),
+        };
+
+        let mut sf = WasabiSoundfont {
+            enabled,
+            ..Default::default()
+        };
+
+        let path = match line.split_once('|') {
+            Some((mapping, path)) if mapping.starts_with('p') => {
+                let source = mapping[1..].split('=').next().unwrap_or_default();
+                if let Some((bank, preset)) = source.split_once(',') {
+                    sf.options.bank = parse_sf_number(bank);
+                    sf.options.preset = parse_sf_number(preset);
+                }
+                path
+            }
+            _ => line,
+        };
+        sf.path = resolve_sf_path(path, base);
+
+        // Wasabi only supports overriding both at once
+        if sf.options.bank.is_none() || sf.options.preset.is_none() {
+            sf.options.bank = None;
+            sf.options.preset = None;
+        }
+
+        out.push(sf);
+    }
+
+    out
+}
+
+fn parse_json_sflist(content: &str, base: &Path) -> Result<Vec<WasabiSoundfont>, WasabiError> {
+    let json = serde_json::from_str::<Value>(content)
+        .map_err(|e| WasabiError::SettingsError(format!("Invalid soundfont list: {e}")))?;
+    let soundfonts = json
+        .get("soundFonts")
+        .and_then(Value::as_array)
+        .ok_or_else(|| WasabiError::SettingsError("The soundfont list is empty".into()))?;
+
+    Ok(soundfonts
+        .iter()
+        .filter_map(|entry| {
+            let mut sf = WasabiSoundfont {
+                path: resolve_sf_path(entry.get("fileName")?.as_str()?, base),
+                enabled: entry
+                    .get("enabled")
+                    .and_then(Value::as_bool)
+                    .unwrap_or(true),
+                ..Default::default()
+            };
+
+            let source = entry
+                .get("patchMappings")
+                .and_then(Value::as_array)
+                .and_then(|mappings| mappings.first())
+                .and_then(|mapping| mapping.get("source"));
+            if let Some(source) = source {
+                let number = |key: &str| {
+                    source
+                        .get(key)
+                        .and_then(Value::as_i64)
+                        .and_then(|n| u8::try_from(n).ok())
+                };
+                if let (Some(bank), Some(preset)) = (number("bank"), number("program")) {
+                    sf.options.bank = Some(bank);
+                    sf.options.preset = Some(preset);
+                }
+            }
+
+            Some(sf)
+        })
+        .collect())
+}
+
 pub fn create_reset_midi_messages() -> Vec<u32> {
     let mut out = Vec::new();
 
@@ -119,3 +305,181 @@ pub fn create_reset_midi_messages() -> Vec<u32> {
 
     out
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn soundfont(path: PathBuf, enabled: bool, mapping: Option<(u8, u8)>) -> WasabiSoundfont {
+        let mut sf = WasabiSoundfont {
+            path,
+            enabled,
+            ..Default::default()
+        };
+        sf.options.bank = mapping.map(|(bank, _)| bank);
+        sf.options.preset = mapping.map(|(_, preset)| preset);
+        sf
+    }
+
+    /// The parts of the soundfonts that the lists store
+    fn summary(list: &[WasabiSoundfont]) -> Vec<(PathBuf, bool, Option<u8>, Option<u8>)> {
+        list.iter()
+            .map(|sf| {
+                (
+                    sf.path.clone(),
+                    sf.enabled,
+                    sf.options.bank,
+                    sf.options.preset,
+                )
+            })
+            .collect()
+    }
+
+    fn example_list(base: &Path) -> Vec<WasabiSoundfont> {
+        vec![
+            soundfont(base.join("piano.sf2"), true, Some((0, 1))),
+            soundfont(base.join("strings.sfz"), false, None),
+            soundfont(base.join("drums.sf2"), false, Some((128, 0))),
+            soundfont(base.join("sub folder").join("gm.sf2"), true, None),
+        ]
+    }
+
+    #[test]
+    fn sflist_round_trip() {
+        let base = std::env::temp_dir();
+        let list = example_list(&base);
+
+        let (content, skipped) = create_sflist(&list);
+        assert!(skipped.is_empty());
+        assert!(content.lines().nth(1).unwrap().starts_with('@'));
+        assert!(content
+            .lines()
+            .nth(2)
+            .unwrap()
+            .starts_with("@p128,0=128,0|"));
+
+        let parsed = parse_sf_list(&content, &base).unwrap();
+        assert_eq!(summary(&parsed), summary(&list));
+    }
+
+    #[test]
+    fn omnimidi_list_round_trip() {
+        let base = std::env::temp_dir();
+        let list = example_list(&base);
+
+        let content = create_om_sf_list(&list);
+        let parsed = parse_sf_list(&content, &base).unwrap();
+        assert_eq!(summary(&parsed), summary(&list));
+    }
+
+    #[test]
+    fn parses_sflist() {
+        let base = Path::new("lists");
+        let content = "# comment\n\
+            \n\
+            piano.sf2\n\
+            @  strings.sfz  \n\
+            p0,1=0,0|drums.sf2\n\
+            @p-1,5=0,0|\"quoted.sf2\"\n";
+
+        let parsed = parse_sf_list(content, base).unwrap();
+        assert_eq!(
+            summary(&parsed),
+            vec![
+                (base.join("piano.sf2"), true, None, None),
+                (base.join("strings.sfz"), false, None, None),
+                (base.join("drums.sf2"), true, Some(0), Some(1)),
+                // Only overriding both the bank and preset is supported
+                (base.join("quoted.sf2"), false, None, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_omnimidi_list() {
+        let base = Path::new("lists");
+        let content = "sf.start\n\
+            sf.path = piano.sf2\n\
+            sf.enabled = 0\n\
+            sf.srcb = 0\n\
+            sf.srcp = 1\n\
+            sf.end\n\
+            \n\
+            sf.start\n\
+            sf.path = strings.sfz\n\
+            sf.srcb = -1\n\
+            sf.srcp = 3\n\
+            sf.end\n\
+            \n\
+            sf.start\n\
+            sf.enabled = 1\n\
+            sf.end\n";
+
+        let parsed = parse_sf_list(content, base).unwrap();
+        assert_eq!(
+            summary(&parsed),
+            vec![
+                (base.join("piano.sf2"), false, Some(0), Some(1)),
+                (base.join("strings.sfz"), true, None, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_json_sflist() {
+        let base = Path::new("lists");
+        let content = r#"{
+            "soundFonts": [
+                {
+                    "fileName": "piano.sf2",
+                    "enabled": false,
+                    "patchMappings": [
+                        { "source": { "bank": 0, "program": 1 } }
+                    ]
+                },
+                { "fileName": "strings.sfz" },
+                {
+                    "fileName": "drums.sf2",
+                    "patchMappings": [
+                        { "source": { "bank": -1, "program": 0 } }
+                    ]
+                },
+                { "enabled": true }
+            ]
+        }"#;
+
+        let parsed = parse_sf_list(content, base).unwrap();
+        assert_eq!(
+            summary(&parsed),
+            vec![
+                (base.join("piano.sf2"), false, Some(0), Some(1)),
+                (base.join("strings.sfz"), true, None, None),
+                (base.join("drums.sf2"), true, None, None),
+            ]
+        );
+
+        assert!(parse_sf_list("{ \"soundFonts\": ", base).is_err());
+        assert!(parse_sf_list("{}", base).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn sflist_skips_non_utf8_paths() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        let base = std::env::temp_dir();
+        let invalid = base.join(OsStr::from_bytes(b"invalid \xFF.sf2"));
+        let list = vec![
+            soundfont(invalid.clone(), true, None),
+            soundfont(base.join("piano.sf2"), true, None),
+        ];
+
+        let (content, skipped) = create_sflist(&list);
+        assert_eq!(skipped, vec![invalid]);
+        assert_eq!(content.lines().count(), 1);
+        assert_eq!(
+            summary(&parse_sf_list(&content, &base).unwrap()),
+            summary(&list[1..])
+        );
+    }
+}